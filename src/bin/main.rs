@@ -8,28 +8,87 @@ extern crate tokio;
 extern crate log;
 extern crate pretty_env_logger;
 
+use std::env as std_env;
+use std::fs;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::process;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
 
 use futures::Async;
 use futures::future::Future;
 
 use lmdb_zero::open::Flags;
 
+use nimiq::consensus::base::account;
+use nimiq::consensus::base::blockchain::{Blockchain, ConsistencyReport, ImportProgress, import_blocks};
+use nimiq::consensus::base::blockchain::consistency;
+use nimiq::consensus::base::blockchain::snapshot;
+use nimiq::consensus::genesis;
 use nimiq::consensus::networks::NetworkId;
+use nimiq::consensus::policy;
+use nimiq::network::NetworkTime;
 use nimiq::network::network_config::NetworkConfig;
-use nimiq::utils::db::Environment;
+use nimiq::utils::db::{Environment, ReadTransaction};
 use nimiq::utils::db::lmdb::LmdbEnvironment;
-use nimiq::network::network::Network;
+use nimiq::utils::services::Services;
 use nimiq::consensus::consensus::Consensus;
 
+const DB_PATH: &str = "./db/";
+
 lazy_static! {
-    static ref env: Environment = LmdbEnvironment::new("./db/", 1024 * 1024 * 50, 10, Flags::empty()).unwrap(); //VolatileEnvironment::new(10).unwrap();
+    static ref env: Environment = LmdbEnvironment::new(DB_PATH, 1024 * 1024 * 50, 10, Flags::empty()).unwrap(); //VolatileEnvironment::new(10).unwrap();
 }
 
 pub fn main() {
     pretty_env_logger::try_init().unwrap_or(());
 
-    let network_id = NetworkId::Main;
+    let mut args: Vec<String> = std_env::args().collect();
+
+    // Run a network defined by a genesis file: --genesis <file>
+    let network_id = match args.iter().position(|arg| arg == "--genesis") {
+        Some(i) => {
+            let path = args.get(i + 1).unwrap_or_else(|| usage()).clone();
+            args.drain(i..i + 2);
+            match genesis::load_network(&path) {
+                Ok(network_info) => network_info.network_id,
+                Err(e) => {
+                    error!("Failed to load genesis file {}: {}", path, e);
+                    process::exit(1);
+                }
+            }
+        },
+        None => NetworkId::Main,
+    };
+
+    match args.get(1).map(String::as_str) {
+        Some("export") => return export(network_id, &args[2..]),
+        Some("import") => return import(network_id, &args[2..]),
+        Some("check") => return check(network_id),
+        Some("repair") => return repair(network_id),
+        Some("snapshot") => return create_snapshot(network_id, &args[2..]),
+        Some("export-accounts") => return export_accounts(network_id, &args[2..]),
+        Some("import-accounts") => return import_accounts(network_id, &args[2..]),
+        Some("audit") => return audit(network_id),
+        Some("genesis") => return generate_genesis(&args[2..]),
+        _ => {}
+    }
+
+    // Seed an empty database from a snapshot: --from-snapshot <dir>
+    // This has to happen before the environment is opened for the first time.
+    if let Some(i) = args.iter().position(|arg| arg == "--from-snapshot") {
+        let snapshot_dir = args.get(i + 1).unwrap_or_else(|| usage());
+        match snapshot::restore_snapshot(snapshot_dir, DB_PATH, network_id) {
+            Ok(manifest) => info!("Restored snapshot of #{} ({}) from {}", manifest.height, manifest.head_hash, snapshot_dir),
+            Err(e) => {
+                error!("Failed to restore snapshot: {}", e);
+                process::exit(1);
+            }
+        }
+    }
 
     let mut network_config = NetworkConfig::new_ws_network_config(
         "test.vcap.me".to_string(),
@@ -38,20 +97,255 @@ pub fn main() {
     );
     network_config.init_volatile();
 
+    // Pruning mode: --prune <depth>
+    let body_pruning_depth = args.iter().position(|arg| arg == "--prune")
+        .map(|i| args.get(i + 1).and_then(|depth| depth.parse::<u32>().ok()).unwrap_or_else(|| usage()));
+    if let Some(depth) = body_pruning_depth {
+        if depth < policy::TRANSACTION_VALIDITY_WINDOW {
+            eprintln!("--prune expects a depth of at least {} blocks", policy::TRANSACTION_VALIDITY_WINDOW);
+            usage();
+        }
+    }
+    if body_pruning_depth.is_some() {
+        network_config.set_services(Services::pruned());
+    }
+
     info!("Nimiq Core starting: network={:?}, peer_address={}", network_id, network_config.peer_address());
 
-    let consensus = Consensus::new(&env, network_id, network_config);
+    let consensus = Consensus::new(&env, network_id, network_config).unwrap_or_else(|e| {
+        error!("Failed to open blockchain: {}", e);
+        process::exit(1);
+    });
+    consensus.set_body_pruning_depth(body_pruning_depth);
 
     info!("Blockchain state: height={}, head={}", consensus.blockchain.height(), consensus.blockchain.head_hash());
 
+    handle_shutdown_signals(consensus.clone());
+
     tokio::run(Runner {
-        network: consensus.network.clone(),
+        consensus: consensus.clone(),
         initialized: false,
     });
 }
 
+/// `export <file> [<from> [<to>]]`: Writes the main chain to a bootstrap file.
+fn export(network_id: NetworkId, args: &[String]) {
+    let path = args.get(0).unwrap_or_else(|| usage());
+    let blockchain = open_blockchain(network_id);
+    let from = args.get(1).map(|from| from.parse::<u32>().unwrap_or_else(|_| usage())).unwrap_or(1);
+    let to = args.get(2).map(|to| to.parse::<u32>().unwrap_or_else(|_| usage())).unwrap_or(blockchain.height());
+
+    let mut writer = BufWriter::new(File::create(path).expect("Failed to create bootstrap file"));
+    match blockchain.export_range(from, to, &mut writer) {
+        Ok(num_blocks) => info!("Exported {} blocks (#{} to #{}) to {}", num_blocks, from, to, path),
+        Err(e) => {
+            error!("Export failed: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+/// `import <file>`: Pushes the blocks of a bootstrap file into the local chain.
+fn import(network_id: NetworkId, args: &[String]) {
+    let path = args.get(0).unwrap_or_else(|| usage());
+    let blockchain = open_blockchain(network_id);
+
+    let mut reader = BufReader::new(File::open(path).expect("Failed to open bootstrap file"));
+    let result = import_blocks(&blockchain, &mut reader, |progress: &ImportProgress| {
+        if progress.num_blocks % 1000 == 0 {
+            info!("Imported {} blocks, now at #{}", progress.num_blocks, progress.height);
+        }
+    });
+    match result {
+        Ok(progress) => info!("Imported {} blocks ({} already known), head is #{}", progress.num_blocks, progress.num_known, blockchain.height()),
+        Err(e) => {
+            error!("Import failed: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+/// `export-accounts <file>`: Writes all accounts after the current head to a file.
+fn export_accounts(network_id: NetworkId, args: &[String]) {
+    let path = args.get(0).unwrap_or_else(|| usage());
+    let blockchain = open_blockchain(network_id);
+
+    let mut writer = BufWriter::new(File::create(path).expect("Failed to create accounts file"));
+    match blockchain.export_accounts(&mut writer) {
+        Ok(header) => info!("Exported accounts at #{} ({}) to {}", header.block_height, header.block_hash, path),
+        Err(e) => {
+            error!("Export failed: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+/// `import-accounts <file>`: Replaces the local accounts with those of a file exported at the current head.
+fn import_accounts(network_id: NetworkId, args: &[String]) {
+    let path = args.get(0).unwrap_or_else(|| usage());
+
+    let mut reader = BufReader::new(File::open(path).expect("Failed to open accounts file"));
+    match account::import_accounts(&env, network_id, &mut reader) {
+        Ok(header) => info!("Imported accounts at #{} ({}) from {}", header.block_height, header.block_hash, path),
+        Err(e) => {
+            error!("Import failed: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+/// `audit`: Prints account statistics and checks the total balance against the expected supply.
+fn audit(network_id: NetworkId) {
+    let blockchain = open_blockchain(network_id);
+    let audit = blockchain.audit_supply();
+
+    {
+        let accounts = blockchain.accounts();
+        let txn = ReadTransaction::new(&env);
+        for (account_type, count) in accounts.count_by_type(&txn) {
+            info!("{:?} accounts: {}", account_type, count);
+        }
+        for (address, account) in accounts.top_balances(&txn, 10) {
+            info!("{}: {}", address, u64::from(account.balance()));
+        }
+    }
+
+    if audit.is_consistent() {
+        info!("{}", audit);
+    } else {
+        error!("{}", audit);
+        process::exit(1);
+    }
+}
+
+/// `check`: Verifies the main chain and the accounts tree without modifying the database.
+fn check(network_id: NetworkId) {
+    let report = consistency::check_consistency(&env, network_id);
+    print_report(&report);
+    if !report.is_consistent() {
+        if report.is_repairable() {
+            info!("Run `nimiq repair` to fix the database");
+        }
+        process::exit(1);
+    }
+}
+
+/// `repair`: Fixes main chain flags and rebuilds the accounts tree from the stored blocks.
+fn repair(network_id: NetworkId) {
+    match consistency::repair(&env, network_id) {
+        Ok(report) => {
+            print_report(&report);
+            if !report.is_consistent() {
+                process::exit(1);
+            }
+        },
+        Err(e) => {
+            error!("Repair failed: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+/// `snapshot <dir>`: Copies the database into an empty directory while the node may keep running.
+fn create_snapshot(network_id: NetworkId, args: &[String]) {
+    let dir = args.get(0).unwrap_or_else(|| usage());
+    match snapshot::create_snapshot(&env, network_id, dir) {
+        Ok(manifest) => info!("Created snapshot of #{} ({}) in {}", manifest.height, manifest.head_hash, dir),
+        Err(e) => {
+            error!("Snapshot failed: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+/// `genesis <accounts file> <network id> <name> <timestamp> <n_bits> <output file>`: Creates the genesis
+/// block of a new network and writes a genesis file that can be loaded with `--genesis`.
+/// Passing `dev` instead of `<n_bits>` creates a development network.
+fn generate_genesis(args: &[String]) {
+    if args.len() != 6 {
+        usage();
+    }
+    let accounts_definition = fs::read_to_string(&args[0]).expect("Failed to read genesis accounts");
+    let network_id = NetworkId::from(args[1].parse::<u8>().unwrap_or_else(|_| usage()));
+    let timestamp = args[3].parse::<u32>().unwrap_or_else(|_| usage());
+    let n_bits = match args[4].as_str() {
+        "dev" => None,
+        n_bits => Some(u32::from_str_radix(n_bits.trim_start_matches("0x"), 16).unwrap_or_else(|_| usage())),
+    };
+
+    let result = genesis::parse_genesis_accounts(&accounts_definition)
+        .and_then(|accounts| match n_bits {
+            Some(n_bits) => genesis::generate_genesis(network_id, args[2].clone(), accounts, timestamp, n_bits.into(), Vec::new()),
+            None => genesis::generate_dev_genesis(network_id, args[2].clone(), accounts, timestamp),
+        })
+        .and_then(|config| config.write(&args[5]).map(|_| config));
+    match result {
+        Ok(config) => info!("Created genesis block {} (nonce {}) for network {:?} in {}", config.hash, config.block.header.nonce, network_id, args[5]),
+        Err(e) => {
+            error!("Failed to create genesis block: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+fn print_report(report: &ConsistencyReport) {
+    for inconsistency in report.inconsistencies.iter() {
+        warn!("{}", inconsistency);
+    }
+    if report.is_consistent() {
+        info!("Database is consistent, head is #{}", report.head_height);
+    }
+}
+
+fn open_blockchain(network_id: NetworkId) -> Blockchain<'static> {
+    Blockchain::new(&env, network_id, Arc::new(NetworkTime::new())).unwrap_or_else(|e| {
+        error!("Failed to open blockchain: {}", e);
+        process::exit(1);
+    })
+}
+
+fn usage() -> ! {
+    eprintln!("Usage: nimiq [--genesis <file>] [--prune <depth>] [--from-snapshot <dir>]");
+    eprintln!("       nimiq export <file> [<from> [<to>]]");
+    eprintln!("       nimiq import <file>");
+    eprintln!("       nimiq export-accounts <file>");
+    eprintln!("       nimiq import-accounts <file>");
+    eprintln!("       nimiq audit");
+    eprintln!("       nimiq check");
+    eprintln!("       nimiq repair");
+    eprintln!("       nimiq snapshot <dir>");
+    eprintln!("       nimiq genesis <accounts file> <network id> <name> <timestamp> <n_bits hex|dev> <output file>");
+    eprintln!("Commands run on the main network unless --genesis <file> is given.");
+    process::exit(1);
+}
+
+static SHUTDOWN_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_shutdown_signal(_: libc::c_int) {
+    // Only async-signal-safe work here, the actual shutdown happens on the watcher thread.
+    SHUTDOWN_REQUESTED.store(true, Ordering::SeqCst);
+}
+
+/// Shuts the consensus down (persisting the mempool) and exits on SIGINT or SIGTERM.
+fn handle_shutdown_signals(consensus: Arc<Consensus>) {
+    let handler = on_shutdown_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    unsafe {
+        libc::signal(libc::SIGINT, handler);
+        libc::signal(libc::SIGTERM, handler);
+    }
+
+    thread::spawn(move || {
+        while !SHUTDOWN_REQUESTED.load(Ordering::SeqCst) {
+            thread::sleep(Duration::from_millis(100));
+        }
+        info!("Shutting down");
+        consensus.shutdown();
+        process::exit(0);
+    });
+}
+
 pub struct Runner {
-    network: Arc<Network>,
+    consensus: Arc<Consensus>,
     initialized: bool,
 }
 
@@ -61,8 +355,9 @@ impl Future for Runner {
 
     fn poll(&mut self) -> Result<Async<<Self as Future>::Item>, <Self as Future>::Error> {
         if !self.initialized {
-            self.network.initialize();
-            self.network.connect();
+            self.consensus.initialize();
+            self.consensus.network.initialize();
+            self.consensus.network.connect();
             self.initialized = true;
         }
         Ok(Async::Ready(()))
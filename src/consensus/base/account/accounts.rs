@@ -1,8 +1,7 @@
-use beserial::Deserialize;
-use hex;
-use std::collections::HashMap;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, BTreeMap, HashMap};
 use crate::consensus::base::account::{Account, AccountType, AccountError};
-use crate::consensus::base::account::tree::AccountsTree;
+use crate::consensus::base::account::tree::{AccountsTree, AccountsTreeBatch, AccountsTreeIter};
 use crate::consensus::base::block::{Block, BlockBody};
 use crate::consensus::base::primitive::{Address, Coin};
 use crate::consensus::base::primitive::hash::Blake2bHash;
@@ -10,7 +9,7 @@ use crate::consensus::base::transaction::{Transaction, TransactionFlags};
 use crate::consensus::networks::{NetworkId, get_network_info};
 use crate::consensus::policy;
 use crate::utils::db;
-use crate::utils::db::{Environment, ReadTransaction, WriteTransaction};
+use crate::utils::db::{Environment, ReadOnlyEnvironment, ReadTransaction, WriteTransaction};
 
 #[derive(Debug)]
 pub struct Accounts<'env> {
@@ -23,28 +22,43 @@ impl<'env> Accounts<'env> {
         return Accounts { env, tree: AccountsTree::new(env) };
     }
 
+    /// Opens accounts that were initialized before. Unlike `new`, this doesn't write to `env`.
+    pub fn open(env: &'env Environment) -> Self {
+        return Accounts { env, tree: AccountsTree::open(env) };
+    }
+
+    /// Opens the accounts of a read-only environment. The accounts must have been initialized before.
+    pub fn new_read_only(env: &'env ReadOnlyEnvironment) -> ReadOnlyAccounts<'env> {
+        return ReadOnlyAccounts { env, tree: AccountsTree::new_read_only(env) };
+    }
+
     pub fn init(&self, txn: &mut WriteTransaction, network_id: NetworkId) {
         let network_info = get_network_info(network_id).unwrap();
-        let account_bytes = hex::decode(&network_info.genesis_accounts).unwrap();
-        let reader = &mut &account_bytes[..];
-        let count = u16::deserialize(reader).unwrap();
-
-        for i in 0..count {
-            let address = Address::deserialize(reader).unwrap();
-            let account = Account::deserialize(reader).unwrap();
-            self.tree.put_batch(txn, &address, account);
-        }
-        self.tree.finalize_batch(txn);
-
         let genesis_header = &network_info.genesis_block.header;
         let genesis_body = network_info.genesis_block.body.as_ref().unwrap();
-        self.commit_block_body(txn, genesis_body, genesis_header.height)
+        self.commit_genesis(txn, &network_info.genesis_accounts, genesis_body)
             .expect("Failed to commit genesis block body");
 
         assert_eq!(self.tree.root_hash(txn), genesis_header.accounts_hash,
                    "Genesis AccountHash mismatch");
     }
 
+    /// Puts the genesis accounts and applies the body of the genesis block on top of them.
+    pub fn commit_genesis(&self, txn: &mut WriteTransaction, genesis_accounts: &[(Address, Account)], genesis_body: &BlockBody) -> Result<(), AccountError> {
+        let mut batch = self.tree.batch();
+        for (address, account) in genesis_accounts.iter() {
+            batch.put(txn, address, account.clone());
+        }
+        batch.finalize(txn);
+
+        return self.commit_block_body(txn, genesis_body, 1);
+    }
+
+    /// Removes all accounts. `init` must be called afterwards to restore the genesis state.
+    pub fn clear(&self, txn: &mut WriteTransaction) {
+        self.tree.clear(txn);
+    }
+
     pub fn get(&self, address: &Address, txn_option: Option<&db::Transaction>) -> Account {
         return match txn_option {
             Some(txn) => self.tree.get(txn, address),
@@ -59,6 +73,45 @@ impl<'env> Accounts<'env> {
         };
     }
 
+    /// Iterates over all non-initial accounts in ascending order of their addresses.
+    pub fn iter<'txn>(&'txn self, txn: &'txn db::Transaction<'env>) -> AccountsTreeIter<'txn, 'env> {
+        return self.tree.iter(txn);
+    }
+
+    /// Iterates over all non-initial accounts whose address starts with `prefix`.
+    pub fn iter_prefix<'txn>(&'txn self, txn: &'txn db::Transaction<'env>, prefix: &[u8]) -> AccountsTreeIter<'txn, 'env> {
+        return self.tree.iter_prefix(txn, prefix);
+    }
+
+    pub fn total_balance(&self, txn: &db::Transaction<'env>) -> Coin {
+        return self.iter(txn).fold(Coin::ZERO, |total, (_, account)| total + account.balance());
+    }
+
+    pub fn count_by_type(&self, txn: &db::Transaction<'env>) -> BTreeMap<AccountType, usize> {
+        let mut counts = BTreeMap::new();
+        for (_, account) in self.iter(txn) {
+            *counts.entry(account.account_type()).or_insert(0) += 1;
+        }
+        return counts;
+    }
+
+    /// Returns the `n` accounts with the highest balances, highest first.
+    pub fn top_balances(&self, txn: &db::Transaction<'env>, n: usize) -> Vec<(Address, Account)> {
+        // Min-heap of the best accounts seen so far.
+        let mut heap = BinaryHeap::with_capacity(n + 1);
+        for (address, account) in self.iter(txn) {
+            heap.push(Reverse((account.balance(), address, account)));
+            if heap.len() > n {
+                heap.pop();
+            }
+        }
+
+        // Ascending order of Reverse is descending order of balances.
+        return heap.into_sorted_vec().into_iter()
+            .map(|Reverse((_, address, account))| (address, account))
+            .collect();
+    }
+
     pub fn hash_with_block_body(&self, body: &BlockBody, block_height: u32) -> Result<Blake2bHash, AccountError> {
         let mut txn = WriteTransaction::new(self.env);
 
@@ -92,9 +145,51 @@ impl<'env> Accounts<'env> {
     }
 
     pub fn commit_block_body(&self, txn: &mut WriteTransaction, body: &BlockBody, block_height: u32) -> Result<(), AccountError> {
+        // On error, the batch is dropped with the changes staged before the error.
+        let mut batch = self.tree.batch();
+        self.commit_block_body_batch(&mut batch, txn, body, block_height)?;
+        batch.finalize(txn);
+        return Ok(());
+    }
+
+    pub fn revert_block_body(&self, txn: &mut WriteTransaction, body: &BlockBody, block_height: u32) -> Result<(), AccountError> {
+        let mut batch = self.tree.batch();
+        self.revert_block_body_batch(&mut batch, txn, body, block_height)?;
+        batch.finalize(txn);
+        return Ok(());
+    }
+
+    /// Applies a single transaction outside of a block, i.e. without miner reward and pruning.
+    /// Either the whole transaction is applied or nothing.
+    pub fn commit_transaction(&self, txn: &mut WriteTransaction, transaction: &Transaction, block_height: u32) -> Result<(), AccountError> {
+        let mut batch = self.tree.batch();
+        self.commit_transaction_batch(&mut batch, txn, transaction, block_height)?;
+        batch.finalize(txn);
+        return Ok(());
+    }
+
+    fn commit_transaction_batch(&self, batch: &mut AccountsTreeBatch, txn: &WriteTransaction, transaction: &Transaction, block_height: u32) -> Result<(), AccountError> {
+        self.process_transaction(batch, txn, &transaction.sender, Some(transaction.sender_type), transaction, block_height,
+                                 |account, transaction, block_height| account.with_outgoing_transaction(transaction, block_height))?;
+
+        let is_contract_creation = transaction.flags.contains(TransactionFlags::CONTRACT_CREATION);
+        let recipient_type = match is_contract_creation {
+            true => None,
+            false => Some(transaction.recipient_type)
+        };
+        self.process_transaction(batch, txn, &transaction.recipient, recipient_type, transaction, block_height,
+                                 |account, transaction, block_height| account.with_incoming_transaction(transaction, block_height))?;
+
+        if is_contract_creation {
+            self.create_contract(batch, txn, transaction, block_height)?;
+        }
+        return Ok(());
+    }
+
+    fn commit_block_body_batch(&self, batch: &mut AccountsTreeBatch, txn: &WriteTransaction, body: &BlockBody, block_height: u32) -> Result<(), AccountError> {
         // Process sender accounts.
         for transaction in &body.transactions {
-            self.process_transaction(txn, &transaction.sender, Some(transaction.sender_type), transaction, block_height,
+            self.process_transaction(batch, txn, &transaction.sender, Some(transaction.sender_type), transaction, block_height,
                                      |account, transaction, block_height| account.with_outgoing_transaction(transaction, block_height))?;
         }
 
@@ -104,37 +199,36 @@ impl<'env> Accounts<'env> {
                 true => None,
                 false => Some(transaction.recipient_type)
             };
-            self.process_transaction(txn, &transaction.recipient, recipient_type, transaction, block_height,
+            self.process_transaction(batch, txn, &transaction.recipient, recipient_type, transaction, block_height,
                                      |account, transaction, block_height| account.with_incoming_transaction(transaction, block_height))?;
         }
 
         // Create contracts.
         for transaction in &body.transactions {
             if transaction.flags.contains(TransactionFlags::CONTRACT_CREATION) {
-                self.create_contract(txn, transaction, block_height)?;
+                self.create_contract(batch, txn, transaction, block_height)?;
             }
         }
 
-        self.prune_accounts(txn, body)?;
+        self.prune_accounts(batch, txn, body)?;
 
-        self.process_miner_reward(txn, body, block_height,
+        self.process_miner_reward(batch, txn, body, block_height,
                                   |account, transaction, block_height| account.with_incoming_transaction(transaction, block_height))?;
 
-        self.tree.finalize_batch(txn);
         return Ok(());
     }
 
-    pub fn revert_block_body(&self, txn: &mut WriteTransaction, body: &BlockBody, block_height: u32) -> Result<(), AccountError> {
-        self.process_miner_reward(txn, body, block_height,
+    fn revert_block_body_batch(&self, batch: &mut AccountsTreeBatch, txn: &WriteTransaction, body: &BlockBody, block_height: u32) -> Result<(), AccountError> {
+        self.process_miner_reward(batch, txn, body, block_height,
                                   |account, transaction, block_height| account.without_incoming_transaction(transaction, block_height))?;
 
         // Restore pruned accounts.
-        self.restore_accounts(txn, body)?;
+        self.restore_accounts(batch, txn, body)?;
 
         // Revert created contracts.
         for transaction in &body.transactions {
             if transaction.flags.contains(TransactionFlags::CONTRACT_CREATION) {
-                self.revert_contract(txn, transaction, block_height)?;
+                self.revert_contract(batch, txn, transaction, block_height)?;
             }
         }
 
@@ -144,24 +238,23 @@ impl<'env> Accounts<'env> {
                 true => None,
                 false => Some(transaction.recipient_type)
             };
-            self.process_transaction(txn, &transaction.recipient, recipient_type, transaction, block_height,
+            self.process_transaction(batch, txn, &transaction.recipient, recipient_type, transaction, block_height,
                                      |account, transaction, block_height| account.without_incoming_transaction(transaction, block_height))?;
         }
 
         // Process sender accounts.
         for transaction in &body.transactions {
-            self.process_transaction(txn, &transaction.sender, Some(transaction.sender_type), transaction, block_height,
+            self.process_transaction(batch, txn, &transaction.sender, Some(transaction.sender_type), transaction, block_height,
                                      |account, transaction, block_height| account.without_outgoing_transaction(transaction, block_height))?;
         }
 
-        self.tree.finalize_batch(txn);
         return Ok(());
     }
 
-    fn process_transaction<F>(&self, txn: &mut WriteTransaction, address: &Address, account_type: Option<AccountType>, transaction: &Transaction, block_height: u32, account_op: F) -> Result<(), AccountError>
+    fn process_transaction<F>(&self, batch: &mut AccountsTreeBatch, txn: &WriteTransaction, address: &Address, account_type: Option<AccountType>, transaction: &Transaction, block_height: u32, account_op: F) -> Result<(), AccountError>
         where F: Fn(Account, &Transaction, u32) -> Result<Account, AccountError> {
 
-        let account = self.get(address, Some(txn));
+        let account = batch.get(txn, address).unwrap_or(Account::INITIAL);
 
         // Check account type.
         if account_type.is_some() && account.account_type() != account_type.unwrap() {
@@ -169,11 +262,11 @@ impl<'env> Accounts<'env> {
         }
 
         let new_account = account_op(account, transaction, block_height)?;
-        self.tree.put_batch(txn, address, new_account);
+        batch.put(txn, address, new_account);
         return Ok(());
     }
 
-    fn process_miner_reward<F>(&self, txn: &mut WriteTransaction, body: &BlockBody, block_height: u32, account_op: F) -> Result<(), AccountError>
+    fn process_miner_reward<F>(&self, batch: &mut AccountsTreeBatch, txn: &WriteTransaction, body: &BlockBody, block_height: u32, account_op: F) -> Result<(), AccountError>
         where F: Fn(Account, &Transaction, u32) -> Result<Account, AccountError> {
 
         // Sum up transaction fees.
@@ -192,39 +285,39 @@ impl<'env> Accounts<'env> {
             NetworkId::Main, // XXX ignored
         );
 
-        return self.process_transaction(txn, &body.miner, Some(AccountType::Basic), &coinbase_tx, block_height, account_op);
+        return self.process_transaction(batch, txn, &body.miner, Some(AccountType::Basic), &coinbase_tx, block_height, account_op);
     }
 
-    fn create_contract(&self, txn: &mut WriteTransaction, transaction: &Transaction, block_height: u32) -> Result<(), AccountError> {
+    fn create_contract(&self, batch: &mut AccountsTreeBatch, txn: &WriteTransaction, transaction: &Transaction, block_height: u32) -> Result<(), AccountError> {
         assert!(transaction.flags.contains(TransactionFlags::CONTRACT_CREATION));
 
-        let recipient_account = self.get(&transaction.recipient, Some(txn));
+        let recipient_account = batch.get(txn, &transaction.recipient).unwrap_or(Account::INITIAL);
         let new_recipient_account = Account::new_contract(transaction.recipient_type, recipient_account.balance(), transaction, block_height)?;
-        self.tree.put_batch(txn, &transaction.recipient, new_recipient_account);
+        batch.put(txn, &transaction.recipient, new_recipient_account);
         return Ok(());
     }
 
-    fn revert_contract(&self, txn: &mut WriteTransaction, transaction: &Transaction, block_height: u32) -> Result<(), AccountError> {
+    fn revert_contract(&self, batch: &mut AccountsTreeBatch, txn: &WriteTransaction, transaction: &Transaction, block_height: u32) -> Result<(), AccountError> {
         assert!(transaction.flags.contains(TransactionFlags::CONTRACT_CREATION));
 
-        let recipient_account = self.get(&transaction.recipient, Some(txn));
+        let recipient_account = batch.get(txn, &transaction.recipient).unwrap_or(Account::INITIAL);
         if recipient_account.account_type() != transaction.recipient_type {
             return Err(AccountError::TypeMismatch);
         }
 
         let new_recipient_account = Account::new_basic(recipient_account.balance());
-        self.tree.put_batch(txn, &transaction.recipient, new_recipient_account);
+        batch.put(txn, &transaction.recipient, new_recipient_account);
         return Ok(());
     }
 
-    fn prune_accounts(&self, txn: &mut WriteTransaction, body: &BlockBody) -> Result<(), AccountError> {
+    fn prune_accounts(&self, batch: &mut AccountsTreeBatch, txn: &WriteTransaction, body: &BlockBody) -> Result<(), AccountError> {
         let mut pruned_accounts: HashMap<Address, Account> = HashMap::new();
         for pruned_account in &body.pruned_accounts {
             pruned_accounts.insert(pruned_account.address.clone(), pruned_account.account.clone());
         }
 
         for transaction in &body.transactions {
-            let sender_account = self.get(&transaction.sender, Some(txn));
+            let sender_account = batch.get(txn, &transaction.sender).unwrap_or(Account::INITIAL);
             if !sender_account.is_to_be_pruned() {
                 continue;
             }
@@ -237,7 +330,7 @@ impl<'env> Accounts<'env> {
                 return Err(AccountError::InvalidPruning);
             }
 
-            self.tree.put_batch(txn, &transaction.sender, Account::INITIAL);
+            batch.put(txn, &transaction.sender, Account::INITIAL);
             pruned_accounts.remove(&transaction.sender);
         }
 
@@ -248,10 +341,33 @@ impl<'env> Accounts<'env> {
         return Ok(());
     }
 
-    fn restore_accounts(&self, txn: &mut WriteTransaction, body: &BlockBody) -> Result<(), AccountError> {
+    fn restore_accounts(&self, batch: &mut AccountsTreeBatch, txn: &WriteTransaction, body: &BlockBody) -> Result<(), AccountError> {
         for pruned_account in &body.pruned_accounts {
-            self.tree.put_batch(txn, &pruned_account.address, pruned_account.account.clone());
+            batch.put(txn, &pruned_account.address, pruned_account.account.clone());
         }
         return Ok(());
     }
 }
+
+/// `Accounts` that only offer read access.
+#[derive(Debug)]
+pub struct ReadOnlyAccounts<'env> {
+    env: &'env ReadOnlyEnvironment,
+    tree: AccountsTree<'env>,
+}
+
+impl<'env> ReadOnlyAccounts<'env> {
+    pub fn get(&self, address: &Address, txn_option: Option<&db::Transaction>) -> Account {
+        return match txn_option {
+            Some(txn) => self.tree.get(txn, address),
+            None => self.tree.get(&self.env.read_transaction(), address)
+        }.unwrap_or(Account::INITIAL);
+    }
+
+    pub fn hash(&self, txn_option: Option<&db::Transaction>) -> Blake2bHash {
+        return match txn_option {
+            Some(txn) => self.tree.root_hash(txn),
+            None => self.tree.root_hash(&self.env.read_transaction())
+        };
+    }
+}
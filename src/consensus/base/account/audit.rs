@@ -0,0 +1,54 @@
+use std::fmt;
+
+use crate::consensus::base::account::Accounts;
+use crate::consensus::base::primitive::Coin;
+use crate::consensus::networks::{NetworkId, get_network_info};
+use crate::consensus::policy;
+use crate::utils::db::Transaction;
+
+/// Compares the sum of all balances with the supply that should be in circulation.
+/// The supply starts with the genesis accounts of the network. Fees only move coins and
+/// block rewards are minted according to `policy`, so any difference points to an accounting bug.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SupplyAudit {
+    pub block_height: u32,
+    pub total_balance: Coin,
+    pub expected_supply: Coin,
+}
+
+impl SupplyAudit {
+    pub fn is_consistent(&self) -> bool {
+        self.total_balance == self.expected_supply
+    }
+
+    /// Coins in accounts minus coins expected to be in circulation.
+    pub fn difference(&self) -> i64 {
+        u64::from(self.total_balance) as i64 - u64::from(self.expected_supply) as i64
+    }
+}
+
+impl fmt::Display for SupplyAudit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Supply at #{}: {} in accounts, {} expected (difference {})",
+               self.block_height, u64::from(self.total_balance), u64::from(self.expected_supply), self.difference())
+    }
+}
+
+/// Audits the accounts as seen by `txn`, which must be the state of `network_id` after the block at `block_height`.
+pub fn audit_supply<'env>(accounts: &Accounts<'env>, txn: &Transaction<'env>, network_id: NetworkId, block_height: u32) -> SupplyAudit {
+    SupplyAudit {
+        block_height,
+        total_balance: accounts.total_balance(txn),
+        expected_supply: Coin::from(expected_supply(network_id, block_height)),
+    }
+}
+
+/// The sum of the genesis balances plus the rewards of the blocks 1 to `block_height`.
+fn expected_supply(network_id: NetworkId, block_height: u32) -> u64 {
+    let network_info = get_network_info(network_id).unwrap();
+    let genesis_supply: u64 = network_info.genesis_accounts.iter()
+        .map(|(_, account)| u64::from(account.balance()))
+        .sum();
+    let rewards = policy::supply_after(block_height) - policy::supply_after(0);
+    return genesis_supply + rewards;
+}
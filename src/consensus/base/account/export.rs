@@ -0,0 +1,137 @@
+use beserial::{Deserialize, Serialize, SerializingError};
+use std::fmt;
+use std::io;
+
+use crate::consensus::base::account::{Account, Accounts};
+use crate::consensus::base::account::tree::AccountsTree;
+use crate::consensus::base::blockchain::ChainStore;
+use crate::consensus::base::primitive::Address;
+use crate::consensus::base::primitive::hash::Blake2bHash;
+use crate::consensus::networks::NetworkId;
+use crate::utils::db::{Environment, Transaction, WriteTransaction, WriteError};
+
+/// Header of an accounts state file. It is followed by a sequence of `(Address, Account)` pairs
+/// in ascending order of their addresses.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountsStateHeader {
+    pub version: u16,
+    pub network_id: NetworkId,
+    /// Hash and height of the block after which the accounts were exported.
+    pub block_hash: Blake2bHash,
+    pub block_height: u32,
+    /// Root hash of the accounts tree, i.e. the `accounts_hash` of the block.
+    pub accounts_hash: Blake2bHash,
+}
+
+impl AccountsStateHeader {
+    pub const VERSION: u16 = 1;
+}
+
+#[derive(Debug)]
+pub enum AccountsStateError {
+    IoError(io::Error),
+    SerializingError(SerializingError),
+    UnsupportedVersion(u16),
+    NetworkMismatch(NetworkId),
+    /// The imported accounts hash to this root instead of the announced one.
+    HashMismatch(Blake2bHash),
+    /// The chain of the target database is at this head instead of the block of the accounts state.
+    HeadMismatch(Blake2bHash, u32),
+    WriteFailed(WriteError),
+}
+
+impl fmt::Display for AccountsStateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AccountsStateError::IoError(e) => write!(f, "I/O error: {}", e),
+            AccountsStateError::SerializingError(e) => write!(f, "Serialization error: {:?}", e),
+            AccountsStateError::UnsupportedVersion(version) => write!(f, "Unsupported accounts state file version {}", version),
+            AccountsStateError::NetworkMismatch(network_id) => write!(f, "Accounts state file is for network {:?}", network_id),
+            AccountsStateError::HashMismatch(hash) => write!(f, "Imported accounts hash to {} instead of the accounts hash in the header", hash),
+            AccountsStateError::HeadMismatch(hash, height) => write!(f, "Accounts state does not belong to the chain head #{} ({})", height, hash),
+            AccountsStateError::WriteFailed(e) => write!(f, "Failed to write the imported accounts: {}", e),
+        }
+    }
+}
+
+impl From<io::Error> for AccountsStateError {
+    fn from(e: io::Error) -> Self {
+        AccountsStateError::IoError(e)
+    }
+}
+
+impl From<SerializingError> for AccountsStateError {
+    fn from(e: SerializingError) -> Self {
+        AccountsStateError::SerializingError(e)
+    }
+}
+
+impl From<WriteError> for AccountsStateError {
+    fn from(e: WriteError) -> Self {
+        AccountsStateError::WriteFailed(e)
+    }
+}
+
+/// Writes `header` followed by all accounts as seen by `txn`. The header must describe the
+/// state visible to `txn`. Returns the number of exported accounts.
+pub fn export_accounts<'env, W: io::Write>(accounts: &Accounts<'env>, txn: &Transaction<'env>, header: &AccountsStateHeader, writer: &mut W) -> Result<u32, AccountsStateError> {
+    header.serialize(writer)?;
+
+    let mut num_accounts = 0;
+    for (address, account) in accounts.iter(txn) {
+        address.serialize(writer)?;
+        account.serialize(writer)?;
+        num_accounts += 1;
+    }
+    return Ok(num_accounts);
+}
+
+/// Replaces the accounts in `env` with those of an accounts state file. The accounts are only
+/// committed if they hash to the `accounts_hash` announced in the header. If `env` already holds
+/// a chain, the header must describe its head, otherwise the accounts wouldn't match the chain.
+pub fn import_accounts<R: io::Read>(env: &Environment, network_id: NetworkId, reader: &mut R) -> Result<AccountsStateHeader, AccountsStateError> {
+    let header: AccountsStateHeader = Deserialize::deserialize(reader)?;
+    if header.version != AccountsStateHeader::VERSION {
+        return Err(AccountsStateError::UnsupportedVersion(header.version));
+    }
+    if header.network_id != network_id {
+        return Err(AccountsStateError::NetworkMismatch(header.network_id));
+    }
+
+    let chain_store = ChainStore::new(env);
+    let tree = AccountsTree::new(env);
+    let mut txn = WriteTransaction::new(env);
+    if let Some(head_hash) = chain_store.get_head(Some(&txn)) {
+        let head = chain_store.get_chain_info(&head_hash, false, Some(&txn)).unwrap().head;
+        if head_hash != header.block_hash || head.header.height != header.block_height {
+            txn.abort();
+            return Err(AccountsStateError::HeadMismatch(head_hash, head.header.height));
+        }
+    }
+    tree.clear(&mut txn);
+
+    let mut batch = tree.batch();
+    while let Some(address) = read_address(reader)? {
+        let account: Account = Deserialize::deserialize(reader)?;
+        batch.put(&txn, &address, account);
+    }
+    batch.finalize(&mut txn);
+
+    let root_hash = tree.root_hash(&txn);
+    if root_hash != header.accounts_hash {
+        txn.abort();
+        return Err(AccountsStateError::HashMismatch(root_hash));
+    }
+    txn.commit()?;
+    return Ok(header);
+}
+
+/// Reads the address of the next account, or returns `None` at the end of the file.
+fn read_address<R: io::Read>(reader: &mut R) -> Result<Option<Address>, AccountsStateError> {
+    let mut buf = [0u8; Address::SIZE];
+    if reader.read(&mut buf[..1])? == 0 {
+        return Ok(None);
+    }
+    reader.read_exact(&mut buf[1..])?;
+    return Ok(Some(Address::from(buf)));
+}
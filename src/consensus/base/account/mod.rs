@@ -3,6 +3,8 @@ pub mod basic_account;
 pub mod htlc_contract;
 pub mod vesting_contract;
 pub mod accounts;
+pub mod export;
+pub mod audit;
 
 use beserial::{Deserialize, Serialize, SerializingError, WriteBytesExt, ReadBytesExt};
 use crate::consensus::base::transaction::{Transaction, TransactionError};
@@ -15,7 +17,9 @@ use std::fmt;
 pub use self::basic_account::BasicAccount;
 pub use self::htlc_contract::HashedTimeLockedContract;
 pub use self::vesting_contract::VestingContract;
-pub use self::accounts::Accounts;
+pub use self::accounts::{Accounts, ReadOnlyAccounts};
+pub use self::audit::{SupplyAudit, audit_supply};
+pub use self::export::{AccountsStateError, AccountsStateHeader, export_accounts, import_accounts};
 
 #[derive(Clone, Copy, PartialEq, PartialOrd, Eq, Ord, Debug, Serialize, Deserialize)]
 #[repr(u8)]
@@ -1,62 +1,94 @@
+use std::collections::BTreeMap;
 use super::{AccountsTreeNode, AddressNibbles, NO_CHILDREN};
 use super::super::{Address, Account};
 use crate::consensus::base::primitive::hash::{Hash, Blake2bHash};
-use crate::utils::db::{Database, Transaction, WriteTransaction, Environment};
+use crate::utils::db::{Transaction, WriteTransaction, Environment, ReadOnlyEnvironment};
+use crate::utils::db::table::{Table, TableSpec};
+
+/// Nodes changed by the current batch, `None` for removed nodes.
+type StagedNodes = BTreeMap<AddressNibbles, Option<AccountsTreeNode>>;
+
+/// Tree nodes by their prefix.
+#[derive(Debug)]
+struct AccountsTable;
+
+impl TableSpec for AccountsTable {
+    const NAME: &'static str = "accounts";
+    type Key = AddressNibbles;
+    type Value = AccountsTreeNode;
+}
 
 #[derive(Debug)]
 pub struct AccountsTree<'env> {
-    db: Database<'env>
+    table: Table<'env, AccountsTable>,
 }
 
 impl<'env> AccountsTree<'env> {
-    const DB_NAME: &'static str = "accounts";
-
     pub fn new(env: &'env Environment) -> Self {
-        let db = env.open_database(Self::DB_NAME.to_string());
-        let tree = AccountsTree { db };
+        let tree = AccountsTree { table: Table::open(env) };
 
         let mut txn = WriteTransaction::new(env);
         if tree.get_root(&txn).is_none() {
             let root = AddressNibbles::empty();
-            txn.put_reserve(&tree.db, &root, &AccountsTreeNode::new_branch(root.clone(), NO_CHILDREN));
+            tree.table.put(&mut txn, &root, &AccountsTreeNode::new_branch(root.clone(), NO_CHILDREN));
         }
-        txn.commit();
+        txn.commit().expect("Failed to store the root of the accounts tree");
         return tree;
     }
 
-    pub fn put(&self, txn: &mut WriteTransaction, address: &Address, account: Account) {
-        self.put_batch(txn, address, account);
-        self.finalize_batch(txn);
+    /// Opens a tree that already contains the root node. Unlike `new`, this doesn't write to `env`.
+    pub fn open(env: &'env Environment) -> Self {
+        return AccountsTree { table: Table::open(env) };
     }
 
-    pub fn put_batch(&self, txn: &mut WriteTransaction, address: &Address, account: Account) {
-        if account.is_initial() && self.get(txn, address).is_none() {
-            return;
-        }
+    /// Opens the tree of a read-only environment, which must already contain the root node.
+    pub fn new_read_only(env: &'env ReadOnlyEnvironment) -> Self {
+        return AccountsTree { table: Table::open_read_only(env) };
+    }
 
-        // Insert account into the tree at address.
-        let prefix = AddressNibbles::from(address);
-        self.insert_batch(txn,AddressNibbles::empty(), prefix, account, Vec::new());
+    /// Removes all accounts, leaving an empty root node.
+    pub fn clear(&self, txn: &mut WriteTransaction) {
+        self.table.clear(txn);
+        let root = AddressNibbles::empty();
+        self.table.put(txn, &root, &AccountsTreeNode::new_branch(root.clone(), NO_CHILDREN));
+    }
+
+    pub fn put(&self, txn: &mut WriteTransaction, address: &Address, account: Account) {
+        let mut batch = self.batch();
+        batch.put(txn, address, account);
+        batch.finalize(txn);
     }
 
-    fn insert_batch(&self, txn: &mut WriteTransaction, node_prefix: AddressNibbles, prefix: AddressNibbles, account: Account, mut root_path: Vec<AccountsTreeNode>) {
+    /// Starts a batch of updates. The changes are staged in the batch and only written to the
+    /// database by `AccountsTreeBatch::finalize`, dropping the batch discards them.
+    pub fn batch<'tree>(&'tree self) -> AccountsTreeBatch<'tree, 'env> {
+        return AccountsTreeBatch { tree: self, staged: StagedNodes::new() };
+    }
+
+    fn insert_batch(&self, txn: &Transaction, staged: &mut StagedNodes, node_prefix: AddressNibbles, prefix: AddressNibbles, account: Account, mut root_path: Vec<AccountsTreeNode>) {
         // Find common prefix between node and new address.
         let common_prefix = node_prefix.common_prefix(&prefix);
-        //println!("Common Prefix between {} and {}: {}", node_prefix.to_string(), prefix.to_string(), common_prefix.to_string());
 
         // If the node prefix does not fully match the new address, split the node.
         if common_prefix.len() != node_prefix.len() {
             // Insert the new account node.
             let new_child = AccountsTreeNode::new_terminal(prefix, account);
-            txn.put_reserve(&self.db, new_child.prefix(), &new_child);
+
+            // The split node keeps its hash unless it was changed by this batch already.
+            let node_hash = match staged.get(&node_prefix) {
+                Some(_) => Blake2bHash::default(),
+                None => self.get_node(txn, staged, &node_prefix).unwrap().hash(),
+            };
 
             // Insert the new parent node.
             let new_parent = AccountsTreeNode::new_branch(common_prefix, NO_CHILDREN)
-                .with_child(&node_prefix, Blake2bHash::default()).unwrap()
+                .with_child(&node_prefix, node_hash).unwrap()
                 .with_child(new_child.prefix(), Blake2bHash::default()).unwrap();
-            txn.put_reserve(&self.db, new_parent.prefix(), &new_parent);
+            let new_parent_prefix = new_parent.prefix().clone();
+            staged.insert(new_child.prefix().clone(), Some(new_child));
+            staged.insert(new_parent_prefix.clone(), Some(new_parent));
 
-            return self.update_keys_batch(txn, new_parent.prefix().clone(), root_path);
+            return Self::update_keys_batch(staged, new_parent_prefix, root_path);
         }
 
         // If the commonPrefix is the specified address, we have found an (existing) node
@@ -67,106 +99,136 @@ impl<'env> AccountsTree<'env> {
             // (i.e. balance=0, nonce=0), it is like the account never existed
             // in the first place. Delete the node in this case.
             if account.is_initial() {
-                txn.remove(&self.db, &node_prefix);
+                staged.insert(node_prefix.clone(), None);
                 // We have already deleted the node, remove the subtree it was on.
-                return self.prune_batch(txn, node_prefix, root_path);
+                return self.prune_batch(txn, staged, node_prefix, root_path);
             }
 
             // Update the account.
-            let node: AccountsTreeNode = txn.get(&self.db, &node_prefix).unwrap();
+            let node = self.get_node(txn, staged, &node_prefix).unwrap();
             let node = node.with_account(account).unwrap();
-            txn.put_reserve(&self.db, node.prefix(), &node);
+            staged.insert(node_prefix.clone(), Some(node));
 
-            return self.update_keys_batch(txn, node_prefix, root_path);
+            return Self::update_keys_batch(staged, node_prefix, root_path);
         }
 
         // If the node prefix matches and there are address bytes left, descend into
         // the matching child node if one exists.
-        let node: AccountsTreeNode = txn.get(&self.db, &node_prefix).unwrap();
+        let node = self.get_node(txn, staged, &node_prefix).unwrap();
         if let Some(child_prefix) = node.get_child_prefix(&prefix) {
             root_path.push(node);
-            return self.insert_batch(txn, child_prefix, prefix, account, root_path);
+            return self.insert_batch(txn, staged, child_prefix, prefix, account, root_path);
         }
 
         // If no matching child exists, add a new child account node to the current node.
         let child = AccountsTreeNode::new_terminal(prefix, account);
-        txn.put_reserve(&self.db, child.prefix(), &child);
         let node = node.with_child(child.prefix(), Blake2bHash::default()).unwrap();
-        txn.put_reserve(&self.db, node.prefix(), &node);
+        staged.insert(child.prefix().clone(), Some(child));
+        staged.insert(node_prefix.clone(), Some(node));
 
-        return self.update_keys_batch(txn, node_prefix, root_path);
+        return Self::update_keys_batch(staged, node_prefix, root_path);
     }
 
-    fn prune_batch(&self, txn: &mut WriteTransaction, prefix: AddressNibbles, mut root_path: Vec<AccountsTreeNode>) {
+    fn prune_batch(&self, txn: &Transaction, staged: &mut StagedNodes, prefix: AddressNibbles, mut root_path: Vec<AccountsTreeNode>) {
         // Walk along the rootPath towards the root node starting with the
         // immediate predecessor of the node specified by 'prefix'.
         let mut tmp_prefix = prefix;
         while let Some(node) = root_path.pop() {
             let node = node.without_child(tmp_prefix).unwrap();
-            let node_prefix = node.prefix();
+            let node_prefix = node.prefix().clone();
 
             // If the node has only a single child, merge it with the next node.
             let root_address = AddressNibbles::empty();
             let num_children = node.iter_children().count();
-            if num_children == 1 && node_prefix != &root_address {
-                txn.remove(&self.db, node_prefix);
+            if num_children == 1 && node_prefix != root_address {
+                staged.insert(node_prefix.clone(), None);
 
+                // The remaining child moves up and has to be rehashed with its new parent.
                 let first_child = node.iter_children().nth(0).unwrap();
-                return self.update_keys_batch(txn, node_prefix + &first_child.suffix, root_path);
-            } else if num_children > 0 || node_prefix == &root_address {
+                let child_prefix = &node_prefix + &first_child.suffix;
+                if !staged.contains_key(&child_prefix) {
+                    let child = self.get_node(txn, staged, &child_prefix);
+                    staged.insert(child_prefix.clone(), child);
+                }
+                return Self::update_keys_batch(staged, child_prefix, root_path);
+            } else if num_children > 0 || node_prefix == root_address {
                 // Otherwise, if the node has children left, update it and all keys on the
                 // remaining root path. Pruning finished.
                 // XXX Special case: We start with an empty root node. Don't delete it.
-                txn.put_reserve(&self.db, node.prefix(), &node);
-                return self.update_keys_batch(txn, node_prefix.clone(), root_path);
+                staged.insert(node_prefix.clone(), Some(node));
+                return Self::update_keys_batch(staged, node_prefix, root_path);
             }
 
-            tmp_prefix = node_prefix.clone();
+            tmp_prefix = node_prefix;
         }
     }
 
-    fn update_keys_batch(&self, txn: &mut WriteTransaction, prefix: AddressNibbles, mut root_path: Vec<AccountsTreeNode>) {
+    fn update_keys_batch(staged: &mut StagedNodes, prefix: AddressNibbles, mut root_path: Vec<AccountsTreeNode>) {
         // Walk along the rootPath towards the root node starting with the
         // immediate predecessor of the node specified by 'prefix'.
+        // A zero hash marks the child as changed, it is hashed in AccountsTreeBatch::finalize.
         let mut tmp_prefix = prefix;
         while let Some(node) = root_path.pop() {
             let node = node.with_child(&tmp_prefix, Blake2bHash::default()).unwrap();
-            txn.put_reserve(&self.db, node.prefix(), &node);
-            tmp_prefix = node.prefix().clone(); // TODO: can we get rid of the clone here?
+            tmp_prefix = node.prefix().clone();
+            staged.insert(tmp_prefix.clone(), Some(node));
         }
     }
 
-    pub fn finalize_batch(&self, txn: &mut WriteTransaction) {
-        self.update_hashes(txn, &AddressNibbles::empty());
+    /// Updates the hashes of all changed nodes, starting at the root.
+    fn update_hashes(staged: &mut StagedNodes) {
+        hash_subtree(staged, &AddressNibbles::empty());
     }
 
-    fn update_hashes(&self, txn: &mut WriteTransaction, node_key: &AddressNibbles) -> Blake2bHash {
-        let mut node: AccountsTreeNode = txn.get(&self.db, node_key).unwrap();
-        if node.is_terminal() {
-            return node.hash();
+    /// Reads a node, preferring the version staged by a batch.
+    fn get_node(&self, txn: &Transaction, staged: &StagedNodes, key: &AddressNibbles) -> Option<AccountsTreeNode> {
+        match staged.get(key) {
+            Some(node) => node.clone(),
+            None => self.table.get(txn, key),
         }
-
-        let zero_hash = Blake2bHash::default();
-        // Compute sub hashes if necessary.
-        for mut child in node.iter_children_mut() {
-            if child.hash  == zero_hash {
-                child.hash = self.update_hashes(txn, &(node_key + &child.suffix));
-            }
-        }
-        txn.put_reserve(&self.db, node.prefix(), &node);
-        return node.hash();
     }
 
-    pub fn get(&self, txn: &Transaction, address: &Address) -> Option<Account> {
-        if let AccountsTreeNode::TerminalNode { account, .. } = txn.get(&self.db, &AddressNibbles::from(address))? {
+    fn get_staged(&self, txn: &Transaction, staged: &StagedNodes, address: &Address) -> Option<Account> {
+        if let AccountsTreeNode::TerminalNode { account, .. } = self.get_node(txn, staged, &AddressNibbles::from(address))? {
             return Some(account);
         }
         return None;
     }
 
+    pub fn get(&self, txn: &Transaction, address: &Address) -> Option<Account> {
+        return self.get_staged(txn, &StagedNodes::new(), address);
+    }
+
+    /// Iterates over all accounts in ascending order of their addresses.
+    pub fn iter<'txn>(&'txn self, txn: &'txn Transaction<'env>) -> AccountsTreeIter<'txn, 'env> {
+        return AccountsTreeIter { tree: self, txn, stack: vec![AddressNibbles::empty()] };
+    }
+
+    /// Iterates over all accounts whose address starts with `prefix`, in ascending order of their addresses.
+    pub fn iter_prefix<'txn>(&'txn self, txn: &'txn Transaction<'env>, prefix: &[u8]) -> AccountsTreeIter<'txn, 'env> {
+        let prefix = AddressNibbles::from(prefix);
+
+        // Descend to the topmost node whose subtree contains exactly the matching accounts.
+        let mut node_prefix = AddressNibbles::empty();
+        let stack = loop {
+            if prefix.is_prefix_of(&node_prefix) {
+                break vec![node_prefix];
+            }
+            if !node_prefix.is_prefix_of(&prefix) {
+                break Vec::new();
+            }
+
+            let node = self.table.get(txn, &node_prefix).unwrap();
+            match node.get_child_prefix(&prefix) {
+                Some(child_prefix) => node_prefix = child_prefix,
+                None => break Vec::new(),
+            }
+        };
+        return AccountsTreeIter { tree: self, txn, stack };
+    }
+
     fn get_root(&self, txn: &Transaction) -> Option<AccountsTreeNode> {
-        let node = txn.get(&self.db, &AddressNibbles::empty());
-        return node;
+        return self.table.get(txn, &AddressNibbles::empty());
     }
 
     pub fn root_hash(&self, txn: &Transaction) -> Blake2bHash {
@@ -174,3 +236,94 @@ impl<'env> AccountsTree<'env> {
         return node.hash();
     }
 }
+
+/// Updates of an `AccountsTree` that are staged in memory until `finalize` writes them to the
+/// database. The batch belongs to the write transaction it is used with and must not outlive it.
+/// Dropping the batch without finalizing it discards all staged changes.
+#[derive(Debug)]
+pub struct AccountsTreeBatch<'tree, 'env: 'tree> {
+    tree: &'tree AccountsTree<'env>,
+    staged: StagedNodes,
+}
+
+impl<'tree, 'env> AccountsTreeBatch<'tree, 'env> {
+    pub fn put(&mut self, txn: &Transaction, address: &Address, account: Account) {
+        if account.is_initial() && self.tree.get_staged(txn, &self.staged, address).is_none() {
+            return;
+        }
+
+        // Insert account into the tree at address.
+        let prefix = AddressNibbles::from(address);
+        self.tree.insert_batch(txn, &mut self.staged, AddressNibbles::empty(), prefix, account, Vec::new());
+    }
+
+    /// Reads an account as seen by `txn` with the changes of this batch applied.
+    pub fn get(&self, txn: &Transaction, address: &Address) -> Option<Account> {
+        return self.tree.get_staged(txn, &self.staged, address);
+    }
+
+    /// Hashes all nodes changed by the batch once and writes them to the database in a single pass.
+    pub fn finalize(mut self, txn: &mut WriteTransaction) {
+        if self.staged.is_empty() {
+            return;
+        }
+
+        AccountsTree::update_hashes(&mut self.staged);
+
+        for (key, node) in self.staged.iter() {
+            match node {
+                Some(node) => self.tree.table.put(txn, key, node),
+                None => self.tree.table.remove(txn, key),
+            }
+        }
+    }
+}
+
+/// Computes the hashes of the changed children of the node at `key` and returns its hash.
+/// All changed nodes of the subtree must be staged.
+fn hash_subtree(staged: &mut StagedNodes, key: &AddressNibbles) -> Blake2bHash {
+    let mut node = match staged.remove(key) {
+        Some(Some(node)) => node,
+        _ => unreachable!("Changed accounts tree node {} is not staged", key),
+    };
+
+    let zero_hash = Blake2bHash::default();
+    for child in node.iter_children_mut() {
+        if child.hash == zero_hash {
+            child.hash = hash_subtree(staged, &(key + &child.suffix));
+        }
+    }
+
+    let hash = node.hash();
+    staged.insert(key.clone(), Some(node));
+    return hash;
+}
+
+/// Depth-first traversal of the tree, yielding the account of every terminal node.
+pub struct AccountsTreeIter<'txn, 'env> {
+    tree: &'txn AccountsTree<'env>,
+    txn: &'txn Transaction<'env>,
+    /// Prefixes of the nodes still to visit, the next one on top.
+    stack: Vec<AddressNibbles>,
+}
+
+impl<'txn, 'env> Iterator for AccountsTreeIter<'txn, 'env> {
+    type Item = (Address, Account);
+
+    fn next(&mut self) -> Option<(Address, Account)> {
+        while let Some(prefix) = self.stack.pop() {
+            let node = self.tree.table.get(self.txn, &prefix)
+                .expect("Accounts tree references a missing node");
+            if node.is_terminal() {
+                if let AccountsTreeNode::TerminalNode { prefix, account } = node {
+                    return Some((prefix.to_address(), account));
+                }
+            }
+
+            // Push the children in reverse, so that they are visited in ascending order.
+            let children: Vec<AddressNibbles> = node.iter_children().map(|child| node.prefix() + &child.suffix).collect();
+            self.stack.extend(children.into_iter().rev());
+        }
+        return None;
+    }
+}
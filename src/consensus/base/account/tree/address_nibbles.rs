@@ -9,7 +9,7 @@ use std::io;
 use std::cmp;
 use std::borrow::Cow;
 use hex;
-use crate::utils::db::AsDatabaseBytes;
+use crate::utils::db::{AsDatabaseBytes, FromDatabaseValue};
 
 // Stores a compact representation of length nibbles.
 // Each u8 stores up to 2 nibbles.
@@ -51,7 +51,7 @@ impl AddressNibbles {
         // If prefix ends in the middle of a byte, compare that part as well.
         if ends_in_byte {
             let own_nibble = (self.bytes[end] >> 4) & 0xf;
-            let other_nibble = (self.bytes[end] >> 4) & 0xf;
+            let other_nibble = (other.bytes[end] >> 4) & 0xf;
             if own_nibble != other_nibble {
                 return false;
             }
@@ -115,6 +115,12 @@ impl AddressNibbles {
     pub (in super) fn suffix(&self, start: u8) -> AddressNibbles {
         return self.slice(start as usize, self.len());
     }
+
+    /// Converts the full-length prefix of a terminal node back into its address.
+    pub (in super) fn to_address(&self) -> Address {
+        assert_eq!(self.len(), Address::SIZE * 2, "prefix {} is not a full address", self);
+        return Address::from(&self.bytes[..]);
+    }
 }
 
 impl<'a> From<&'a Address> for AddressNibbles {
@@ -253,6 +259,13 @@ impl AsDatabaseBytes for AddressNibbles {
     }
 }
 
+impl FromDatabaseValue for AddressNibbles {
+    fn copy_from_database(bytes: &[u8]) -> io::Result<Self> where Self: Sized {
+        let mut cursor = io::Cursor::new(bytes);
+        return Ok(Deserialize::deserialize(&mut cursor)?);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -6,7 +6,7 @@ use beserial::{Serialize, Deserialize};
 
 use self::accounts_tree_node::{AccountsTreeNode, NO_CHILDREN};
 use self::address_nibbles::AddressNibbles;
-pub use self::accounts_tree::AccountsTree;
+pub use self::accounts_tree::{AccountsTree, AccountsTreeBatch, AccountsTreeIter};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct AccountsProof {}
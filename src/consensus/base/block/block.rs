@@ -27,7 +27,7 @@ impl Deserialize for Block {
 impl Block {
     pub const VERSION: u16 = 1;
     const TIMESTAMP_DRIFT_MAX: u64 = 600 * 1000;
-    const MAX_SIZE: usize = 100000; // 100 kb
+    pub const MAX_SIZE: usize = 100000; // 100 kb
 
     pub fn verify(&self, timestamp_now: u64, network_id: NetworkId) -> Result<(), BlockError> {
         // XXX Check that the block version is supported.
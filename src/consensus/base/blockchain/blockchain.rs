@@ -1,14 +1,22 @@
 use bigdecimal::BigDecimal;
 use parking_lot::{RwLock, RwLockReadGuard, MappedRwLockReadGuard, Mutex};
+use beserial::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::io;
 use std::sync::Arc;
-use crate::consensus::base::account::{Accounts, AccountError};
-use crate::consensus::base::block::{Block, BlockError, Target, TargetCompact};
-use crate::consensus::base::blockchain::{ChainInfo, ChainStore, TransactionCache, Direction};
+use crate::consensus::base::account::{Account, Accounts, AccountError, AccountsStateError, AccountsStateHeader, SupplyAudit, audit_supply, export_accounts};
+use crate::consensus::base::block::{Block, BlockBody, BlockError, Target, TargetCompact};
+use crate::consensus::base::blockchain::{BlockchainView, ChainInfo, ChainStore, TransactionCache, Direction};
+use crate::consensus::base::blockchain::bootstrap::{BootstrapError, BootstrapHeader};
+use crate::consensus::base::blockchain::schema;
+use crate::consensus::base::primitive::Address;
 use crate::consensus::base::primitive::hash::{Hash, Blake2bHash};
+use crate::consensus::base::transaction::{Transaction, TransactionFlags};
 use crate::consensus::networks::{NetworkId, get_network_info};
 use crate::consensus::policy;
 use crate::network::NetworkTime;
-use crate::utils::db::{Environment, ReadTransaction, WriteTransaction};
+use crate::utils::db::{Environment, ReadOnlyEnvironment, ReadTransaction, WriteTransaction, WriteError};
 use crate::utils::observer::Notifier;
 use crate::utils::unique_ptr::UniquePtr;
 use std::cmp;
@@ -39,6 +47,8 @@ pub enum PushResult {
     Extended,
     Rebranched,
     Forked,
+    /// The block could not be written to the database.
+    StoreFailed,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
@@ -51,6 +61,91 @@ pub enum PushError {
     InvalidFork,
 }
 
+/// Account states resulting from `Blockchain::simulate_transaction`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransactionSimulation {
+    /// Height of the next block, at which the transaction was applied.
+    pub block_height: u32,
+    pub sender: Account,
+    pub recipient: Account,
+    /// Pending transactions of the sender that were skipped because they no longer apply.
+    /// Only `Mempool::simulate_transaction` considers pending transactions.
+    pub skipped_transactions: Vec<Blake2bHash>,
+}
+
+impl TransactionSimulation {
+    /// Applies `transaction` to copies of the `sender` and `recipient` accounts in the same way
+    /// `Accounts::commit_transaction` does, as if it was included in the block at `block_height`.
+    pub fn apply(transaction: &Transaction, block_height: u32, sender: Account, recipient: Account) -> Result<Self, AccountError> {
+        if sender.account_type() != transaction.sender_type {
+            return Err(AccountError::TypeMismatch);
+        }
+        let sender = sender.with_outgoing_transaction(transaction, block_height)?;
+
+        // A transaction to its own sender is received by the already updated sender account.
+        let to_self = transaction.recipient == transaction.sender;
+        let recipient = if to_self { sender.clone() } else { recipient };
+        let recipient = if transaction.flags.contains(TransactionFlags::CONTRACT_CREATION) {
+            let recipient = recipient.with_incoming_transaction(transaction, block_height)?;
+            Account::new_contract(transaction.recipient_type, recipient.balance(), transaction, block_height)?
+        } else {
+            if recipient.account_type() != transaction.recipient_type {
+                return Err(AccountError::TypeMismatch);
+            }
+            recipient.with_incoming_transaction(transaction, block_height)?
+        };
+        let sender = if to_self { recipient.clone() } else { sender };
+
+        return Ok(TransactionSimulation { block_height, sender, recipient, skipped_transactions: Vec::new() });
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlockchainError {
+    UnsupportedSchemaVersion(u32),
+    MigrationFailed(u32, String),
+    InvalidGenesisBlock,
+    FailedLoadingMainChain,
+    InconsistentState,
+    WriteFailed(String),
+}
+
+impl fmt::Display for BlockchainError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BlockchainError::UnsupportedSchemaVersion(version) => write!(f, "Database schema version {} is not supported by this version (expected at most {}). Upgrade your node or reset your consensus database.", version, schema::SCHEMA_VERSION),
+            BlockchainError::MigrationFailed(version, e) => write!(f, "Failed to migrate database to schema version {}: {}", version, e),
+            BlockchainError::InvalidGenesisBlock => write!(f, "Invalid genesis block stored. Reset your consensus database."),
+            BlockchainError::FailedLoadingMainChain => write!(f, "Failed to load main chain. Reset your consensus database."),
+            BlockchainError::InconsistentState => write!(f, "Inconsistent chain/accounts state. Reset your consensus database."),
+            BlockchainError::WriteFailed(e) => write!(f, "Failed to write to the consensus database: {}", e),
+        }
+    }
+}
+
+/// Reasons why `Blockchain::get_account_at` can't reconstruct an account.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AccountHistoryError {
+    /// The requested height is above the head.
+    AboveHead(u32),
+    /// The requested height is more than `MAX_ACCOUNT_HISTORY_DEPTH` blocks below the head.
+    TooDeep(u32),
+    /// The body of the block at this height was pruned, so it can't be reverted.
+    Pruned(u32),
+    RevertFailed(AccountError),
+}
+
+impl fmt::Display for AccountHistoryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AccountHistoryError::AboveHead(height) => write!(f, "Height #{} is above the head", height),
+            AccountHistoryError::TooDeep(height) => write!(f, "Height #{} is more than {} blocks below the head", height, Blockchain::MAX_ACCOUNT_HISTORY_DEPTH),
+            AccountHistoryError::Pruned(height) => write!(f, "Body of block #{} was pruned", height),
+            AccountHistoryError::RevertFailed(e) => write!(f, "Failed to revert block: {}", e),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum BlockchainEvent {
     Extended(Blake2bHash, UniquePtr<Block>),
@@ -58,30 +153,47 @@ pub enum BlockchainEvent {
 }
 
 impl<'env> Blockchain<'env> {
-    pub fn new(env: &'env Environment, network_id: NetworkId, network_time: Arc<NetworkTime>) -> Self {
+    /// How many blocks below the head `get_account_at` reverts at most. Pushes wait while the blocks
+    /// are reverted, so keep this small. Pruned nodes keep the bodies of this many blocks.
+    pub const MAX_ACCOUNT_HISTORY_DEPTH: u32 = policy::TRANSACTION_VALIDITY_WINDOW;
+    /// How many heights `prune_forks` scans at most per call.
+    pub const MAX_FORK_PRUNING_HEIGHTS: u32 = 10_000;
+
+    pub fn new(env: &'env Environment, network_id: NetworkId, network_time: Arc<NetworkTime>) -> Result<Self, BlockchainError> {
         let chain_store = ChainStore::new(env);
+
+        // Bring the database up to the current schema version first.
+        schema::migrate(env, &chain_store)?;
+
         match chain_store.get_head(None) {
             Some(head_hash) => Blockchain::load(env, network_time, network_id, chain_store, head_hash),
             None => Blockchain::init(env, network_time, network_id, chain_store)
         }
     }
 
-    fn load(env: &'env Environment, network_time: Arc<NetworkTime>, network_id: NetworkId, chain_store: ChainStore<'env>, head_hash: Blake2bHash) -> Self {
+    /// Opens a read-only view of the chain in `env`, e.g. of a database written by a running node.
+    pub fn new_read_only(env: &'env ReadOnlyEnvironment, network_id: NetworkId) -> Result<BlockchainView<'env>, BlockchainError> {
+        BlockchainView::new(env, network_id)
+    }
+
+    fn load(env: &'env Environment, network_time: Arc<NetworkTime>, network_id: NetworkId, chain_store: ChainStore<'env>, head_hash: Blake2bHash) -> Result<Self, BlockchainError> {
         // Check that the correct genesis block is stored.
         let network_info = get_network_info(network_id).unwrap();
         let genesis_info = chain_store.get_chain_info(&network_info.genesis_hash, false, None);
-        assert!(genesis_info.is_some() && genesis_info.unwrap().on_main_chain,
-            "Invalid genesis block stored. Reset your consensus database.");
+        if !genesis_info.map_or(false, |genesis_info| genesis_info.on_main_chain) {
+            return Err(BlockchainError::InvalidGenesisBlock);
+        }
 
         // Load main chain from store.
         let main_chain = chain_store
             .get_chain_info(&head_hash, true, None)
-            .expect("Failed to load main chain. Reset your consensus database.");
+            .ok_or(BlockchainError::FailedLoadingMainChain)?;
 
         // Check that chain/accounts state is consistent.
         let accounts = Accounts::new(env);
-        assert_eq!(main_chain.head.header.accounts_hash, accounts.hash(None),
-            "Inconsistent chain/accounts state. Reset your consensus database.");
+        if main_chain.head.header.accounts_hash != accounts.hash(None) {
+            return Err(BlockchainError::InconsistentState);
+        }
 
         // Initialize TransactionCache.
         let mut transaction_cache = TransactionCache::new();
@@ -92,7 +204,7 @@ impl<'env> Blockchain<'env> {
         transaction_cache.push_block(&main_chain.head);
         assert_eq!(transaction_cache.missing_blocks(), policy::TRANSACTION_VALIDITY_WINDOW.saturating_sub(main_chain.head.header.height));
 
-        Blockchain {
+        Ok(Blockchain {
             env,
             network_id,
             network_time,
@@ -104,11 +216,11 @@ impl<'env> Blockchain<'env> {
                 main_chain,
                 head_hash
             }),
-            push_lock: Mutex::new(())
-        }
+            push_lock: Mutex::new(()),
+        })
     }
 
-    fn init(env: &'env Environment, network_time: Arc<NetworkTime>, network_id: NetworkId, chain_store: ChainStore<'env>) -> Self {
+    fn init(env: &'env Environment, network_time: Arc<NetworkTime>, network_id: NetworkId, chain_store: ChainStore<'env>) -> Result<Self, BlockchainError> {
         // Initialize chain & accounts with genesis block.
         let network_info = get_network_info(network_id).unwrap();
         let main_chain = ChainInfo::initial(network_info.genesis_block.clone());
@@ -122,12 +234,12 @@ impl<'env> Blockchain<'env> {
         // Store genesis block.
         chain_store.put_chain_info(&mut txn, &head_hash, &main_chain, true);
         chain_store.set_head(&mut txn, &head_hash);
-        txn.commit();
+        txn.commit().map_err(|e| BlockchainError::WriteFailed(e.to_string()))?;
 
         // Initialize empty TransactionCache.
         let transaction_cache = TransactionCache::new();
 
-        Blockchain {
+        Ok(Blockchain {
             env,
             network_id,
             network_time,
@@ -139,8 +251,8 @@ impl<'env> Blockchain<'env> {
                 main_chain,
                 head_hash
             }),
-            push_lock: Mutex::new(())
-        }
+            push_lock: Mutex::new(()),
+        })
     }
 
     pub fn push(&self, block: Block) -> PushResult {
@@ -156,35 +268,49 @@ impl<'env> Blockchain<'env> {
         // Only one push operation at a time.
         let lock = self.push_lock.lock();
 
+        // A write transaction that ran out of space grows the map before the next one starts,
+        // so try to store the block once more.
+        let result = match self.push_locked(&block) {
+            Err(WriteError::MapFull) => self.push_locked(&block),
+            result => result,
+        };
+        return result.unwrap_or_else(|e| {
+            error!("Failed to store block - {}", e);
+            PushResult::StoreFailed
+        });
+    }
+
+    /// Pushes a verified block. Must be called while holding `push_lock`.
+    fn push_locked(&self, block: &Block) -> Result<PushResult, WriteError> {
         // Check if we already know this block.
         let hash: Blake2bHash = block.header.hash();
         if self.chain_store.get_chain_info(&hash, false, None).is_some() {
-            return PushResult::Known;
+            return Ok(PushResult::Known);
         }
 
         // Check if the block's immediate predecessor is part of the chain.
         let prev_info_opt = self.chain_store.get_chain_info(&block.header.prev_hash, false, None);
         if prev_info_opt.is_none() {
             warn!("Rejecting block - unknown predecessor");
-            return PushResult::Orphan;
+            return Ok(PushResult::Orphan);
         }
 
         // Check that the block is a valid successor of its predecessor.
         let prev_info = prev_info_opt.unwrap();
         if !block.is_immediate_successor_of(&prev_info.head) {
             warn!("Rejecting block - not a valid successor");
-            return PushResult::Invalid(PushError::InvalidSuccessor);
+            return Ok(PushResult::Invalid(PushError::InvalidSuccessor));
         }
 
         // Check that the difficulty is correct.
         let next_target = self.get_next_target(Some(&block.header.prev_hash));
         if block.header.n_bits != TargetCompact::from(next_target) {
             warn!("Rejecting block - difficulty mismatch");
-            return PushResult::Invalid(PushError::DifficultyMismatch);
+            return Ok(PushResult::Invalid(PushError::DifficultyMismatch));
         }
 
         // Block looks good, create ChainInfo.
-        let chain_info = prev_info.next(block);
+        let chain_info = prev_info.next(block.clone());
 
         // Check if the block extends our current main chain.
         if chain_info.head.header.prev_hash == self.state.read().head_hash {
@@ -201,12 +327,13 @@ impl<'env> Blockchain<'env> {
         debug!("Creating/extending fork with block {}, height #{}, total_difficulty {}", hash, chain_info.head.header.height, chain_info.total_difficulty);
         let mut txn = WriteTransaction::new(self.env);
         self.chain_store.put_chain_info(&mut txn, &hash, &chain_info, true);
-        txn.commit();
+        self.chain_store.touch_fork_height(&mut txn, chain_info.head.header.height);
+        txn.commit()?;
 
-        return PushResult::Forked;
+        return Ok(PushResult::Forked);
     }
 
-    fn extend(&self, block_hash: Blake2bHash, mut chain_info: ChainInfo, mut prev_info: ChainInfo) -> PushResult {
+    fn extend(&self, block_hash: Blake2bHash, mut chain_info: ChainInfo, mut prev_info: ChainInfo) -> Result<PushResult, WriteError> {
         let mut txn = WriteTransaction::new(self.env);
         {
             let state = self.state.read();
@@ -215,14 +342,14 @@ impl<'env> Blockchain<'env> {
             if state.transaction_cache.contains_any(&chain_info.head) {
                 warn!("Rejecting block - transaction already included");
                 txn.abort();
-                return PushResult::Invalid(PushError::DuplicateTransaction);
+                return Ok(PushResult::Invalid(PushError::DuplicateTransaction));
             }
 
             // Commit block to AccountsTree.
             if let Err(e) = state.accounts.commit_block(&mut txn, &chain_info.head) {
                 warn!("Rejecting block - commit failed: {}", e);
                 txn.abort();
-                return PushResult::Invalid(PushError::AccountsError(e));
+                return Ok(PushResult::Invalid(PushError::AccountsError(e)));
             }
         }
 
@@ -237,12 +364,12 @@ impl<'env> Blockchain<'env> {
             // Acquire write lock.
             let mut state = self.state.write();
 
+            txn.commit()?;
+
             state.transaction_cache.push_block(&chain_info.head);
 
             state.main_chain = chain_info;
             state.head_hash = block_hash;
-
-            txn.commit();
         }
 
         // Give up write lock before notifying.
@@ -250,23 +377,25 @@ impl<'env> Blockchain<'env> {
         let event = BlockchainEvent::Extended(state.head_hash.clone(), UniquePtr::new(&state.main_chain.head));
         self.notifier.read().notify(event);
 
-        return PushResult::Extended;
+        return Ok(PushResult::Extended);
     }
 
-    fn rebranch(&self, block_hash: Blake2bHash, chain_info: ChainInfo) -> PushResult {
+    fn rebranch(&self, block_hash: Blake2bHash, chain_info: ChainInfo) -> Result<PushResult, WriteError> {
         debug!("Rebranching to fork {}, height #{}, total_difficulty {}", block_hash, chain_info.head.header.height, chain_info.total_difficulty);
 
+        // All reads go through the write transaction, so no other transaction is open meanwhile.
+        let mut write_txn = WriteTransaction::new(self.env);
+
         // Find the common ancestor between our current main chain and the fork chain.
         // Walk up the fork chain until we find a block that is part of the main chain.
         // Store the chain along the way.
-        let read_txn = ReadTransaction::new(self.env);
 
         let mut fork_chain: Vec<(Blake2bHash, ChainInfo)> = vec![];
         let mut current: (Blake2bHash, ChainInfo) = (block_hash, chain_info);
         while !current.1.on_main_chain {
             let prev_hash = current.1.head.header.prev_hash.clone();
             let prev_info = self.chain_store
-                .get_chain_info(&prev_hash, true, Some(&read_txn))
+                .get_chain_info(&prev_hash, true, Some(&write_txn))
                 .expect("Corrupted store: Failed to find fork predecessor while rebranching");
 
             fork_chain.push(current);
@@ -275,11 +404,18 @@ impl<'env> Blockchain<'env> {
 
         debug!("Found common ancestor {} at height #{}, {} blocks up", current.0, current.1.head.header.height, fork_chain.len());
 
+        // We can't revert blocks whose bodies were pruned, nor rebuild the TransactionCache below the ancestor.
+        let pruned_height = self.chain_store.get_pruned_height(Some(&write_txn));
+        if pruned_height > 0 && current.1.head.header.height < pruned_height + policy::TRANSACTION_VALIDITY_WINDOW {
+            warn!("Rejecting fork - common ancestor at height #{} is below the pruned height #{}", current.1.head.header.height, pruned_height);
+            write_txn.abort();
+            return Ok(PushResult::Invalid(PushError::InvalidFork));
+        }
+
         // Revert AccountsTree & TransactionCache to the common ancestor state.
         let mut revert_chain: Vec<(Blake2bHash, ChainInfo)> = vec![];
         let mut ancestor = current;
 
-        let mut write_txn = WriteTransaction::new(self.env);
         let mut cache_txn;
         {
             let state = self.state.read();
@@ -297,7 +433,7 @@ impl<'env> Blockchain<'env> {
 
                 let prev_hash = current.1.head.header.prev_hash.clone();
                 let prev_info = self.chain_store
-                    .get_chain_info(&prev_hash, true, Some(&read_txn))
+                    .get_chain_info(&prev_hash, true, Some(&write_txn))
                     .expect("Corrupted store: Failed to find main chain predecessor while rebranching");
 
                 assert_eq!(prev_info.head.header.accounts_hash, state.accounts.hash(Some(&write_txn)),
@@ -314,26 +450,26 @@ impl<'env> Blockchain<'env> {
             } else {
                 cache_txn.tail_hash()
             };
-            let blocks = self.chain_store.get_blocks_backward(&start_hash, cache_txn.missing_blocks(), true, Some(&read_txn));
+            let blocks = self.chain_store.get_blocks_backward(&start_hash, cache_txn.missing_blocks(), true, Some(&write_txn));
             for block in blocks.iter() {
                 cache_txn.prepend_block(block);
             }
             assert_eq!(cache_txn.missing_blocks(), policy::TRANSACTION_VALIDITY_WINDOW.saturating_sub(ancestor.1.head.header.height));
 
             // Check each fork block against TransactionCache & commit to AccountsTree.
-            for fork_block in fork_chain.iter().rev() {
+            for (i, fork_block) in fork_chain.iter().enumerate().rev() {
                 if cache_txn.contains_any(&fork_block.1.head) {
                     warn!("Failed to apply fork block while rebranching - transaction already included");
-                    // TODO delete invalid fork from store
                     write_txn.abort();
-                    return PushResult::Invalid(PushError::InvalidFork);
+                    self.remove_invalid_fork(&fork_chain[..=i]);
+                    return Ok(PushResult::Invalid(PushError::InvalidFork));
                 }
 
                 if let Err(e) = state.accounts.commit_block(&mut write_txn, &fork_block.1.head) {
                     warn!("Failed to apply fork block while rebranching - {}", e);
-                    // TODO delete invalid fork from store
                     write_txn.abort();
-                    return PushResult::Invalid(PushError::InvalidFork);
+                    self.remove_invalid_fork(&fork_chain[..=i]);
+                    return Ok(PushResult::Invalid(PushError::InvalidFork));
                 }
 
                 cache_txn.push_block(&fork_block.1.head);
@@ -353,6 +489,9 @@ impl<'env> Blockchain<'env> {
                 self.chain_store.put_chain_info(&mut write_txn, &reverted_block.0, &reverted_block.1, false);
             }
 
+            // The reverted blocks are forks now, they may lie below the fork pruning height.
+            self.chain_store.touch_fork_height(&mut write_txn, ancestor.1.head.header.height + 1);
+
             // Update the mainChainSuccessor of the common ancestor block.
             ancestor.1.main_chain_successor = Some(fork_chain.last().unwrap().0.clone());
             self.chain_store.put_chain_info(&mut write_txn, &ancestor.0, &ancestor.1, false);
@@ -373,7 +512,7 @@ impl<'env> Blockchain<'env> {
             }
 
             // Commit transaction & update head.
-            write_txn.commit();
+            write_txn.commit()?;
             state.transaction_cache = cache_txn;
 
             state.main_chain = fork_chain[0].1.clone();
@@ -392,10 +531,267 @@ impl<'env> Blockchain<'env> {
         let event = BlockchainEvent::Rebranched(reverted_blocks, adopted_blocks);
         self.notifier.read().notify(event);
 
-        return PushResult::Rebranched;
+        return Ok(PushResult::Rebranched);
+    }
+
+    /// Removes the invalid blocks of a fork (and everything that builds on them) from the store.
+    fn remove_invalid_fork(&self, invalid_blocks: &[(Blake2bHash, ChainInfo)]) {
+        let blocks = invalid_blocks.iter()
+            .map(|(hash, chain_info)| (hash.clone(), chain_info.head.header.height))
+            .collect();
+
+        let mut txn = WriteTransaction::new(self.env);
+        let num_removed = self.remove_fork_blocks(&mut txn, blocks);
+        if let Err(e) = txn.commit() {
+            // The blocks are removed by the next fork pruning instead.
+            error!("Failed to remove invalid fork blocks from store - {}", e);
+            return;
+        }
+
+        debug!("Removed {} invalid fork blocks from store", num_removed);
+    }
+
+    /// Removes all side chain blocks that are more than `depth` blocks behind the head of the
+    /// main chain, together with everything that builds on them. Returns the number of removed blocks.
+    /// Pushes wait while forks are pruned, so each call only scans up to `MAX_FORK_PRUNING_HEIGHTS`
+    /// heights above the last pruned one. Later calls continue from there.
+    pub fn prune_forks(&self, depth: u32) -> usize {
+        // Forks must not change while we are pruning them.
+        let lock = self.push_lock.lock();
+
+        let mut txn = WriteTransaction::new(self.env);
+        let pruning_height = self.chain_store.get_fork_pruning_height(Some(&txn));
+        let cutoff = cmp::min(self.height().saturating_sub(depth), pruning_height + Self::MAX_FORK_PRUNING_HEIGHTS);
+        if cutoff <= pruning_height {
+            txn.abort();
+            return 0;
+        }
+
+        let mut blocks = Vec::new();
+        for height in (pruning_height + 1)..=cutoff {
+            for hash in self.chain_store.get_block_hashes_at(height, Some(&txn)) {
+                let chain_info = self.chain_store
+                    .get_chain_info(&hash, false, Some(&txn))
+                    .expect("Corrupted store: ChainInfo referenced from index not found");
+                if !chain_info.on_main_chain {
+                    blocks.push((hash, height));
+                }
+            }
+        }
+
+        let num_removed = self.remove_fork_blocks(&mut txn, blocks);
+        self.chain_store.set_fork_pruning_height(&mut txn, cutoff);
+        if let Err(e) = txn.commit() {
+            error!("Failed to prune stale fork blocks - {}", e);
+            return 0;
+        }
+
+        debug!("Pruned {} stale fork blocks up to height #{}", num_removed, cutoff);
+        return num_removed;
+    }
+
+    /// Removes the given fork blocks and all of their descendants. Descendants are found
+    /// height by height until a height contains no more blocks building on removed ones.
+    fn remove_fork_blocks(&self, txn: &mut WriteTransaction, blocks: Vec<(Blake2bHash, u32)>) -> usize {
+        let mut blocks_by_height: BTreeMap<u32, Vec<Blake2bHash>> = BTreeMap::new();
+        for (hash, height) in blocks {
+            blocks_by_height.entry(height).or_insert_with(Vec::new).push(hash);
+        }
+
+        let mut height = match blocks_by_height.keys().next() {
+            Some(height) => *height,
+            None => return 0
+        };
+
+        let mut num_removed = 0;
+        let mut removed_prev: HashSet<Blake2bHash> = HashSet::new();
+        loop {
+            let mut to_remove: HashSet<Blake2bHash> = blocks_by_height.remove(&height)
+                .unwrap_or_default()
+                .into_iter()
+                .collect();
+
+            // Collect the blocks building on blocks removed at the previous height.
+            if !removed_prev.is_empty() {
+                for hash in self.chain_store.get_block_hashes_at(height, Some(txn)) {
+                    if let Some(chain_info) = self.chain_store.get_chain_info(&hash, false, Some(txn)) {
+                        if removed_prev.contains(&chain_info.head.header.prev_hash) {
+                            to_remove.insert(hash);
+                        }
+                    }
+                }
+            }
+
+            if to_remove.is_empty() && blocks_by_height.is_empty() {
+                break;
+            }
+
+            for hash in to_remove.iter() {
+                self.chain_store.remove_chain_info(txn, hash, height);
+            }
+            num_removed += to_remove.len();
+
+            removed_prev = to_remove;
+            height += 1;
+        }
+
+        return num_removed;
+    }
+
+    /// Writes the main chain blocks from height `from` to `to` (inclusive) in the bootstrap format.
+    /// Returns the number of exported blocks.
+    pub fn export_range<W: io::Write>(&self, from: u32, to: u32, writer: &mut W) -> Result<u32, BootstrapError> {
+        BootstrapHeader::new(self.network_id).serialize(writer)?;
+
+        let read_txn = ReadTransaction::new(self.env);
+        let mut num_blocks = 0;
+        for height in from..=to {
+            let block = self.chain_store.get_chain_info_at(height, true, Some(&read_txn))
+                .map(|chain_info| chain_info.head)
+                .filter(|block| block.body.is_some())
+                .ok_or(BootstrapError::BlockNotFound(height))?;
+            (block.serialized_size() as u32).serialize(writer)?;
+            block.serialize(writer)?;
+            num_blocks += 1;
+        }
+        return Ok(num_blocks);
+    }
+
+    /// Writes the accounts state after the current head in the accounts state format.
+    pub fn export_accounts<W: io::Write>(&self, writer: &mut W) -> Result<AccountsStateHeader, AccountsStateError> {
+        // Read the head and the accounts from the same snapshot, so that pushes don't wait for the export.
+        let accounts = Accounts::open(self.env);
+        let read_txn = ReadTransaction::new(self.env);
+        let head_hash = self.chain_store.get_head(Some(&read_txn)).unwrap();
+        let head = self.chain_store.get_block(&head_hash, false, Some(&read_txn)).unwrap();
+        let header = AccountsStateHeader {
+            version: AccountsStateHeader::VERSION,
+            network_id: self.network_id,
+            block_hash: head_hash,
+            block_height: head.header.height,
+            accounts_hash: head.header.accounts_hash,
+        };
+        export_accounts(&accounts, &read_txn, &header, writer)?;
+        return Ok(header);
+    }
+
+    /// Checks that the accounts at the head hold exactly the coins minted up to the head.
+    pub fn audit_supply(&self) -> SupplyAudit {
+        let accounts = Accounts::open(self.env);
+        let read_txn = ReadTransaction::new(self.env);
+        let head_hash = self.chain_store.get_head(Some(&read_txn)).unwrap();
+        let head = self.chain_store.get_block(&head_hash, false, Some(&read_txn)).unwrap();
+        return audit_supply(&accounts, &read_txn, self.network_id, head.header.height);
+    }
+
+    /// Deletes the bodies of all blocks that are more than `depth` blocks behind the head.
+    /// Headers are kept. `depth` must be at least `TRANSACTION_VALIDITY_WINDOW`, so that the bodies
+    /// needed to revert blocks and to rebuild the TransactionCache remain available.
+    /// Returns the number of deleted bodies.
+    pub fn prune_bodies(&self, depth: u32) -> usize {
+        assert!(depth >= policy::TRANSACTION_VALIDITY_WINDOW, "Pruning depth must be at least TRANSACTION_VALIDITY_WINDOW");
+
+        // Only one push operation at a time.
+        let lock = self.push_lock.lock();
+
+        let cutoff = self.height().saturating_sub(depth);
+        let mut txn = WriteTransaction::new(self.env);
+        let num_pruned = self.chain_store.prune_bodies(&mut txn, cutoff);
+        if let Err(e) = txn.commit() {
+            error!("Failed to prune block bodies - {}", e);
+            return 0;
+        }
+
+        debug!("Pruned {} block bodies up to height #{}", num_pruned, cutoff);
+        return num_pruned;
+    }
+
+    /// Returns the account at `address` as it was after the main chain block at `height`.
+    /// The state is reconstructed by reverting all blocks above `height` in a transaction that is
+    /// aborted afterwards. Other writes wait for that transaction, so the query is limited to
+    /// `MAX_ACCOUNT_HISTORY_DEPTH` blocks below the head.
+    /// Fails if `height` is above the head or too far below it, or if a block body that needs to be
+    /// reverted was pruned. Addresses without an account get the initial account, like `Accounts::get`.
+    pub fn get_account_at(&self, address: &Address, height: u32) -> Result<Account, AccountHistoryError> {
+        // The head can't move while we hold the write transaction, so read it from there.
+        // Pushes take the write transaction before the state lock, so take them in the same order.
+        let mut txn = WriteTransaction::new(self.env);
+        let state = self.state.read();
+        let accounts = &state.accounts;
+        let head_hash = self.chain_store.get_head(Some(&txn)).unwrap();
+        let mut block = self.chain_store.get_block(&head_hash, true, Some(&txn))
+            .or_else(|| self.chain_store.get_block(&head_hash, false, Some(&txn)))
+            .expect("Corrupted store: Failed to find head block");
+
+        let head_height = block.header.height;
+        if height > head_height {
+            txn.abort();
+            return Err(AccountHistoryError::AboveHead(height));
+        }
+        if head_height - height > Self::MAX_ACCOUNT_HISTORY_DEPTH {
+            txn.abort();
+            return Err(AccountHistoryError::TooDeep(height));
+        }
+
+        while block.header.height > height {
+            if block.body.is_none() {
+                txn.abort();
+                return Err(AccountHistoryError::Pruned(block.header.height));
+            }
+            if let Err(e) = accounts.revert_block(&mut txn, &block) {
+                error!("Failed to revert block #{} for a historical account query - {}", block.header.height, e);
+                txn.abort();
+                return Err(AccountHistoryError::RevertFailed(e));
+            }
+            block = self.chain_store.get_block(&block.header.prev_hash, true, Some(&txn))
+                .or_else(|| self.chain_store.get_block(&block.header.prev_hash, false, Some(&txn)))
+                .expect("Corrupted store: Failed to find main chain predecessor");
+        }
+
+        let account = accounts.get(address, Some(&txn));
+        txn.abort();
+        return Ok(account);
+    }
+
+    /// Returns the accounts hash after applying `body` to the accounts at the head as the block at `block_height`.
+    pub fn accounts_hash_with_block_body(&self, body: &BlockBody, block_height: u32) -> Result<Blake2bHash, AccountError> {
+        // Pushes take the write transaction before the state lock, so take them in the same order.
+        let mut txn = WriteTransaction::new(self.env);
+        let state = self.state.read();
+        state.accounts.commit_block_body(&mut txn, body, block_height)?;
+        let hash = state.accounts.hash(Some(&txn));
+        txn.abort();
+        return Ok(hash);
+    }
+
+    /// Applies `transaction` to copies of the accounts at the head as if it was included in the
+    /// next block. Nothing is written to the database.
+    /// The validity window and whether the transaction was already mined are not checked.
+    /// Only the sender and recipient accounts are involved, so they are copied instead of
+    /// committing the transaction in an aborted `WriteTransaction`, which would wait for pushes.
+    pub fn simulate_transaction(&self, transaction: &Transaction) -> Result<TransactionSimulation, AccountError> {
+        // Intrinsic verification, including the checks of the sender and recipient account types.
+        transaction.verify(self.network_id)?;
+
+        let state = self.state.read();
+        let block_height = state.main_chain.head.header.height + 1;
+        let sender = state.accounts.get(&transaction.sender, None);
+        let recipient = state.accounts.get(&transaction.recipient, None);
+        return TransactionSimulation::apply(transaction, block_height, sender, recipient);
+    }
+
+    /// Returns the height up to which block bodies have been deleted, 0 if the store is not pruned.
+    pub fn pruned_height(&self) -> u32 {
+        self.chain_store.get_pruned_height(None)
     }
 
     pub fn get_next_target(&self, head_hash: Option<&Blake2bHash>) -> Target {
+        // The difficulty of development networks is fixed by their genesis block.
+        let network_info = get_network_info(self.network_id).unwrap();
+        if network_info.development {
+            return Target::from(network_info.genesis_block.header.n_bits);
+        }
+
         let state = self.state.read();
 
         let chain_info;
@@ -544,6 +940,10 @@ impl<'env> Blockchain<'env> {
         let chain_info_opt = self.chain_store.get_chain_info(hash, include_body, None);
         if chain_info_opt.is_some() {
             let chain_info = chain_info_opt.unwrap();
+            // The body might have been pruned.
+            if include_body && chain_info.head.body.is_none() {
+                return None;
+            }
             if chain_info.on_main_chain || include_forks {
                 return Some(chain_info.head);
             }
@@ -0,0 +1,116 @@
+use beserial::{Deserialize, Serialize, SerializingError, BigEndian, ByteOrder};
+use std::fmt;
+use std::io;
+
+use crate::consensus::base::block::Block;
+use crate::consensus::base::blockchain::{Blockchain, PushResult};
+use crate::consensus::networks::NetworkId;
+
+/// Header of a bootstrap file. It is followed by a sequence of blocks (with body),
+/// each prefixed by its serialized length as `u32`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BootstrapHeader {
+    pub version: u16,
+    pub network_id: NetworkId,
+}
+
+impl BootstrapHeader {
+    pub const VERSION: u16 = 1;
+
+    pub fn new(network_id: NetworkId) -> Self {
+        BootstrapHeader { version: BootstrapHeader::VERSION, network_id }
+    }
+}
+
+#[derive(Debug)]
+pub enum BootstrapError {
+    IoError(io::Error),
+    SerializingError(SerializingError),
+    UnsupportedVersion(u16),
+    NetworkMismatch(NetworkId),
+    BlockNotFound(u32),
+    BlockTooLarge(u32),
+    PushFailed(u32, PushResult),
+}
+
+impl fmt::Display for BootstrapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BootstrapError::IoError(e) => write!(f, "I/O error: {}", e),
+            BootstrapError::SerializingError(e) => write!(f, "Serialization error: {:?}", e),
+            BootstrapError::UnsupportedVersion(version) => write!(f, "Unsupported bootstrap file version {}", version),
+            BootstrapError::NetworkMismatch(network_id) => write!(f, "Bootstrap file is for network {:?}", network_id),
+            BootstrapError::BlockNotFound(height) => write!(f, "Block #{} not found (or its body was pruned)", height),
+            BootstrapError::BlockTooLarge(len) => write!(f, "Block of {} bytes exceeds the maximum block size", len),
+            BootstrapError::PushFailed(height, result) => write!(f, "Failed to push block #{}: {:?}", height, result),
+        }
+    }
+}
+
+impl From<io::Error> for BootstrapError {
+    fn from(e: io::Error) -> Self {
+        BootstrapError::IoError(e)
+    }
+}
+
+impl From<SerializingError> for BootstrapError {
+    fn from(e: SerializingError) -> Self {
+        BootstrapError::SerializingError(e)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ImportProgress {
+    /// Number of blocks read from the bootstrap file.
+    pub num_blocks: u32,
+    /// Number of blocks we already knew.
+    pub num_known: u32,
+    /// Height of the last block read.
+    pub height: u32,
+}
+
+/// Reads a bootstrap file and pushes its blocks into the blockchain.
+/// `on_progress` is called after every pushed block.
+pub fn import_blocks<R, F>(blockchain: &Blockchain, reader: &mut R, mut on_progress: F) -> Result<ImportProgress, BootstrapError>
+    where R: io::Read, F: FnMut(&ImportProgress) {
+    let header: BootstrapHeader = Deserialize::deserialize(reader)?;
+    if header.version != BootstrapHeader::VERSION {
+        return Err(BootstrapError::UnsupportedVersion(header.version));
+    }
+    if header.network_id != blockchain.network_id {
+        return Err(BootstrapError::NetworkMismatch(header.network_id));
+    }
+
+    let mut progress = ImportProgress::default();
+    while let Some(len) = read_block_len(reader)? {
+        // Don't trust the length prefix with the allocation.
+        if len as usize > Block::MAX_SIZE {
+            return Err(BootstrapError::BlockTooLarge(len));
+        }
+        let mut buf = vec![0u8; len as usize];
+        reader.read_exact(&mut buf)?;
+        let block = Block::deserialize_from_vec(&buf)?;
+        let height = block.header.height;
+
+        match blockchain.push(block) {
+            PushResult::Extended | PushResult::Rebranched | PushResult::Forked => {},
+            PushResult::Known => progress.num_known += 1,
+            result => return Err(BootstrapError::PushFailed(height, result)),
+        }
+
+        progress.num_blocks += 1;
+        progress.height = height;
+        on_progress(&progress);
+    }
+    return Ok(progress);
+}
+
+/// Reads the length prefix of the next block, or returns `None` at the end of the file.
+fn read_block_len<R: io::Read>(reader: &mut R) -> Result<Option<u32>, BootstrapError> {
+    let mut buf = [0u8; 4];
+    if reader.read(&mut buf[..1])? == 0 {
+        return Ok(None);
+    }
+    reader.read_exact(&mut buf[1..])?;
+    return Ok(Some(BigEndian::read_u32(&buf)));
+}
@@ -0,0 +1,403 @@
+use beserial::Serialize;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::consensus::base::account::{Account, AccountError, Accounts, PrunedAccount, VestingContract};
+use crate::consensus::base::block::{Block, BlockBody, BlockError, BlockHeader, Target, TargetCompact};
+use crate::consensus::base::blockchain::{Blockchain, PushError, PushResult};
+use crate::consensus::base::primitive::{Address, Coin};
+use crate::consensus::base::primitive::crypto::{KeyPair, PrivateKey};
+use crate::consensus::base::primitive::hash::{Blake2bHash, Hash};
+use crate::consensus::base::transaction::{SignatureProof, Transaction, TransactionError};
+use crate::consensus::networks::{NetworkId, get_network_info};
+use crate::consensus::policy;
+use crate::utils::db::{Environment, WriteTransaction};
+use crate::utils::db::memory::MemoryEnvironment;
+
+/// Builds valid blocks, forks and deliberately invalid blocks on top of a `Blockchain`, for tests
+/// and benchmarks. Built blocks don't need to be pushed to be built upon.
+///
+/// Targets are taken from the blockchain. On development networks like `NetworkId::Dummy` they never
+/// change and mining is cheap. On other networks, predecessors have to be pushed before building on
+/// them and mining takes as long as it usually does.
+pub struct ChainBuilder<'env, 'bc> {
+    blockchain: &'bc Blockchain<'env>,
+    /// Accounts after block `accounts_head`, used to compute accounts hashes.
+    env: Environment,
+    state: Mutex<ChainBuilderState>,
+}
+
+struct ChainBuilderState {
+    accounts_head: Option<Blake2bHash>,
+    /// Blocks built by this builder, by hash.
+    blocks: HashMap<Blake2bHash, Block>,
+}
+
+/// The ways `BlockTemplate::build_invalid` can break a block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Defect {
+    UnsupportedVersion,
+    FromTheFuture,
+    InvalidPoW,
+    SizeExceeded,
+    InterlinkHashMismatch,
+    BodyHashMismatch,
+    DuplicateTransaction,
+    InvalidTransaction,
+    ExpiredTransaction,
+    TransactionsNotOrdered,
+    DuplicatePrunedAccount,
+    PrunedAccountsNotOrdered,
+    InvalidPrunedAccount,
+    InvalidSuccessor,
+    DifficultyMismatch,
+    /// Includes a transaction of the last `TRANSACTION_VALIDITY_WINDOW` blocks again.
+    /// There has to be such a transaction that is still valid at the new block's height.
+    ReplayedTransaction,
+    /// Spends funds that the sender doesn't have. A block with this defect is accepted on a fork,
+    /// pushing a successor that makes the fork the hardest chain fails with `PushError::InvalidFork`.
+    AccountsError,
+}
+
+impl Defect {
+    pub const ALL: [Defect; 17] = [
+        Defect::UnsupportedVersion,
+        Defect::FromTheFuture,
+        Defect::InvalidPoW,
+        Defect::SizeExceeded,
+        Defect::InterlinkHashMismatch,
+        Defect::BodyHashMismatch,
+        Defect::DuplicateTransaction,
+        Defect::InvalidTransaction,
+        Defect::ExpiredTransaction,
+        Defect::TransactionsNotOrdered,
+        Defect::DuplicatePrunedAccount,
+        Defect::PrunedAccountsNotOrdered,
+        Defect::InvalidPrunedAccount,
+        Defect::InvalidSuccessor,
+        Defect::DifficultyMismatch,
+        Defect::ReplayedTransaction,
+        Defect::AccountsError,
+    ];
+
+    /// The error with which `Blockchain::push` rejects a block with this defect on top of the head.
+    pub fn expected_error(&self) -> PushError {
+        match self {
+            Defect::UnsupportedVersion => PushError::InvalidBlock(BlockError::UnsupportedVersion),
+            Defect::FromTheFuture => PushError::InvalidBlock(BlockError::FromTheFuture),
+            Defect::InvalidPoW => PushError::InvalidBlock(BlockError::InvalidPoW),
+            Defect::SizeExceeded => PushError::InvalidBlock(BlockError::SizeExceeded),
+            Defect::InterlinkHashMismatch => PushError::InvalidBlock(BlockError::InterlinkHashMismatch),
+            Defect::BodyHashMismatch => PushError::InvalidBlock(BlockError::BodyHashMismatch),
+            Defect::DuplicateTransaction => PushError::InvalidBlock(BlockError::DuplicateTransaction),
+            Defect::InvalidTransaction => PushError::InvalidBlock(BlockError::InvalidTransaction(TransactionError::ForeignNetwork)),
+            Defect::ExpiredTransaction => PushError::InvalidBlock(BlockError::ExpiredTransaction),
+            Defect::TransactionsNotOrdered => PushError::InvalidBlock(BlockError::TransactionsNotOrdered),
+            Defect::DuplicatePrunedAccount => PushError::InvalidBlock(BlockError::DuplicatePrunedAccount),
+            Defect::PrunedAccountsNotOrdered => PushError::InvalidBlock(BlockError::PrunedAccountsNotOrdered),
+            Defect::InvalidPrunedAccount => PushError::InvalidBlock(BlockError::InvalidPrunedAccount),
+            Defect::InvalidSuccessor => PushError::InvalidSuccessor,
+            Defect::DifficultyMismatch => PushError::DifficultyMismatch,
+            Defect::ReplayedTransaction => PushError::DuplicateTransaction,
+            Defect::AccountsError => PushError::AccountsError(AccountError::InsufficientFunds),
+        }
+    }
+}
+
+impl<'env, 'bc> ChainBuilder<'env, 'bc> {
+    pub fn new(blockchain: &'bc Blockchain<'env>) -> Self {
+        ChainBuilder {
+            blockchain,
+            env: MemoryEnvironment::new(),
+            state: Mutex::new(ChainBuilderState {
+                accounts_head: None,
+                blocks: HashMap::new(),
+            }),
+        }
+    }
+
+    /// Starts a block on top of the head of the main chain.
+    pub fn next_block<'b>(&'b self) -> BlockTemplate<'env, 'bc, 'b> {
+        self.block_on(&self.blockchain.head_hash())
+    }
+
+    /// Starts a block on top of `prev_hash`, which may be any block of the blockchain or a block
+    /// built by this builder.
+    pub fn block_on<'b>(&'b self, prev_hash: &Blake2bHash) -> BlockTemplate<'env, 'bc, 'b> {
+        let prev = self.get_block(prev_hash).expect("ChainBuilder: unknown predecessor");
+        BlockTemplate::new(self, prev)
+    }
+
+    /// Builds `count` valid empty blocks on top of `prev_hash` without pushing them,
+    /// e.g. to fork from an arbitrary ancestor.
+    pub fn chain_on(&self, prev_hash: &Blake2bHash, count: u32) -> Vec<Block> {
+        let mut blocks = Vec::with_capacity(count as usize);
+        let mut prev_hash = prev_hash.clone();
+        for _ in 0..count {
+            let block = self.block_on(&prev_hash).build();
+            prev_hash = block.header.hash();
+            blocks.push(block);
+        }
+        blocks
+    }
+
+    /// Builds and pushes `count` valid empty blocks on top of the head.
+    /// Panics if a block doesn't extend the main chain.
+    pub fn extend(&self, count: u32) -> Vec<Block> {
+        let mut blocks = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let block = self.next_block().build();
+            assert_eq!(self.blockchain.push(block.clone()), PushResult::Extended, "ChainBuilder: block didn't extend the main chain");
+            blocks.push(block);
+        }
+        blocks
+    }
+
+    fn get_block(&self, hash: &Blake2bHash) -> Option<Block> {
+        let state = self.state.lock();
+        self.get_block_locked(&state, hash)
+    }
+
+    fn get_block_locked(&self, state: &ChainBuilderState, hash: &Blake2bHash) -> Option<Block> {
+        state.blocks.get(hash).cloned()
+            .or_else(|| self.blockchain.get_block(hash, true, true))
+    }
+
+    fn remember(&self, block: &Block) {
+        self.state.lock().blocks.insert(block.header.hash(), block.clone());
+    }
+
+    /// Computes the accounts hash after applying `body` on top of block `prev_hash`.
+    /// Returns `None` if the body or one of the predecessors doesn't apply.
+    fn accounts_hash(&self, prev_hash: &Blake2bHash, body: &BlockBody, block_height: u32) -> Option<Blake2bHash> {
+        let mut state = self.state.lock();
+        let accounts = Accounts::new(&self.env);
+        if state.accounts_head.as_ref() != Some(prev_hash) {
+            let accounts_head = self.replay(&state, &accounts, prev_hash);
+            state.accounts_head = accounts_head;
+            if state.accounts_head.is_none() {
+                return None;
+            }
+        }
+        accounts.hash_with_block_body(body, block_height).ok()
+    }
+
+    /// Brings the accounts to the state after block `hash`. Only the blocks since the current
+    /// accounts head are applied if it is an ancestor, otherwise the accounts are rebuilt from genesis.
+    /// Returns the new accounts head, or `None` if a block body is missing or doesn't apply.
+    fn replay(&self, state: &ChainBuilderState, accounts: &Accounts, hash: &Blake2bHash) -> Option<Blake2bHash> {
+        let genesis_hash = &get_network_info(self.blockchain.network_id).unwrap().genesis_hash;
+
+        let mut blocks = Vec::new();
+        let mut current = hash.clone();
+        while state.accounts_head.as_ref() != Some(&current) && &current != genesis_hash {
+            let block = self.get_block_locked(state, &current)?;
+            current = block.header.prev_hash.clone();
+            blocks.push(block);
+        }
+
+        let mut txn = WriteTransaction::new(&self.env);
+        if state.accounts_head.as_ref() != Some(&current) {
+            accounts.clear(&mut txn);
+            accounts.init(&mut txn, self.blockchain.network_id);
+        }
+        for block in blocks.iter().rev() {
+            if accounts.commit_block(&mut txn, block).is_err() {
+                txn.abort();
+                return None;
+            }
+        }
+        txn.commit().ok()?;
+        Some(hash.clone())
+    }
+}
+
+/// A block under construction, see `ChainBuilder`. Transactions are put into block order and
+/// everything derived from the contents (hashes, interlink and proof of work) is filled in on build.
+pub struct BlockTemplate<'env, 'bc, 'b> {
+    builder: &'b ChainBuilder<'env, 'bc>,
+    prev: Block,
+    header: BlockHeader,
+    body: BlockBody,
+}
+
+impl<'env, 'bc, 'b> BlockTemplate<'env, 'bc, 'b> {
+    fn new(builder: &'b ChainBuilder<'env, 'bc>, prev: Block) -> Self {
+        let prev_hash: Blake2bHash = prev.header.hash();
+        let next_target = builder.blockchain.get_next_target(Some(&prev_hash));
+        let header = BlockHeader {
+            version: Block::VERSION,
+            prev_hash,
+            interlink_hash: [0u8; Blake2bHash::SIZE].into(),
+            body_hash: [0u8; Blake2bHash::SIZE].into(),
+            accounts_hash: [0u8; Blake2bHash::SIZE].into(),
+            n_bits: TargetCompact::from(&next_target),
+            height: prev.header.height + 1,
+            timestamp: prev.header.timestamp + policy::BLOCK_TIME,
+            nonce: 0,
+        };
+        BlockTemplate {
+            builder,
+            prev,
+            header,
+            body: BlockBody {
+                miner: [0u8; Address::SIZE].into(),
+                extra_data: Vec::new(),
+                transactions: Vec::new(),
+                pruned_accounts: Vec::new(),
+            },
+        }
+    }
+
+    pub fn with_miner(mut self, miner: Address) -> Self {
+        self.body.miner = miner;
+        self
+    }
+
+    pub fn with_extra_data(mut self, extra_data: Vec<u8>) -> Self {
+        self.body.extra_data = extra_data;
+        self
+    }
+
+    pub fn with_transactions(mut self, transactions: Vec<Transaction>) -> Self {
+        self.body.transactions = transactions;
+        self
+    }
+
+    /// Pruned accounts are not derived from the transactions, they have to be given if the
+    /// transactions empty a contract.
+    pub fn with_pruned_accounts(mut self, pruned_accounts: Vec<PrunedAccount>) -> Self {
+        self.body.pruned_accounts = pruned_accounts;
+        self
+    }
+
+    pub fn with_timestamp(mut self, timestamp: u32) -> Self {
+        self.header.timestamp = timestamp;
+        self
+    }
+
+    pub fn build(self) -> Block {
+        self.assemble(None)
+    }
+
+    /// Builds a block that is valid except for `defect`. Contents given to the template may be
+    /// replaced to produce the defect.
+    pub fn build_invalid(self, defect: Defect) -> Block {
+        self.assemble(Some(defect))
+    }
+
+    fn assemble(mut self, defect: Option<Defect>) -> Block {
+        let network_id = self.builder.blockchain.network_id;
+        let height = self.header.height;
+
+        match defect {
+            Some(Defect::UnsupportedVersion) => self.header.version = Block::VERSION + 1,
+            Some(Defect::FromTheFuture) => {
+                let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as u32;
+                self.header.timestamp = now + 86400;
+            },
+            Some(Defect::SizeExceeded) => {
+                let tx = signed_transaction(1, height, network_id);
+                self.body.transactions = vec![tx; Block::MAX_SIZE / Transaction::MIN_SIZE + 1];
+            },
+            Some(Defect::DuplicateTransaction) => {
+                let tx = signed_transaction(1, height, network_id);
+                self.body.transactions = vec![tx.clone(), tx];
+            },
+            Some(Defect::InvalidTransaction) => {
+                let foreign_network_id = if network_id == NetworkId::Main { NetworkId::Test } else { NetworkId::Main };
+                self.body.transactions = vec![signed_transaction(1, height, foreign_network_id)];
+            },
+            Some(Defect::ExpiredTransaction) => self.body.transactions = vec![signed_transaction(1, height + 1, network_id)],
+            Some(Defect::DuplicatePrunedAccount) => self.body.pruned_accounts = vec![pruned_account(1), pruned_account(1)],
+            Some(Defect::PrunedAccountsNotOrdered) => self.body.pruned_accounts = vec![pruned_account(2), pruned_account(1)],
+            Some(Defect::InvalidPrunedAccount) => self.body.pruned_accounts = vec![PrunedAccount {
+                address: Address::from([1u8; Address::SIZE]),
+                account: Account::new_basic(Coin::from(1)),
+            }],
+            Some(Defect::InvalidSuccessor) => self.header.timestamp = self.prev.header.timestamp - 1,
+            Some(Defect::DifficultyMismatch) => self.header.n_bits = TargetCompact::from(u32::from(self.header.n_bits) - 1),
+            Some(Defect::ReplayedTransaction) => self.body.transactions = vec![self.find_replayable_transaction()],
+            Some(Defect::AccountsError) => self.body.transactions = vec![signed_transaction(1, height, network_id)],
+            _ => {},
+        }
+
+        match defect {
+            Some(Defect::TransactionsNotOrdered) => {
+                let mut transactions = vec![signed_transaction(1, height, network_id), signed_transaction(2, height, network_id)];
+                transactions.sort_by(|a, b| b.cmp_block_order(a));
+                self.body.transactions = transactions;
+            },
+            _ => self.body.transactions.sort_by(|a, b| a.cmp_block_order(b)),
+        }
+
+        let interlink = self.prev.get_next_interlink(&Target::from(self.header.n_bits));
+        self.header.interlink_hash = interlink.hash(network_id);
+        self.header.body_hash = self.body.hash();
+        self.header.accounts_hash = self.builder.accounts_hash(&self.header.prev_hash, &self.body, height)
+            .unwrap_or([0u8; Blake2bHash::SIZE].into());
+
+        match defect {
+            Some(Defect::InterlinkHashMismatch) => self.header.interlink_hash = [0u8; Blake2bHash::SIZE].into(),
+            Some(Defect::BodyHashMismatch) => self.header.body_hash = [0u8; Blake2bHash::SIZE].into(),
+            _ => {},
+        }
+
+        while !self.header.verify_proof_of_work() {
+            self.header.nonce += 1;
+        }
+        if defect == Some(Defect::InvalidPoW) {
+            while self.header.verify_proof_of_work() {
+                self.header.nonce += 1;
+            }
+        }
+
+        let block = Block {
+            header: self.header,
+            interlink,
+            body: Some(self.body),
+        };
+        self.builder.remember(&block);
+        block
+    }
+
+    fn find_replayable_transaction(&self) -> Transaction {
+        let mut block = self.prev.clone();
+        for _ in 0..policy::TRANSACTION_VALIDITY_WINDOW {
+            let body = block.body.as_ref().expect("ChainBuilder: block body missing");
+            if let Some(tx) = body.transactions.iter().find(|tx| tx.is_valid_at(self.header.height)) {
+                return tx.clone();
+            }
+            if block.header.height == 1 {
+                break;
+            }
+            block = self.builder.get_block(&block.header.prev_hash).expect("ChainBuilder: unknown predecessor");
+        }
+        panic!("ChainBuilder: no transaction to replay");
+    }
+}
+
+/// A basic transaction from an account of a fixed key that nobody funds.
+fn signed_transaction(value: u64, validity_start_height: u32, network_id: NetworkId) -> Transaction {
+    let keypair: KeyPair = PrivateKey::from([1u8; PrivateKey::SIZE]).into();
+    let mut tx = Transaction::new_basic(Address::from(&keypair.public), Address::from([2u8; Address::SIZE]), Coin::from(value), Coin::ZERO, validity_start_height, network_id);
+    let signature_proof = SignatureProof::from(keypair.public.clone(), keypair.sign(&tx.serialize_content()));
+    tx.proof = signature_proof.serialize_to_vec();
+    tx
+}
+
+/// An empty vesting contract, which is to be pruned.
+fn pruned_account(address_byte: u8) -> PrunedAccount {
+    PrunedAccount {
+        address: Address::from([address_byte; Address::SIZE]),
+        account: Account::Vesting(VestingContract {
+            balance: Coin::ZERO,
+            owner: Address::from([address_byte; Address::SIZE]),
+            vesting_start: 0,
+            vesting_step_blocks: 1,
+            vesting_step_amount: Coin::ZERO,
+            vesting_total_amount: Coin::ZERO,
+        }),
+    }
+}
@@ -1,7 +1,8 @@
 use crate::consensus::base::block::Block;
 use crate::consensus::base::blockchain::ChainInfo;
 use crate::consensus::base::primitive::hash::Blake2bHash;
-use crate::utils::db::{Environment, Database, DatabaseFlags, Transaction, ReadTransaction, WriteTransaction};
+use crate::utils::db::{Environment, DatabaseFlags, ReadableEnvironment, ReadOnlyEnvironment, Transaction, ReadTransaction, WriteTransaction};
+use crate::utils::db::table::{Table, TableSpec};
 use crate::network::message::GetBlocksDirection;
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd)]
@@ -19,37 +20,81 @@ impl From<GetBlocksDirection> for Direction {
     }
 }
 
+/// Chain infos by block hash. The metadata of the store lives in the same database under string keys.
+#[derive(Debug)]
+struct ChainTable;
+
+impl TableSpec for ChainTable {
+    const NAME: &'static str = "ChainData";
+    type Key = Blake2bHash;
+    type Value = ChainInfo;
+}
+
+/// Blocks including their bodies by block hash.
+#[derive(Debug)]
+struct BlockTable;
+
+impl TableSpec for BlockTable {
+    const NAME: &'static str = "Block";
+    type Key = Blake2bHash;
+    type Value = Block;
+}
+
+/// Hashes of all blocks at a height.
+#[derive(Debug)]
+struct HeightIndex;
+
+impl TableSpec for HeightIndex {
+    const NAME: &'static str = "HeightIdx";
+    type Key = u32;
+    type Value = Blake2bHash;
+
+    fn flags() -> DatabaseFlags {
+        DatabaseFlags::DUPLICATE_KEYS | DatabaseFlags::DUP_FIXED_SIZE_VALUES
+    }
+}
+
 #[derive(Debug)]
 pub struct ChainStore<'env> {
-    env: &'env Environment,
-    chain_db: Database<'env>,
-    block_db: Database<'env>,
-    height_idx: Database<'env>
+    env: ReadableEnvironment<'env>,
+    chain_table: Table<'env, ChainTable>,
+    block_table: Table<'env, BlockTable>,
+    height_idx: Table<'env, HeightIndex>,
 }
 
 impl<'env> ChainStore<'env> {
-    const CHAIN_DB_NAME: &'static str = "ChainData";
-    const BLOCK_DB_NAME: &'static str = "Block";
-    const HEIGHT_IDX_NAME: &'static str = "HeightIdx";
     const HEAD_KEY: &'static str = "head";
+    const PRUNED_HEIGHT_KEY: &'static str = "prunedHeight";
+    const FORK_PRUNING_HEIGHT_KEY: &'static str = "forkPruningHeight";
 
     pub fn new(env: &'env Environment) -> Self {
-        let chain_db = env.open_database(Self::CHAIN_DB_NAME.to_string());
-        let block_db = env.open_database(Self::BLOCK_DB_NAME.to_string());
-        let height_idx = env.open_database_with_flags(Self::HEIGHT_IDX_NAME.to_string(),
-            DatabaseFlags::DUPLICATE_KEYS | DatabaseFlags::DUP_FIXED_SIZE_VALUES);
-        return ChainStore { env, chain_db, block_db, height_idx };
+        return ChainStore {
+            env: ReadableEnvironment::Writable(env),
+            chain_table: Table::open(env),
+            block_table: Table::open(env),
+            height_idx: Table::open(env),
+        };
+    }
+
+    /// Opens the chain store of a read-only environment. The store must have been created before.
+    pub fn new_read_only(env: &'env ReadOnlyEnvironment) -> ReadOnlyChainStore<'env> {
+        return ReadOnlyChainStore(ChainStore {
+            env: ReadableEnvironment::ReadOnly(env),
+            chain_table: Table::open_read_only(env),
+            block_table: Table::open_read_only(env),
+            height_idx: Table::open_read_only(env),
+        });
     }
 
     pub fn get_head(&self, txn_option: Option<&Transaction>) -> Option<Blake2bHash> {
         return match txn_option {
-            Some(txn) => txn.get(&self.chain_db, ChainStore::HEAD_KEY),
-            None => ReadTransaction::new(self.env).get(&self.chain_db, ChainStore::HEAD_KEY)
+            Some(txn) => txn.get(self.chain_table.database(), ChainStore::HEAD_KEY),
+            None => self.env.read_transaction().get(self.chain_table.database(), ChainStore::HEAD_KEY)
         };
     }
 
     pub fn set_head(&self, txn: &mut WriteTransaction, hash: &Blake2bHash) {
-        txn.put(&self.chain_db, ChainStore::HEAD_KEY, hash);
+        txn.put(self.chain_table.database(), ChainStore::HEAD_KEY, hash);
     }
 
     pub fn get_chain_info(&self, hash: &Blake2bHash, include_body: bool, txn_option: Option<&Transaction>) -> Option<ChainInfo> {
@@ -57,21 +102,23 @@ impl<'env> ChainStore<'env> {
         let txn = match txn_option {
             Some(txn) => txn,
             None => {
-                read_txn = ReadTransaction::new(self.env);
+                read_txn = self.env.read_transaction();
                 &read_txn
             }
         };
 
-        let mut chain_info: ChainInfo = match txn.get(&self.chain_db, hash) {
+        let mut chain_info = match self.chain_table.get(txn, hash) {
             Some(data) => data,
             None => return None
         };
 
         if include_body {
-            if let Some(block) = txn.get(&self.block_db, hash) {
+            if let Some(block) = self.block_table.get(txn, hash) {
                 chain_info.head = block;
-            } else {
+            } else if chain_info.head.header.height > self.get_pruned_height(Some(txn)) {
                 warn!("Block body requested but not present");
+            } else {
+                trace!("Block body requested but pruned");
             }
         }
 
@@ -80,53 +127,110 @@ impl<'env> ChainStore<'env> {
 
     pub fn put_chain_info(&self, txn: &mut WriteTransaction, hash: &Blake2bHash, chain_info: &ChainInfo, include_body: bool) {
         // Store chain data. Block body will not be persisted.
-        txn.put_reserve(&self.chain_db, hash, chain_info);
+        self.chain_table.put(txn, hash, chain_info);
 
         // Store body if requested.
         if include_body && chain_info.head.body.is_some() {
-            txn.put_reserve(&self.block_db, hash, &chain_info.head);
+            self.block_table.put(txn, hash, &chain_info.head);
         }
 
         // Add to height index.
         let height = chain_info.head.header.height;
-        txn.put(&self.height_idx, &height, hash);
+        self.height_idx.put(txn, &height, hash);
     }
 
-    pub fn get_chain_info_at(&self, block_height: u32, include_body: bool, txn_option: Option<&Transaction>) -> Option<ChainInfo> {
+    /// Removes the block with the given hash and height from the store, including its body and height index entry.
+    pub fn remove_chain_info(&self, txn: &mut WriteTransaction, hash: &Blake2bHash, height: u32) {
+        self.chain_table.remove(txn, hash);
+        self.block_table.remove(txn, hash);
+        self.height_idx.remove_item(txn, &height, hash);
+    }
+
+    /// Returns the hashes of all blocks (main chain and forks) at the given height.
+    pub fn get_block_hashes_at(&self, block_height: u32, txn_option: Option<&Transaction>) -> Vec<Blake2bHash> {
         let read_txn: ReadTransaction;
         let txn = match txn_option {
             Some(txn) => txn,
             None => {
-                read_txn = ReadTransaction::new(self.env);
+                read_txn = self.env.read_transaction();
                 &read_txn
             }
         };
 
-        // Seek to the first block at the given height.
-        let mut cursor = txn.cursor(&self.height_idx);
-        let mut block_hash = match cursor.seek_key::<u32, Blake2bHash>(&block_height) {
-            Some(hash) => hash,
-            None => return None
+        return self.height_idx.get_all(txn, &block_height);
+    }
+
+    /// Returns the height up to which block bodies have been deleted, 0 if none were.
+    pub fn get_pruned_height(&self, txn_option: Option<&Transaction>) -> u32 {
+        let pruned_height: Option<u32> = match txn_option {
+            Some(txn) => txn.get(self.chain_table.database(), ChainStore::PRUNED_HEIGHT_KEY),
+            None => self.env.read_transaction().get(self.chain_table.database(), ChainStore::PRUNED_HEIGHT_KEY)
         };
+        return pruned_height.unwrap_or(0);
+    }
+
+    /// Deletes the bodies of all blocks up to and including `block_height`. Headers are kept.
+    /// Returns the number of deleted bodies.
+    pub fn prune_bodies(&self, txn: &mut WriteTransaction, block_height: u32) -> usize {
+        let pruned_height = self.get_pruned_height(Some(txn));
+        if block_height <= pruned_height {
+            return 0;
+        }
+
+        let mut num_pruned = 0;
+        for height in (pruned_height + 1)..=block_height {
+            for hash in self.get_block_hashes_at(height, Some(txn)) {
+                self.block_table.remove(txn, &hash);
+                num_pruned += 1;
+            }
+        }
+        txn.put(self.chain_table.database(), ChainStore::PRUNED_HEIGHT_KEY, &block_height);
+        return num_pruned;
+    }
+
+    /// Returns the height up to which stale forks have been pruned, 0 if they never were.
+    pub fn get_fork_pruning_height(&self, txn_option: Option<&Transaction>) -> u32 {
+        let fork_pruning_height: Option<u32> = match txn_option {
+            Some(txn) => txn.get(self.chain_table.database(), ChainStore::FORK_PRUNING_HEIGHT_KEY),
+            None => self.env.read_transaction().get(self.chain_table.database(), ChainStore::FORK_PRUNING_HEIGHT_KEY)
+        };
+        return fork_pruning_height.unwrap_or(0);
+    }
 
-        // Iterate until we find the main chain block.
-        let mut chain_info: ChainInfo;
-        while {
-            // Loop condition
-            chain_info = txn
-                .get(&self.chain_db, &block_hash)
-                .expect("Corrupted store: ChainInfo referenced from index not found");
-            !chain_info.on_main_chain
-        } {
-            // Loop Body
-            block_hash = match cursor.next_duplicate::<u32, Blake2bHash>() {
-                Some((_, hash)) => hash,
-                None => return None
-            };
+    pub fn set_fork_pruning_height(&self, txn: &mut WriteTransaction, block_height: u32) {
+        txn.put(self.chain_table.database(), ChainStore::FORK_PRUNING_HEIGHT_KEY, &block_height);
+    }
+
+    /// Records that a fork block was stored at `block_height`. If forks have already been pruned
+    /// up to that height, the watermark is lowered so that the next pruning run rescans it.
+    pub fn touch_fork_height(&self, txn: &mut WriteTransaction, block_height: u32) {
+        let fork_pruning_height = self.get_fork_pruning_height(Some(txn));
+        if block_height <= fork_pruning_height {
+            self.set_fork_pruning_height(txn, block_height.saturating_sub(1));
         }
+    }
+
+    pub fn get_chain_info_at(&self, block_height: u32, include_body: bool, txn_option: Option<&Transaction>) -> Option<ChainInfo> {
+        let read_txn: ReadTransaction;
+        let txn = match txn_option {
+            Some(txn) => txn,
+            None => {
+                read_txn = self.env.read_transaction();
+                &read_txn
+            }
+        };
+
+        // Find the main chain block among the blocks at the given height.
+        let (block_hash, mut chain_info) = self.height_idx.get_all(txn, &block_height).into_iter()
+            .map(|hash| {
+                let chain_info = self.chain_table.get(txn, &hash)
+                    .expect("Corrupted store: ChainInfo referenced from index not found");
+                (hash, chain_info)
+            })
+            .find(|(_, chain_info)| chain_info.on_main_chain)?;
 
         if include_body {
-            if let Some(block) = txn.get(&self.block_db, &block_hash) {
+            if let Some(block) = self.block_table.get(txn, &block_hash) {
                 chain_info.head = block;
             } else {
                 warn!("Block body requested but not present");
@@ -141,15 +245,15 @@ impl<'env> ChainStore<'env> {
         let txn = match txn_option {
             Some(txn) => txn,
             None => {
-                read_txn = ReadTransaction::new(self.env);
+                read_txn = self.env.read_transaction();
                 &read_txn
             }
         };
 
         return if include_body {
-            txn.get(&self.block_db, hash)
+            self.block_table.get(txn, hash)
         } else {
-            txn.get(&self.chain_db, hash).map(|chain_info: ChainInfo| chain_info.head)
+            self.chain_table.get(txn, hash).map(|chain_info| chain_info.head)
         };
     }
 
@@ -163,7 +267,7 @@ impl<'env> ChainStore<'env> {
         let txn = match txn_option {
             Some(txn) => txn,
             None => {
-                read_txn = ReadTransaction::new(self.env);
+                read_txn = self.env.read_transaction();
                 &read_txn
             }
         };
@@ -194,7 +298,7 @@ impl<'env> ChainStore<'env> {
         let txn = match txn_option {
             Some(txn) => txn,
             None => {
-                read_txn = ReadTransaction::new(self.env);
+                read_txn = self.env.read_transaction();
                 &read_txn
             }
         };
@@ -229,3 +333,37 @@ impl<'env> ChainStore<'env> {
         }
     }
 }
+
+/// A `ChainStore` that only offers read access.
+#[derive(Debug)]
+pub struct ReadOnlyChainStore<'env>(ChainStore<'env>);
+
+impl<'env> ReadOnlyChainStore<'env> {
+    pub fn get_head(&self, txn_option: Option<&Transaction>) -> Option<Blake2bHash> {
+        self.0.get_head(txn_option)
+    }
+
+    pub fn get_chain_info(&self, hash: &Blake2bHash, include_body: bool, txn_option: Option<&Transaction>) -> Option<ChainInfo> {
+        self.0.get_chain_info(hash, include_body, txn_option)
+    }
+
+    pub fn get_chain_info_at(&self, block_height: u32, include_body: bool, txn_option: Option<&Transaction>) -> Option<ChainInfo> {
+        self.0.get_chain_info_at(block_height, include_body, txn_option)
+    }
+
+    pub fn get_block_hashes_at(&self, block_height: u32, txn_option: Option<&Transaction>) -> Vec<Blake2bHash> {
+        self.0.get_block_hashes_at(block_height, txn_option)
+    }
+
+    pub fn get_pruned_height(&self, txn_option: Option<&Transaction>) -> u32 {
+        self.0.get_pruned_height(txn_option)
+    }
+
+    pub fn get_block(&self, hash: &Blake2bHash, include_body: bool, txn_option: Option<&Transaction>) -> Option<Block> {
+        self.0.get_block(hash, include_body, txn_option)
+    }
+
+    pub fn get_blocks(&self, start_block_hash: &Blake2bHash, count: u32, include_body: bool, direction: Direction, txn_option: Option<&Transaction>) -> Vec<Block> {
+        self.0.get_blocks(start_block_hash, count, include_body, direction, txn_option)
+    }
+}
@@ -0,0 +1,312 @@
+use std::fmt;
+
+use crate::consensus::base::account::{Accounts, AccountError};
+use crate::consensus::base::blockchain::ChainStore;
+use crate::consensus::base::primitive::hash::{Blake2bHash, Hash};
+use crate::consensus::networks::{NetworkId, get_network_info};
+use crate::utils::db::{Environment, ReadTransaction, WriteTransaction, WriteError};
+
+/// Number of blocks between progress messages when rebuilding the accounts tree.
+const REPLAY_PROGRESS_INTERVAL: usize = 1000;
+/// How often the accounts tree is rebuilt when the map runs full. The map grows by at least one
+/// resize step before every attempt.
+const MAX_REPLAY_ATTEMPTS: usize = 10;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Inconsistency {
+    /// No head hash is stored.
+    MissingHead,
+    /// A main chain block is referenced but not stored.
+    MissingBlock(Blake2bHash),
+    /// The block stored under this hash has a different hash.
+    HashMismatch(Blake2bHash),
+    /// The block with this hash does not have the height expected from its successor.
+    HeightMismatch(Blake2bHash, u32),
+    /// The main chain does not start at the genesis block of the network.
+    WrongGenesis(Blake2bHash),
+    /// The body of the main chain block at this height is missing although it was not pruned.
+    MissingBody(u32),
+    /// The main chain block at this height is not flagged as being on the main chain.
+    NotOnMainChain(u32),
+    /// The main chain block at this height does not point to its main chain successor.
+    WrongSuccessor(u32),
+    /// The fork block with this hash and height is flagged as being on the main chain.
+    ForkOnMainChain(Blake2bHash, u32),
+    /// The accounts tree does not match the accounts hash of the head block.
+    AccountsHashMismatch,
+}
+
+impl Inconsistency {
+    /// Flags and the accounts tree can be rebuilt from the main chain, broken links cannot.
+    pub fn is_repairable(&self) -> bool {
+        match self {
+            Inconsistency::NotOnMainChain(_)
+            | Inconsistency::WrongSuccessor(_)
+            | Inconsistency::ForkOnMainChain(_, _)
+            | Inconsistency::AccountsHashMismatch => true,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Inconsistency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Inconsistency::MissingHead => write!(f, "No head block stored"),
+            Inconsistency::MissingBlock(hash) => write!(f, "Block {} is missing", hash),
+            Inconsistency::HashMismatch(hash) => write!(f, "Block stored as {} has a different hash", hash),
+            Inconsistency::HeightMismatch(hash, height) => write!(f, "Block {} has unexpected height #{}", hash, height),
+            Inconsistency::WrongGenesis(hash) => write!(f, "Main chain starts at {} instead of the genesis block", hash),
+            Inconsistency::MissingBody(height) => write!(f, "Body of block #{} is missing", height),
+            Inconsistency::NotOnMainChain(height) => write!(f, "Block #{} is not flagged as main chain", height),
+            Inconsistency::WrongSuccessor(height) => write!(f, "Block #{} has a wrong main chain successor", height),
+            Inconsistency::ForkOnMainChain(hash, height) => write!(f, "Fork block {} at #{} is flagged as main chain", hash, height),
+            Inconsistency::AccountsHashMismatch => write!(f, "Accounts tree does not match the head block"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ConsistencyReport {
+    /// Height of the stored head block.
+    pub head_height: u32,
+    pub inconsistencies: Vec<Inconsistency>,
+    /// Hashes of the main chain blocks that could be walked, in ascending order.
+    main_chain: Vec<Blake2bHash>,
+}
+
+impl ConsistencyReport {
+    pub fn is_consistent(&self) -> bool {
+        self.inconsistencies.is_empty()
+    }
+
+    pub fn is_repairable(&self) -> bool {
+        self.inconsistencies.iter().all(Inconsistency::is_repairable)
+    }
+}
+
+#[derive(Debug)]
+pub enum RepairError {
+    Unrepairable(Vec<Inconsistency>),
+    BodiesPruned(u32),
+    ReplayFailed(u32, AccountError),
+    WriteFailed(WriteError),
+}
+
+impl fmt::Display for RepairError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RepairError::Unrepairable(inconsistencies) => {
+                write!(f, "Database cannot be repaired, reset your consensus database:")?;
+                for inconsistency in inconsistencies.iter() {
+                    write!(f, " {};", inconsistency)?;
+                }
+                Ok(())
+            },
+            RepairError::BodiesPruned(height) => write!(f, "Cannot rebuild accounts, block bodies up to #{} were pruned", height),
+            RepairError::ReplayFailed(height, e) => write!(f, "Failed to replay block #{}: {}", height, e),
+            RepairError::WriteFailed(e) => write!(f, "Failed to write repairs: {}", e),
+        }
+    }
+}
+
+impl From<WriteError> for RepairError {
+    fn from(e: WriteError) -> Self {
+        RepairError::WriteFailed(e)
+    }
+}
+
+/// Walks the main chain from the head back to genesis and checks block links, heights,
+/// main chain flags and the accounts tree.
+pub fn check_consistency(env: &Environment, network_id: NetworkId) -> ConsistencyReport {
+    let network_info = get_network_info(network_id).unwrap();
+    let chain_store = ChainStore::new(env);
+    let accounts = Accounts::new(env);
+    let mut report = ConsistencyReport::default();
+
+    let txn = ReadTransaction::new(env);
+    let head_hash = match chain_store.get_head(Some(&txn)) {
+        Some(hash) => hash,
+        None => {
+            report.inconsistencies.push(Inconsistency::MissingHead);
+            return report;
+        }
+    };
+    let pruned_height = chain_store.get_pruned_height(Some(&txn));
+
+    let mut hash = head_hash;
+    let mut successor: Option<Blake2bHash> = None;
+    let mut expected_height: Option<u32> = None;
+    loop {
+        let chain_info = match chain_store.get_chain_info(&hash, false, Some(&txn)) {
+            Some(chain_info) => chain_info,
+            None => {
+                report.inconsistencies.push(Inconsistency::MissingBlock(hash));
+                break;
+            }
+        };
+
+        // Stop at the first broken link, we can't trust anything below it.
+        let height = chain_info.head.header.height;
+        if chain_info.head.header.hash::<Blake2bHash>() != hash {
+            report.inconsistencies.push(Inconsistency::HashMismatch(hash));
+            break;
+        }
+        match expected_height {
+            Some(expected_height) if height != expected_height => {
+                report.inconsistencies.push(Inconsistency::HeightMismatch(hash, height));
+                break;
+            },
+            Some(_) => {},
+            None => report.head_height = height,
+        }
+
+        if !chain_info.on_main_chain {
+            report.inconsistencies.push(Inconsistency::NotOnMainChain(height));
+        }
+        if chain_info.main_chain_successor != successor {
+            report.inconsistencies.push(Inconsistency::WrongSuccessor(height));
+        }
+        for fork_hash in chain_store.get_block_hashes_at(height, Some(&txn)) {
+            if fork_hash == hash {
+                continue;
+            }
+            if let Some(fork_info) = chain_store.get_chain_info(&fork_hash, false, Some(&txn)) {
+                if fork_info.on_main_chain || fork_info.main_chain_successor.is_some() {
+                    report.inconsistencies.push(Inconsistency::ForkOnMainChain(fork_hash, height));
+                }
+            }
+        }
+        if height > pruned_height && chain_store.get_block(&hash, true, Some(&txn)).is_none() {
+            report.inconsistencies.push(Inconsistency::MissingBody(height));
+        }
+
+        report.main_chain.push(hash.clone());
+
+        if height <= 1 {
+            if hash != network_info.genesis_hash {
+                report.inconsistencies.push(Inconsistency::WrongGenesis(hash));
+            }
+            break;
+        }
+
+        expected_height = Some(height - 1);
+        successor = Some(hash);
+        hash = chain_info.head.header.prev_hash;
+    }
+    report.main_chain.reverse();
+
+    // The accounts tree can only be compared against an intact head.
+    if let Some(head_info) = report.main_chain.last().and_then(|hash| chain_store.get_chain_info(hash, false, Some(&txn))) {
+        if head_info.head.header.accounts_hash != accounts.hash(Some(&txn)) {
+            report.inconsistencies.push(Inconsistency::AccountsHashMismatch);
+        }
+    }
+
+    report
+}
+
+/// Checks the consensus database and fixes what can be derived from the stored main chain:
+/// main chain flags are rewritten and the accounts tree is rebuilt by replaying all block
+/// bodies from genesis. Returns the report of a check after the repair.
+///
+/// If replaying fails midway, the accounts tree is left inconsistent and the repair can be retried.
+pub fn repair(env: &Environment, network_id: NetworkId) -> Result<ConsistencyReport, RepairError> {
+    let report = check_consistency(env, network_id);
+    if report.is_consistent() {
+        return Ok(report);
+    }
+    if !report.is_repairable() {
+        let unrepairable = report.inconsistencies.into_iter()
+            .filter(|inconsistency| !inconsistency.is_repairable())
+            .collect();
+        return Err(RepairError::Unrepairable(unrepairable));
+    }
+
+    // The chain store must be closed again before the final check reopens it.
+    {
+        let chain_store = ChainStore::new(env);
+        let needs_replay = report.inconsistencies.contains(&Inconsistency::AccountsHashMismatch);
+        if needs_replay {
+            let pruned_height = chain_store.get_pruned_height(None);
+            if pruned_height > 0 {
+                return Err(RepairError::BodiesPruned(pruned_height));
+            }
+        }
+
+        fix_main_chain_flags(env, &chain_store, &report)?;
+        if needs_replay {
+            rebuild_accounts(env, network_id, &chain_store, &report.main_chain)?;
+        }
+    }
+
+    Ok(check_consistency(env, network_id))
+}
+
+fn fix_main_chain_flags(env: &Environment, chain_store: &ChainStore, report: &ConsistencyReport) -> Result<(), WriteError> {
+    let mut txn = WriteTransaction::new(env);
+
+    for (i, hash) in report.main_chain.iter().enumerate() {
+        let mut chain_info = chain_store.get_chain_info(hash, false, Some(&txn)).unwrap();
+        let successor = report.main_chain.get(i + 1).cloned();
+        if !chain_info.on_main_chain || chain_info.main_chain_successor != successor {
+            chain_info.on_main_chain = true;
+            chain_info.main_chain_successor = successor;
+            chain_store.put_chain_info(&mut txn, hash, &chain_info, false);
+        }
+    }
+
+    for inconsistency in report.inconsistencies.iter() {
+        if let Inconsistency::ForkOnMainChain(hash, _) = inconsistency {
+            let mut chain_info = chain_store.get_chain_info(hash, false, Some(&txn)).unwrap();
+            chain_info.on_main_chain = false;
+            chain_info.main_chain_successor = None;
+            chain_store.put_chain_info(&mut txn, hash, &chain_info, false);
+        }
+    }
+
+    return txn.commit();
+}
+
+/// Rebuilds the accounts tree in a single transaction, so that a failed rebuild leaves the old tree
+/// in place. If the map runs full, the rebuild starts over once the map has grown.
+fn rebuild_accounts(env: &Environment, network_id: NetworkId, chain_store: &ChainStore, main_chain: &[Blake2bHash]) -> Result<(), RepairError> {
+    let mut attempt = 1;
+    loop {
+        match replay_accounts(env, network_id, chain_store, main_chain) {
+            Err(RepairError::WriteFailed(WriteError::MapFull)) if attempt < MAX_REPLAY_ATTEMPTS => {
+                warn!("Database map ran full while rebuilding the accounts, starting over");
+                attempt += 1;
+            },
+            result => return result,
+        }
+    }
+}
+
+fn replay_accounts(env: &Environment, network_id: NetworkId, chain_store: &ChainStore, main_chain: &[Blake2bHash]) -> Result<(), RepairError> {
+    let accounts = Accounts::new(env);
+
+    let mut txn = WriteTransaction::new(env);
+    accounts.clear(&mut txn);
+    accounts.init(&mut txn, network_id);
+
+    // The genesis block was committed by init().
+    for (i, hash) in main_chain.iter().enumerate().skip(1) {
+        let block = chain_store.get_block(hash, true, Some(&txn)).unwrap();
+        if let Err(e) = accounts.commit_block(&mut txn, &block) {
+            // After a failed write, the replay reads outdated accounts, so report the write error instead.
+            if txn.failed() {
+                return txn.commit().map_err(RepairError::from);
+            }
+            txn.abort();
+            return Err(RepairError::ReplayFailed(block.header.height, e));
+        }
+
+        if i % REPLAY_PROGRESS_INTERVAL == 0 {
+            info!("Replayed blocks up to #{}", block.header.height);
+        }
+    }
+
+    txn.commit()?;
+    Ok(())
+}
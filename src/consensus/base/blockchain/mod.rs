@@ -2,9 +2,23 @@ pub mod chain_info;
 pub mod chain_store;
 pub mod blockchain;
 pub mod transaction_cache;
+pub mod bootstrap;
+pub mod schema;
+pub mod consistency;
+pub mod snapshot;
+pub mod view;
+#[cfg(any(test, feature = "simulation"))]
+pub mod chain_builder;
 
 pub use self::chain_info::ChainInfo;
-pub use self::chain_store::ChainStore;
+pub use self::chain_store::{ChainStore, ReadOnlyChainStore};
 pub use self::chain_store::Direction;
-pub use self::blockchain::{Blockchain, BlockchainEvent, PushResult, PushError};
+pub use self::blockchain::{Blockchain, BlockchainEvent, PushResult, PushError, BlockchainError, AccountHistoryError, TransactionSimulation};
 pub use self::transaction_cache::TransactionCache;
+pub use self::bootstrap::{BootstrapError, BootstrapHeader, ImportProgress, import_blocks};
+pub use self::schema::{MetadataStore, SCHEMA_VERSION};
+pub use self::consistency::{ConsistencyReport, Inconsistency, RepairError, check_consistency, repair};
+pub use self::snapshot::{SnapshotError, SnapshotManifest, create_snapshot, restore_snapshot};
+pub use self::view::BlockchainView;
+#[cfg(any(test, feature = "simulation"))]
+pub use self::chain_builder::{BlockTemplate, ChainBuilder, Defect};
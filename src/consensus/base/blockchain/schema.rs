@@ -0,0 +1,88 @@
+use crate::consensus::base::blockchain::ChainStore;
+use crate::consensus::base::blockchain::blockchain::BlockchainError;
+use crate::utils::db::{Environment, Database, ReadableEnvironment, ReadOnlyEnvironment, Transaction, WriteTransaction};
+
+/// The schema version of the consensus database written by this version.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// A migration brings the database from the previous schema version to the given version.
+/// It runs in the same transaction that records the new version, so a failed migration
+/// leaves the database untouched.
+type Migration = fn(&mut WriteTransaction) -> Result<(), String>;
+
+/// All migrations in ascending order of the schema version they migrate to.
+const MIGRATIONS: &[(u32, Migration)] = &[
+    (1, migrate_to_v1),
+];
+
+#[derive(Debug)]
+pub struct MetadataStore<'env> {
+    env: ReadableEnvironment<'env>,
+    meta_db: Database<'env>,
+}
+
+impl<'env> MetadataStore<'env> {
+    const META_DB_NAME: &'static str = "Metadata";
+    const SCHEMA_VERSION_KEY: &'static str = "schemaVersion";
+
+    pub fn new(env: &'env Environment) -> Self {
+        let meta_db = env.open_database(Self::META_DB_NAME.to_string());
+        return MetadataStore { env: ReadableEnvironment::Writable(env), meta_db };
+    }
+
+    /// Opens the metadata of a read-only environment. Returns `None` for databases that were
+    /// written before schema versioning existed.
+    pub fn new_read_only(env: &'env ReadOnlyEnvironment) -> Option<Self> {
+        let meta_db = env.open_existing_database(Self::META_DB_NAME.to_string())?;
+        return Some(MetadataStore { env: ReadableEnvironment::ReadOnly(env), meta_db });
+    }
+
+    pub fn get_schema_version(&self, txn_option: Option<&Transaction>) -> Option<u32> {
+        return match txn_option {
+            Some(txn) => txn.get(&self.meta_db, MetadataStore::SCHEMA_VERSION_KEY),
+            None => self.env.read_transaction().get(&self.meta_db, MetadataStore::SCHEMA_VERSION_KEY)
+        };
+    }
+
+    pub fn set_schema_version(&self, txn: &mut WriteTransaction, version: u32) {
+        txn.put(&self.meta_db, MetadataStore::SCHEMA_VERSION_KEY, &version);
+    }
+}
+
+/// Brings the database up to `SCHEMA_VERSION` and returns the version it was at before.
+///
+/// Fresh databases are stamped with the current version. Databases that contain a chain
+/// but no version predate versioning and are treated as version 0.
+pub fn migrate(env: &Environment, chain_store: &ChainStore) -> Result<u32, BlockchainError> {
+    let meta_store = MetadataStore::new(env);
+
+    let version = match meta_store.get_schema_version(None) {
+        Some(version) => version,
+        None if chain_store.get_head(None).is_none() => {
+            let mut txn = WriteTransaction::new(env);
+            meta_store.set_schema_version(&mut txn, SCHEMA_VERSION);
+            txn.commit().map_err(|e| BlockchainError::WriteFailed(e.to_string()))?;
+            return Ok(SCHEMA_VERSION);
+        },
+        None => 0,
+    };
+
+    if version > SCHEMA_VERSION {
+        return Err(BlockchainError::UnsupportedSchemaVersion(version));
+    }
+
+    for &(target_version, migration) in MIGRATIONS.iter().filter(|(v, _)| *v > version) {
+        info!("Migrating database to schema version {}", target_version);
+        let mut txn = WriteTransaction::new(env);
+        migration(&mut txn).map_err(|e| BlockchainError::MigrationFailed(target_version, e))?;
+        meta_store.set_schema_version(&mut txn, target_version);
+        txn.commit().map_err(|e| BlockchainError::MigrationFailed(target_version, e.to_string()))?;
+    }
+
+    return Ok(version);
+}
+
+/// Version 1 is the layout used before versioning was introduced, so there is nothing to change.
+fn migrate_to_v1(_txn: &mut WriteTransaction) -> Result<(), String> {
+    Ok(())
+}
@@ -0,0 +1,132 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::consensus::base::blockchain::ChainStore;
+use crate::consensus::base::primitive::hash::Blake2bHash;
+use crate::consensus::networks::NetworkId;
+use crate::utils::db::Environment;
+use crate::utils::db::lmdb::LmdbEnvironment;
+
+/// Describes the chain state contained in a snapshot directory.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SnapshotManifest {
+    pub network_id: NetworkId,
+    pub head_hash: Blake2bHash,
+    pub height: u32,
+    /// Unix time in seconds at which the snapshot was taken.
+    pub timestamp: u64,
+}
+
+impl SnapshotManifest {
+    pub const FILE_NAME: &'static str = "manifest";
+
+    pub fn read(dir: &str) -> Result<Self, SnapshotError> {
+        let content = fs::read_to_string(Path::new(dir).join(Self::FILE_NAME))?;
+
+        let mut network_id = None;
+        let mut head_hash = None;
+        let mut height = None;
+        let mut timestamp = None;
+        for line in content.lines() {
+            let mut parts = line.splitn(2, ':');
+            let key = parts.next().unwrap_or("").trim();
+            let value = parts.next().unwrap_or("").trim();
+            match key {
+                "network" => network_id = value.parse::<u8>().ok().map(NetworkId::from),
+                "head" => head_hash = value.parse::<Blake2bHash>().ok(),
+                "height" => height = value.parse::<u32>().ok(),
+                "timestamp" => timestamp = value.parse::<u64>().ok(),
+                _ => {}
+            }
+        }
+
+        Ok(SnapshotManifest {
+            network_id: network_id.ok_or(SnapshotError::InvalidManifest("network"))?,
+            head_hash: head_hash.ok_or(SnapshotError::InvalidManifest("head"))?,
+            height: height.ok_or(SnapshotError::InvalidManifest("height"))?,
+            timestamp: timestamp.ok_or(SnapshotError::InvalidManifest("timestamp"))?,
+        })
+    }
+
+    pub fn write(&self, dir: &str) -> Result<(), SnapshotError> {
+        let content = format!("network: {}\nhead: {}\nheight: {}\ntimestamp: {}\n",
+            u8::from(self.network_id), self.head_hash, self.height, self.timestamp);
+        fs::write(Path::new(dir).join(Self::FILE_NAME), content)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    IoError(io::Error),
+    InvalidManifest(&'static str),
+    EmptySnapshot,
+    NetworkMismatch(NetworkId),
+    DatabaseExists,
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SnapshotError::IoError(e) => write!(f, "I/O error: {}", e),
+            SnapshotError::InvalidManifest(field) => write!(f, "Invalid snapshot manifest: missing or invalid {}", field),
+            SnapshotError::EmptySnapshot => write!(f, "Snapshot does not contain a chain"),
+            SnapshotError::NetworkMismatch(network_id) => write!(f, "Snapshot is for network {:?}", network_id),
+            SnapshotError::DatabaseExists => write!(f, "Database directory is not empty"),
+        }
+    }
+}
+
+impl From<io::Error> for SnapshotError {
+    fn from(e: io::Error) -> Self {
+        SnapshotError::IoError(e)
+    }
+}
+
+/// File name of the LMDB data file inside an environment directory.
+const DATA_FILE_NAME: &str = "data.mdb";
+
+/// Copies the database into `dir` while the node keeps running and writes a manifest describing
+/// the copied chain. The head is read from the copy itself, so it matches the copied state exactly.
+pub fn create_snapshot(env: &Environment, network_id: NetworkId, dir: &str) -> Result<SnapshotManifest, SnapshotError> {
+    env.copy_to(dir)?;
+
+    let (head_hash, height) = {
+        // ChainStore opens three databases. The copy is never written to.
+        let snapshot_env = LmdbEnvironment::open_read_only(dir, 3)
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        let chain_store = ChainStore::new_read_only(&snapshot_env);
+        let head_hash = chain_store.get_head(None).ok_or(SnapshotError::EmptySnapshot)?;
+        let head_info = chain_store.get_chain_info(&head_hash, false, None).ok_or(SnapshotError::EmptySnapshot)?;
+        (head_hash, head_info.head.header.height)
+    };
+
+    let manifest = SnapshotManifest {
+        network_id,
+        head_hash,
+        height,
+        timestamp: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+    };
+    manifest.write(dir)?;
+    Ok(manifest)
+}
+
+/// Copies the database of the snapshot in `snapshot_dir` into the empty database directory `db_dir`.
+/// The snapshot itself is left untouched.
+pub fn restore_snapshot(snapshot_dir: &str, db_dir: &str, network_id: NetworkId) -> Result<SnapshotManifest, SnapshotError> {
+    let manifest = SnapshotManifest::read(snapshot_dir)?;
+    if manifest.network_id != network_id {
+        return Err(SnapshotError::NetworkMismatch(manifest.network_id));
+    }
+
+    let target = Path::new(db_dir).join(DATA_FILE_NAME);
+    if target.exists() {
+        return Err(SnapshotError::DatabaseExists);
+    }
+    fs::create_dir_all(db_dir)?;
+    fs::copy(Path::new(snapshot_dir).join(DATA_FILE_NAME), target)?;
+    Ok(manifest)
+}
@@ -0,0 +1,106 @@
+use crate::consensus::base::account::{Account, Accounts, ReadOnlyAccounts};
+use crate::consensus::base::block::Block;
+use crate::consensus::base::blockchain::{ChainStore, Direction, ReadOnlyChainStore};
+use crate::consensus::base::blockchain::blockchain::BlockchainError;
+use crate::consensus::base::blockchain::schema::{MetadataStore, SCHEMA_VERSION};
+use crate::consensus::base::primitive::Address;
+use crate::consensus::base::primitive::hash::Blake2bHash;
+use crate::consensus::networks::{NetworkId, get_network_info};
+use crate::utils::db::{ReadOnlyEnvironment, ReadTransaction};
+
+/// Read-only access to the chain of a database that may be written by a running node.
+/// Nothing is cached, so every call sees the latest committed head. Calls that need to agree
+/// with each other should share a transaction from `read_transaction()`.
+#[derive(Debug)]
+pub struct BlockchainView<'env> {
+    env: &'env ReadOnlyEnvironment,
+    pub network_id: NetworkId,
+    chain_store: ReadOnlyChainStore<'env>,
+    accounts: ReadOnlyAccounts<'env>,
+}
+
+impl<'env> BlockchainView<'env> {
+    pub fn new(env: &'env ReadOnlyEnvironment, network_id: NetworkId) -> Result<Self, BlockchainError> {
+        // The writer has to have migrated the database to the schema we understand.
+        let version = MetadataStore::new_read_only(env).and_then(|metadata| metadata.get_schema_version(None));
+        match version {
+            Some(SCHEMA_VERSION) => {},
+            version => return Err(BlockchainError::UnsupportedSchemaVersion(version.unwrap_or(0))),
+        }
+
+        let chain_store = ChainStore::new_read_only(env);
+        let network_info = get_network_info(network_id).unwrap();
+        let genesis_info = chain_store.get_chain_info(&network_info.genesis_hash, false, None);
+        if !genesis_info.map_or(false, |genesis_info| genesis_info.on_main_chain) {
+            return Err(BlockchainError::InvalidGenesisBlock);
+        }
+        if chain_store.get_head(None).is_none() {
+            return Err(BlockchainError::FailedLoadingMainChain);
+        }
+
+        Ok(BlockchainView {
+            env,
+            network_id,
+            chain_store,
+            accounts: Accounts::new_read_only(env),
+        })
+    }
+
+    /// Starts a read transaction on the latest committed state.
+    pub fn read_transaction(&self) -> ReadTransaction<'env> {
+        self.env.read_transaction()
+    }
+
+    pub fn head_hash(&self) -> Blake2bHash {
+        self.chain_store.get_head(None).expect("Head vanished from the database")
+    }
+
+    pub fn head(&self) -> Block {
+        let txn = self.read_transaction();
+        let head_hash = self.chain_store.get_head(Some(&txn)).expect("Head vanished from the database");
+        self.chain_store.get_block(&head_hash, false, Some(&txn)).expect("Head block missing from the database")
+    }
+
+    pub fn height(&self) -> u32 {
+        self.head().header.height
+    }
+
+    pub fn contains(&self, hash: &Blake2bHash, include_forks: bool) -> bool {
+        match self.chain_store.get_chain_info(hash, false, None) {
+            Some(chain_info) => include_forks || chain_info.on_main_chain,
+            None => false
+        }
+    }
+
+    pub fn get_block(&self, hash: &Blake2bHash, include_forks: bool, include_body: bool) -> Option<Block> {
+        let chain_info = self.chain_store.get_chain_info(hash, include_body, None)?;
+        // The body might have been pruned.
+        if include_body && chain_info.head.body.is_none() {
+            return None;
+        }
+        if chain_info.on_main_chain || include_forks {
+            return Some(chain_info.head);
+        }
+        None
+    }
+
+    pub fn get_block_at(&self, height: u32, include_body: bool) -> Option<Block> {
+        self.chain_store.get_chain_info_at(height, include_body, None).map(|chain_info| chain_info.head)
+    }
+
+    pub fn get_blocks(&self, start_block_hash: &Blake2bHash, count: u32, include_body: bool, direction: Direction) -> Vec<Block> {
+        self.chain_store.get_blocks(start_block_hash, count, include_body, direction, None)
+    }
+
+    pub fn get_account(&self, address: &Address) -> Account {
+        self.accounts.get(address, None)
+    }
+
+    pub fn chain_store(&self) -> &ReadOnlyChainStore<'env> {
+        &self.chain_store
+    }
+
+    pub fn accounts(&self) -> &ReadOnlyAccounts<'env> {
+        &self.accounts
+    }
+}
@@ -0,0 +1,115 @@
+use beserial::Serialize;
+use crate::consensus::base::block::Block;
+use crate::consensus::base::blockchain::Direction;
+use crate::consensus::base::primitive::{Address, Coin};
+use super::{Mempool, FREE_TRANSACTIONS_PER_SENDER_MAX, FULL_MEMPOOL_FEE_INCREMENT, TRANSACTION_RELAY_FEE_MIN};
+
+/// When a transaction should be included in the chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeeTarget {
+    /// Include the transaction in the next block.
+    NextBlock,
+    /// Include the transaction within the given number of blocks.
+    WithinBlocks(u32),
+    /// Send the transaction for free if the mempool accepts it.
+    Free,
+}
+
+/// Number of recent main chain blocks taken into account.
+const RECENT_BLOCKS: u32 = 10;
+
+/// Estimates the fee/byte a transaction from `sender` needs to pay to meet `target`.
+///
+/// The estimate is based on the fee/byte distribution of the transactions in the mempool
+/// and on the lowest fee/byte included in recent blocks. Estimates below `TRANSACTION_RELAY_FEE_MIN`
+/// are lowered to zero if the sender may still send free transactions and raised to
+/// `TRANSACTION_RELAY_FEE_MIN` otherwise.
+pub fn estimate_fee_per_byte(mempool: &Mempool, sender: &Address, target: FeeTarget) -> f64 {
+    let mut fee_per_byte = match target {
+        FeeTarget::NextBlock => estimate_for_blocks(mempool, 1),
+        FeeTarget::WithinBlocks(num_blocks) => estimate_for_blocks(mempool, num_blocks.max(1)),
+        FeeTarget::Free => 0f64,
+    };
+
+    let state = mempool.state.read();
+
+    // A full mempool only accepts transactions that outbid the lowest one in it.
+    if state.transactions_sorted_fee.len() >= mempool.size_max {
+        if let Some(lowest_tx) = state.transactions_sorted_fee.iter().next() {
+            let min_fee_per_byte = lowest_tx.fee_per_byte() + FULL_MEMPOOL_FEE_INCREMENT;
+            if fee_per_byte < min_fee_per_byte {
+                fee_per_byte = min_fee_per_byte;
+            }
+        }
+    }
+
+    if fee_per_byte >= TRANSACTION_RELAY_FEE_MIN {
+        return fee_per_byte;
+    }
+
+    // Anything below the relay fee counts as a free transaction.
+    let num_free_tx = state.transactions_by_sender.get(sender)
+        .map(|txs| txs.iter().filter(|tx| tx.fee_per_byte() < TRANSACTION_RELAY_FEE_MIN).count())
+        .unwrap_or(0);
+    if (num_free_tx as u32) < FREE_TRANSACTIONS_PER_SENDER_MAX && fee_per_byte == 0f64 {
+        return 0f64;
+    }
+    TRANSACTION_RELAY_FEE_MIN
+}
+
+/// Estimates the total fee for a transaction of `tx_size` bytes from `sender` to meet `target`.
+pub fn estimate_fee(mempool: &Mempool, sender: &Address, tx_size: usize, target: FeeTarget) -> Coin {
+    let fee_per_byte = estimate_fee_per_byte(mempool, sender, target);
+    Coin::from((fee_per_byte * tx_size as f64).ceil() as u64)
+}
+
+fn estimate_for_blocks(mempool: &Mempool, num_blocks: u32) -> f64 {
+    let from_mempool = estimate_from_mempool(mempool, num_blocks);
+    let from_blocks = estimate_from_recent_blocks(mempool, num_blocks);
+    from_mempool.max(from_blocks)
+}
+
+/// Returns the fee/byte of the transaction at the end of the space of `num_blocks` blocks
+/// if the mempool were mined in fee/byte order, or zero if the mempool fits into these blocks.
+fn estimate_from_mempool(mempool: &Mempool, num_blocks: u32) -> f64 {
+    let capacity = Block::MAX_SIZE * num_blocks as usize;
+
+    let state = mempool.state.read();
+    let mut size = 0;
+    for tx in state.transactions_sorted_fee.iter().rev() {
+        size += tx.serialized_size();
+        if size >= capacity {
+            return tx.fee_per_byte();
+        }
+    }
+    0f64
+}
+
+/// Looks at the lowest fee/byte paid in each of the recent main chain blocks that were (almost) full.
+/// The more blocks we are willing to wait, the lower the fee we pick from these minima.
+fn estimate_from_recent_blocks(mempool: &Mempool, num_blocks: u32) -> f64 {
+    let blockchain = &mempool.blockchain;
+    let head_hash = blockchain.head_hash();
+
+    let mut blocks = Vec::new();
+    if let Some(head) = blockchain.get_block(&head_hash, false, true) {
+        blocks.push(head);
+    }
+    blocks.extend(blockchain.get_blocks(&head_hash, RECENT_BLOCKS - 1, true, Direction::Backward));
+
+    // The lowest fee in a block with space left says nothing about the fee needed to get in.
+    let mut min_fees: Vec<f64> = blocks.iter()
+        .filter(|block| block.serialized_size() >= Block::MAX_SIZE * 9 / 10)
+        .filter_map(|block| block.body.as_ref())
+        .filter(|body| !body.transactions.is_empty())
+        .map(|body| body.transactions.iter()
+            .map(|tx| tx.fee_per_byte())
+            .fold(std::f64::MAX, f64::min))
+        .collect();
+    if min_fees.is_empty() {
+        return 0f64;
+    }
+
+    min_fees.sort_by(|a, b| b.partial_cmp(a).unwrap());
+    min_fees[(min_fees.len() - 1) / num_blocks as usize]
+}
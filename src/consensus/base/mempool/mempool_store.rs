@@ -0,0 +1,58 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+use crate::consensus::base::primitive::hash::{Blake2bHash, Hash};
+use crate::consensus::base::transaction::Transaction;
+use crate::utils::db::{Environment, ReadTransaction, WriteTransaction};
+use crate::utils::db::table::{Table, TableSpec};
+
+/// Pending transactions by hash.
+#[derive(Debug)]
+struct MempoolTable;
+
+impl TableSpec for MempoolTable {
+    const NAME: &'static str = "Mempool";
+    type Key = Blake2bHash;
+    type Value = Transaction;
+}
+
+/// Persists the contents of the mempool so that pending transactions survive node restarts.
+#[derive(Debug)]
+pub struct MempoolStore<'env> {
+    env: &'env Environment,
+    tx_table: Table<'env, MempoolTable>,
+}
+
+impl<'env> MempoolStore<'env> {
+    pub fn new(env: &'env Environment) -> Self {
+        return MempoolStore { env, tx_table: Table::open(env) };
+    }
+
+    pub fn get_transactions(&self) -> Vec<Transaction> {
+        let txn = ReadTransaction::new(self.env);
+        return self.tx_table.iter(&txn).map(|(_, tx)| tx).collect();
+    }
+
+    /// Replaces the stored transactions with the given ones.
+    pub fn put_transactions(&self, transactions: &[Arc<Transaction>]) {
+        let hashes: HashSet<Blake2bHash> = transactions.iter().map(|tx| tx.hash()).collect();
+
+        // Collect the hashes of stored transactions that are no longer in the mempool.
+        let txn = ReadTransaction::new(self.env);
+        let hashes_to_remove: Vec<Blake2bHash> = self.tx_table.iter(&txn)
+            .map(|(hash, _)| hash)
+            .filter(|hash| !hashes.contains(hash))
+            .collect();
+        txn.close();
+
+        let mut txn = WriteTransaction::new(self.env);
+        for hash in hashes_to_remove.iter() {
+            self.tx_table.remove(&mut txn, hash);
+        }
+        for tx in transactions {
+            self.tx_table.put(&mut txn, &tx.hash(), tx.as_ref());
+        }
+        if let Err(e) = txn.commit() {
+            error!("Failed to store the mempool transactions: {}", e);
+        }
+    }
+}
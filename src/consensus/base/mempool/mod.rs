@@ -1,22 +1,30 @@
+pub mod mempool_store;
+pub mod fee_estimator;
+
 use beserial::Serialize;
 use parking_lot::{RwLock, Mutex};
 use std::cmp::Ordering;
 use std::collections::BTreeSet;
 use std::collections::HashMap;
 use std::sync::Arc;
-use crate::consensus::base::account::Accounts;
+use crate::consensus::base::account::{Account, AccountError, Accounts, PrunedAccount};
 use crate::consensus::base::block::Block;
-use crate::consensus::base::blockchain::{Blockchain, BlockchainEvent};
+use crate::consensus::base::blockchain::{Blockchain, BlockchainEvent, TransactionSimulation};
 use crate::consensus::base::primitive::hash::{Blake2bHash, Hash};
-use crate::consensus::base::primitive::Address;
+use crate::consensus::base::primitive::{Address, Coin};
 use crate::consensus::base::transaction::Transaction;
 use crate::utils::observer::Notifier;
 
+pub use self::mempool_store::MempoolStore;
+pub use self::fee_estimator::{FeeTarget, estimate_fee, estimate_fee_per_byte};
+
 pub struct Mempool<'env> {
     blockchain: Arc<Blockchain<'env>>,
     pub notifier: RwLock<Notifier<'env, MempoolEvent>>,
     state: RwLock<MempoolState>,
     mut_lock: Mutex<()>,
+    /// Maximum number of transactions in the mempool.
+    size_max: usize,
 }
 
 struct MempoolState {
@@ -29,13 +37,43 @@ struct MempoolState {
 #[derive(Debug, Clone, PartialOrd, Ord, PartialEq, Eq)]
 pub enum MempoolEvent {
     TransactionAdded(Blake2bHash, Arc<Transaction>),
-    TransactionRestored,
-    TransactionMined,
-    TransactionEvicted,
+    TransactionRestored(Blake2bHash, Arc<Transaction>),
+    TransactionMined(Blake2bHash, Arc<Transaction>),
+    TransactionEvicted(Blake2bHash, Arc<Transaction>),
+}
+
+/// Confirmed balance of an address and the funds its pending transactions move.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingBalance {
+    pub address: Address,
+    /// Balance of the account at the head of the chain.
+    pub confirmed: Coin,
+    /// Sum of value and fee of the pending transactions sent from the address.
+    pub pending_outgoing: Coin,
+    /// Sum of the values of the pending transactions sent to the address.
+    pub pending_incoming: Coin,
+    /// Hashes of the pending transactions from and to the address, outgoing first,
+    /// each in descending fee/byte order.
+    pub pending_transactions: Vec<Blake2bHash>,
+}
+
+impl PendingBalance {
+    /// The balance after all pending transactions have been mined. Returns `None` if the pending
+    /// outgoing transactions exceed the available funds, e.g. because the confirmed balance changed
+    /// before the mempool evicted them.
+    pub fn effective(&self) -> Option<Coin> {
+        return self.confirmed.checked_add(self.pending_incoming)?
+            .checked_sub(self.pending_outgoing);
+    }
 }
 
 impl<'env> Mempool<'env> {
     pub fn new(blockchain: Arc<Blockchain<'env>>) -> Arc<Self> {
+        Mempool::with_size_max(blockchain, SIZE_MAX)
+    }
+
+    /// Creates a mempool that holds at most `size_max` transactions.
+    pub fn with_size_max(blockchain: Arc<Blockchain<'env>>, size_max: usize) -> Arc<Self> {
         let arc = Arc::new(Self {
             blockchain: blockchain.clone(),
             notifier: RwLock::new(Notifier::new()),
@@ -46,6 +84,7 @@ impl<'env> Mempool<'env> {
                 transactions_sorted_fee: BTreeSet::new(),
             }),
             mut_lock: Mutex::new(()),
+            size_max,
         });
 
         let arc_self = arc.clone();
@@ -94,6 +133,16 @@ impl<'env> Mempool<'env> {
                 }
             }
 
+            // If the mempool is full, only accept the transaction if it outbids the lowest fee
+            // transaction in the pool, which will be evicted in exchange.
+            if state.transactions_sorted_fee.len() >= self.size_max {
+                if let Some(lowest_tx) = state.transactions_sorted_fee.iter().next() {
+                    if transaction.fee_per_byte() < lowest_tx.fee_per_byte() + FULL_MEMPOOL_FEE_INCREMENT {
+                        return ReturnCode::FeeTooLow;
+                    }
+                }
+            }
+
             // Acquire blockchain read lock.
             let accounts = self.blockchain.accounts();
             let transaction_cache = self.blockchain.transaction_cache();
@@ -184,6 +233,7 @@ impl<'env> Mempool<'env> {
         }
 
         let tx_arc = Arc::new(transaction);
+        let mut txs_evicted = Vec::new();
 
         {
             // Transaction is valid, add it to the mempool.
@@ -193,19 +243,20 @@ impl<'env> Mempool<'env> {
             // Evict transactions that were invalidated by the new transaction.
             for tx in txs_to_remove {
                 Mempool::remove_transaction(&mut *state, &tx);
+                txs_evicted.push(tx);
             }
 
-            // Remove the lowest fee transaction if mempool max size is reached.
-            if state.transactions_sorted_fee.len() > SIZE_MAX {
-                let tx = state.transactions_sorted_fee.iter().next().unwrap().clone();
-                Mempool::remove_transaction(&mut state, &tx);
-            }
+            // Remove the lowest fee transactions if mempool max size is reached.
+            txs_evicted.append(&mut Mempool::evict_lowest_fee_transactions(&mut state, self.size_max));
         }
 
         // Tell listeners about the new transaction we received.
         self.notifier.read().notify(MempoolEvent::TransactionAdded(hash, tx_arc));
 
-        // TODO Tell listeners about the transactions we evicted.
+        // Tell listeners about the transactions we evicted.
+        for tx in txs_evicted {
+            self.notifier.read().notify(MempoolEvent::TransactionEvicted(tx.hash(), tx));
+        }
 
         return ReturnCode::Accepted;
     }
@@ -218,12 +269,18 @@ impl<'env> Mempool<'env> {
         self.state.read().transactions_by_hash.get(hash).map(|arc| arc.clone())
     }
 
+    /// Returns transactions paying at least `min_fee_per_byte` in ascending order of their fee/byte,
+    /// up to a total size of `max_size`.
     pub fn get_transactions(&self, max_size: usize, min_fee_per_byte: f64) -> Vec<Arc<Transaction>> {
         let mut txs = Vec::new();
         let mut size = 0;
 
         let state = self.state.read();
         for tx in &state.transactions_sorted_fee {
+            if tx.fee_per_byte() < min_fee_per_byte {
+                continue;
+            }
+
             let tx_size = tx.serialized_size();
             if size + tx_size <= max_size {
                 txs.push(tx.clone());
@@ -237,10 +294,93 @@ impl<'env> Mempool<'env> {
         return txs;
     }
 
-    pub fn get_transactions_for_block(&self, max_size: usize) -> Vec<Arc<Transaction>> {
-        let transactions = self.get_transactions(max_size, 0f64);
-        // TODO get to be pruned accounts and remove transactions to fit max_size
-        unimplemented!();
+    /// Writes all transactions currently in the mempool to the store.
+    pub fn persist(&self, store: &MempoolStore) {
+        let transactions: Vec<Arc<Transaction>> = self.state.read().transactions_sorted_fee.iter().cloned().collect();
+        store.put_transactions(&transactions);
+    }
+
+    /// Re-validates the transactions in the store and adds the valid ones to the mempool.
+    /// Transactions that expired in the meantime are rejected by `push_transaction`.
+    /// Returns the number of restored transactions.
+    pub fn restore_from_store(&self, store: &MempoolStore) -> usize {
+        let mut transactions = store.get_transactions();
+
+        // Push transactions in descending mempool order, so that the transactions of a sender
+        // are checked against its balance in the same order as when they were first received.
+        transactions.sort_by(|a, b| b.cmp(a));
+
+        let mut num_restored = 0;
+        for tx in transactions {
+            if self.push_transaction(tx) == ReturnCode::Accepted {
+                num_restored += 1;
+            }
+        }
+        num_restored
+    }
+
+    /// Selects the transactions for the next block in fee/byte order together with the accounts
+    /// they prune. The transactions of each sender are checked against the sender's balance in
+    /// fee/byte order, transactions that their recipient doesn't accept are left out and the total
+    /// size of transactions and pruned accounts does not exceed `max_size`.
+    /// The returned transactions are sorted in block order.
+    pub fn get_transactions_for_block(&self, max_size: usize) -> (Vec<Transaction>, Vec<PrunedAccount>) {
+        // Acquire blockchain read lock.
+        let accounts = self.blockchain.accounts();
+        let block_height = self.blockchain.height() + 1;
+
+        let mut transactions = Vec::new();
+        let mut sender_accounts: HashMap<Address, Account> = HashMap::new();
+        let mut size = 0;
+        {
+            let state = self.state.read();
+            for tx in state.transactions_sorted_fee.iter().rev() {
+                let tx_size = tx.serialized_size();
+                if size + tx_size > max_size {
+                    // Break if we can't fit the smallest possible transaction anymore.
+                    if max_size - size < Transaction::MIN_SIZE {
+                        break;
+                    }
+                    continue;
+                }
+
+                if !tx.is_valid_at(block_height) {
+                    continue;
+                }
+
+                // Check the recipient like `push_transaction` does, its account might have changed since.
+                let recipient_account = accounts.get(&tx.recipient, None);
+                if recipient_account.account_type() != tx.recipient_type || recipient_account.with_incoming_transaction(tx, block_height).is_err() {
+                    continue;
+                }
+
+                // The transactions of a sender are visited in fee/byte order, which is the order
+                // in which they were checked against the sender's balance when entering the mempool.
+                let sender_account = match sender_accounts.get(&tx.sender) {
+                    Some(account) => account.clone(),
+                    None => accounts.get(&tx.sender, None)
+                };
+                match sender_account.with_outgoing_transaction(tx, block_height) {
+                    Ok(account) => sender_accounts.insert(tx.sender.clone(), account),
+                    Err(_) => continue
+                };
+
+                transactions.push(tx.clone());
+                size += tx_size;
+            }
+        }
+
+        // Drop the lowest fee transactions until the pruned accounts fit into the block as well.
+        let mut pruned_accounts = Mempool::gather_pruned_accounts(&accounts, &transactions, block_height);
+        while size + pruned_accounts.iter().map(|acc| acc.serialized_size()).sum::<usize>() > max_size {
+            let tx = transactions.pop().unwrap();
+            size -= tx.serialized_size();
+            pruned_accounts = Mempool::gather_pruned_accounts(&accounts, &transactions, block_height);
+        }
+
+        let mut transactions: Vec<Transaction> = transactions.iter().map(|tx| Transaction::clone(tx)).collect();
+        transactions.sort_by(|a, b| a.cmp_block_order(b));
+        (transactions, pruned_accounts)
     }
 
     pub fn get_transactions_by_addresses(&self, addresses: Vec<Address>, max_transactions: u32) -> Vec<Arc<Transaction>> {
@@ -265,6 +405,92 @@ impl<'env> Mempool<'env> {
         return txs;
     }
 
+    /// Returns the confirmed balance and the pending transactions of each address.
+    /// Pending transactions are tracked when they enter or leave the pool, including when transactions
+    /// of reverted blocks are restored on rebranch, so the result always matches the pool's contents.
+    pub fn get_pending_balances(&self, addresses: Vec<Address>) -> Vec<PendingBalance> {
+        // Don't observe the pool while it is being updated for a new head.
+        let lock = self.mut_lock.lock();
+
+        let state = self.state.read();
+        let accounts = self.blockchain.accounts();
+
+        let mut balances = Vec::with_capacity(addresses.len());
+        for address in addresses {
+            let mut pending_outgoing = Coin::ZERO;
+            let mut pending_incoming = Coin::ZERO;
+            let mut pending_transactions = Vec::new();
+
+            if let Some(transactions) = state.transactions_by_sender.get(&address) {
+                for tx in transactions.iter().rev() {
+                    pending_outgoing = pending_outgoing + tx.value + tx.fee;
+                    pending_transactions.push(tx.hash());
+                }
+            }
+            if let Some(transactions) = state.transactions_by_recipient.get(&address) {
+                for tx in transactions.iter().rev() {
+                    pending_incoming = pending_incoming + tx.value;
+                    pending_transactions.push(tx.hash());
+                }
+            }
+
+            balances.push(PendingBalance {
+                confirmed: accounts.get(&address, None).balance(),
+                address,
+                pending_outgoing,
+                pending_incoming,
+                pending_transactions,
+            });
+        }
+        return balances;
+    }
+
+    /// Simulates `transaction` on top of the pending transactions of its sender, see
+    /// `Blockchain::simulate_transaction`. Pending transactions that no longer apply are skipped,
+    /// just like when assembling a block, and listed in the result.
+    pub fn simulate_transaction(&self, transaction: &Transaction) -> Result<TransactionSimulation, AccountError> {
+        // Intrinsic verification, including the checks of the sender and recipient account types.
+        transaction.verify(self.blockchain.network_id)?;
+
+        let hash: Blake2bHash = transaction.hash();
+        let pending: Vec<Arc<Transaction>> = match self.state.read().transactions_by_sender.get(&transaction.sender) {
+            // Highest fee/byte first, the order in which they are checked against the sender's balance.
+            Some(transactions) => transactions.iter().rev()
+                .filter(|tx| tx.hash::<Blake2bHash>() != hash)
+                .cloned()
+                .collect(),
+            None => Vec::new(),
+        };
+
+        // Acquire blockchain read lock.
+        let accounts = self.blockchain.accounts();
+        let block_height = self.blockchain.height() + 1;
+
+        // Copies of the accounts changed by the pending transactions.
+        let mut changed: HashMap<Address, Account> = HashMap::new();
+        let mut skipped_transactions = Vec::new();
+        let get = |changed: &HashMap<Address, Account>, address: &Address| {
+            changed.get(address).cloned().unwrap_or_else(|| accounts.get(address, None))
+        };
+        for tx in pending.iter() {
+            match TransactionSimulation::apply(tx, block_height, get(&changed, &tx.sender), get(&changed, &tx.recipient)) {
+                Ok(simulation) => {
+                    changed.insert(tx.sender.clone(), simulation.sender);
+                    changed.insert(tx.recipient.clone(), simulation.recipient);
+                },
+                Err(e) => {
+                    let tx_hash: Blake2bHash = tx.hash();
+                    debug!("Skipping pending transaction {} in simulation - {}", tx_hash, e);
+                    skipped_transactions.push(tx_hash);
+                },
+            }
+        }
+
+        let mut simulation = TransactionSimulation::apply(transaction, block_height, get(&changed, &transaction.sender), get(&changed, &transaction.recipient))?;
+        simulation.skipped_transactions = skipped_transactions;
+        return Ok(simulation);
+    }
+
     fn on_blockchain_event(&self, event: &BlockchainEvent) {
         match event {
             BlockchainEvent::Extended(_, _) => self.evict_transactions(),
@@ -327,15 +553,21 @@ impl<'env> Mempool<'env> {
         {
             // Evict transactions.
             let mut state = self.state.write();
-            for tx in txs_mined {
-                Mempool::remove_transaction(&mut state, &tx);
+            for tx in txs_mined.iter() {
+                Mempool::remove_transaction(&mut state, tx);
             }
-            for tx in txs_evicted {
-                Mempool::remove_transaction(&mut state, &tx);
+            for tx in txs_evicted.iter() {
+                Mempool::remove_transaction(&mut state, tx);
             }
         }
 
-        // TODO notify listeners
+        // Tell listeners about the transactions we removed.
+        for tx in txs_mined {
+            self.notifier.read().notify(MempoolEvent::TransactionMined(tx.hash(), tx));
+        }
+        for tx in txs_evicted {
+            self.notifier.read().notify(MempoolEvent::TransactionEvicted(tx.hash(), tx));
+        }
     }
 
     fn restore_transactions(&self, reverted_blocks: &Vec<(Blake2bHash, Block)>) {
@@ -377,6 +609,9 @@ impl<'env> Mempool<'env> {
             }
         }
 
+        let mut txs_restored = Vec::new();
+        let mut txs_evicted = Vec::new();
+
         // Merge the new transaction sets per sender with the existing ones.
         let mut state = self.state.write();
 
@@ -392,25 +627,79 @@ impl<'env> Mempool<'env> {
 
             let (txs_to_add, txs_to_remove) = Mempool::merge_transactions(&accounts, sender, block_height, existing_txs, &restored_txs);
             for tx in txs_to_add {
-                Mempool::add_transaction(&mut state, tx.hash(), Arc::new(tx.clone()));
+                let tx_arc = Arc::new(tx.clone());
+                Mempool::add_transaction(&mut state, tx.hash(), tx_arc.clone());
+                txs_restored.push(tx_arc);
             }
             for tx in txs_to_remove {
                 Mempool::remove_transaction(&mut state, &tx);
+                txs_evicted.push(tx);
             }
         }
 
         // Evict lowest fee transactions if the mempool has grown too large.
+        txs_evicted.append(&mut Mempool::evict_lowest_fee_transactions(&mut state, self.size_max));
+
+        // Give up locks before notifying.
+        drop(state);
+        drop(accounts);
+        drop(transaction_cache);
+
+        for tx in txs_restored {
+            self.notifier.read().notify(MempoolEvent::TransactionRestored(tx.hash(), tx));
+        }
+        for tx in txs_evicted {
+            self.notifier.read().notify(MempoolEvent::TransactionEvicted(tx.hash(), tx));
+        }
+    }
+
+    /// Removes the transactions with the lowest fee/byte until the mempool holds at most `size_max` transactions.
+    fn evict_lowest_fee_transactions(state: &mut MempoolState, size_max: usize) -> Vec<Arc<Transaction>> {
         let size = state.transactions_sorted_fee.len();
-        if size > SIZE_MAX {
-            let mut txs_to_remove = Vec::with_capacity(size - SIZE_MAX);
-            let mut iter = state.transactions_sorted_fee.iter();
-            for _ in 0..size - SIZE_MAX {
-                txs_to_remove.push(iter.next().unwrap().clone());
+        if size <= size_max {
+            return Vec::new();
+        }
+
+        let txs_to_remove: Vec<Arc<Transaction>> = state.transactions_sorted_fee.iter()
+            .take(size - size_max)
+            .cloned()
+            .collect();
+        for tx in txs_to_remove.iter() {
+            Mempool::remove_transaction(state, tx);
+        }
+        txs_to_remove
+    }
+
+    /// Computes the sender accounts that are left to be pruned after applying the given transactions.
+    /// Mirrors `Accounts::commit_block_body`: outgoing transactions are applied first, then incoming ones.
+    fn gather_pruned_accounts(accounts: &Accounts, transactions: &Vec<Arc<Transaction>>, block_height: u32) -> Vec<PrunedAccount> {
+        let mut sender_accounts: HashMap<Address, Account> = HashMap::new();
+        for tx in transactions {
+            let account = match sender_accounts.get(&tx.sender) {
+                Some(account) => account.clone(),
+                None => accounts.get(&tx.sender, None)
+            };
+            if let Ok(account) = account.with_outgoing_transaction(tx, block_height) {
+                sender_accounts.insert(tx.sender.clone(), account);
             }
-            for tx in txs_to_remove {
-                Mempool::remove_transaction(&mut state, &tx);
+        }
+
+        for tx in transactions {
+            let recipient_account = match sender_accounts.get(&tx.recipient) {
+                Some(account) => account.clone(),
+                None => continue
+            };
+            if let Ok(account) = recipient_account.with_incoming_transaction(tx, block_height) {
+                sender_accounts.insert(tx.recipient.clone(), account);
             }
         }
+
+        let mut pruned_accounts: Vec<PrunedAccount> = sender_accounts.into_iter()
+            .filter(|(_, account)| account.is_to_be_pruned())
+            .map(|(address, account)| PrunedAccount { address, account })
+            .collect();
+        pruned_accounts.sort();
+        pruned_accounts
     }
 
     fn add_transaction(state: &mut MempoolState, hash: Blake2bHash, tx: Arc<Transaction>) {
@@ -518,5 +807,8 @@ const TRANSACTIONS_PER_SENDER_MAX : u32 = 500;
 /// Maximum number of "free" transactions per sender.
 const FREE_TRANSACTIONS_PER_SENDER_MAX : u32 = 10;
 
-/// Maximum number of transactions in the mempool.
+/// Default maximum number of transactions in the mempool.
 const SIZE_MAX : usize = 100000;
+
+/// Fee in sat/byte a transaction has to pay on top of the lowest one to enter a full mempool.
+pub const FULL_MEMPOOL_FEE_INCREMENT : f64 = 1f64;
@@ -7,7 +7,7 @@ use libargon2_sys::argon2d_hash;
 use sha2::{Sha256, Sha512, Digest};
 use beserial::{Serialize, Deserialize};
 use hex::FromHex;
-use crate::utils::db::{AsDatabaseBytes, FromDatabaseValue};
+use crate::utils::db::{AsDatabaseBytes, FromDatabaseValue, IntoDatabaseValue};
 
 use std::borrow::Cow;
 use std::cmp::Ordering;
@@ -119,6 +119,16 @@ impl AsDatabaseBytes for Blake2bHash {
     }
 }
 
+impl IntoDatabaseValue for Blake2bHash {
+    fn database_byte_size(&self) -> usize {
+        return self.0.len();
+    }
+
+    fn copy_into_database(&self, bytes: &mut [u8]) {
+        bytes.copy_from_slice(&self.0);
+    }
+}
+
 impl FromDatabaseValue for Blake2bHash {
     fn copy_from_database(bytes: &[u8]) -> io::Result<Self> where Self: Sized {
         return Ok(bytes.into());
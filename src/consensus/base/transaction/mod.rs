@@ -8,6 +8,7 @@ use crate::consensus::policy;
 use std::cmp::{Ord, Ordering};
 use std::io;
 use crate::utils::merkle::Blake2bMerklePath;
+use crate::utils::db::{FromDatabaseValue, IntoDatabaseValue};
 
 #[derive(Clone, Copy, PartialEq, PartialOrd, Eq, Ord, Debug, Serialize, Deserialize)]
 #[repr(u8)]
@@ -341,6 +342,23 @@ impl SerializeContent for Transaction {
 
 impl Hash for Transaction {}
 
+impl IntoDatabaseValue for Transaction {
+    fn database_byte_size(&self) -> usize {
+        return self.serialized_size();
+    }
+
+    fn copy_into_database(&self, mut bytes: &mut [u8]) {
+        Serialize::serialize(&self, &mut bytes).unwrap();
+    }
+}
+
+impl FromDatabaseValue for Transaction {
+    fn copy_from_database(bytes: &[u8]) -> io::Result<Self> where Self: Sized {
+        let mut cursor = io::Cursor::new(bytes);
+        return Ok(Deserialize::deserialize(&mut cursor)?);
+    }
+}
+
 impl PartialEq for Transaction {
     fn eq(&self, other: &Self) -> bool {
         self.sender == other.sender
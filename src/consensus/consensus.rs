@@ -3,12 +3,18 @@ use std::sync::{Arc, Weak};
 use parking_lot::{RwLock, Mutex};
 use rand::{rngs::OsRng, Rng};
 
-use crate::consensus::base::blockchain::Blockchain;
-use crate::consensus::base::mempool::Mempool;
+use crate::consensus::base::block::Block;
+use crate::consensus::base::blockchain::{Blockchain, BlockchainError, BlockchainEvent};
+use crate::consensus::base::mempool::{Mempool, MempoolStore};
+use crate::consensus::base::primitive::Address;
+use crate::consensus::base::primitive::hash::Blake2bHash;
 use crate::consensus::consensus_agent::ConsensusAgent;
 use crate::consensus::consensus_agent::ConsensusAgentEvent;
+use crate::consensus::generator::{GenerateError, generate_blocks};
 use crate::consensus::inventory::InventoryManager;
+use crate::consensus::orphan_pool::OrphanPool;
 use crate::consensus::networks::NetworkId;
+use crate::consensus::policy;
 use crate::network::{Network, NetworkConfig, NetworkEvent, NetworkTime, Peer};
 use crate::utils::db::Environment;
 use crate::utils::observer::Notifier;
@@ -20,9 +26,11 @@ use std::collections::HashMap;
 pub struct Consensus {
     pub blockchain: Arc<Blockchain<'static>>,
     pub mempool: Arc<Mempool<'static>>,
+    mempool_store: MempoolStore<'static>,
     pub network: Arc<Network>,
 
     inv_mgr: Arc<RwLock<InventoryManager>>,
+    orphan_pool: Arc<OrphanPool>,
     timers: Timers<ConsensusTimer>,
 
     state: RwLock<ConsensusState>,
@@ -45,6 +53,9 @@ pub enum ConsensusEvent {
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 enum ConsensusTimer {
     Sync,
+    PersistMempool,
+    PruneForks,
+    PruneBodies,
 }
 
 struct ConsensusState {
@@ -52,24 +63,39 @@ struct ConsensusState {
     agents: HashMap<Arc<Peer>, Arc<ConsensusAgent>>,
 
     sync_peer: Option<Arc<Peer>>,
+
+    /// Side chains more than this many blocks behind the head are pruned.
+    fork_pruning_depth: u32,
+
+    /// In pruning mode, the bodies of blocks more than this many blocks behind the head are deleted.
+    body_pruning_depth: Option<u32>,
 }
 
 impl Consensus {
     const MIN_FULL_NODES: usize = 1;
     const SYNC_THROTTLE: Duration = Duration::from_millis(1500);
+    const MEMPOOL_PERSIST_INTERVAL: Duration = Duration::from_secs(60);
+    const FORK_PRUNING_INTERVAL: Duration = Duration::from_secs(600);
+    pub const FORK_PRUNING_DEPTH_DEFAULT: u32 = 1000;
+    const BODY_PRUNING_INTERVAL: Duration = Duration::from_secs(600);
 
-    pub fn new(env: &'static Environment, network_id: NetworkId, network_config: NetworkConfig) -> Arc<Self> {
+    pub fn new(env: &'static Environment, network_id: NetworkId, network_config: NetworkConfig) -> Result<Arc<Self>, BlockchainError> {
         let network_time = Arc::new(NetworkTime::new());
-        let blockchain = Arc::new(Blockchain::new(env, network_id, network_time.clone()));
+        let blockchain = Arc::new(Blockchain::new(env, network_id, network_time.clone())?);
         let mempool = Mempool::new(blockchain.clone());
+        let mempool_store = MempoolStore::new(env);
+        let num_restored = mempool.restore_from_store(&mempool_store);
+        info!("Restored {} transactions into the mempool", num_restored);
         let network = Network::new(blockchain.clone(), network_config, network_time);
 
         let this = Arc::new(Consensus {
             blockchain,
             mempool,
+            mempool_store,
             network,
 
             inv_mgr: InventoryManager::new(),
+            orphan_pool: Arc::new(OrphanPool::new()),
             timers: Timers::new(),
 
             state: RwLock::new(ConsensusState {
@@ -77,6 +103,9 @@ impl Consensus {
                 agents: HashMap::new(),
 
                 sync_peer: None,
+
+                fork_pruning_depth: Self::FORK_PRUNING_DEPTH_DEFAULT,
+                body_pruning_depth: None,
             }),
 
             self_weak: MutableOnce::new(Weak::new()),
@@ -85,7 +114,61 @@ impl Consensus {
             sync_lock: Mutex::new(()),
         });
         Consensus::init_listeners(&this);
-        this
+        Ok(this)
+    }
+
+    /// Starts the periodic persistence of the mempool and pruning of stale forks.
+    /// Must be called from within the runtime.
+    pub fn initialize(&self) {
+        let weak = self.self_weak.clone();
+        self.timers.set_interval(ConsensusTimer::PersistMempool, move || {
+            let this = upgrade_weak!(weak);
+            this.mempool.persist(&this.mempool_store);
+        }, Self::MEMPOOL_PERSIST_INTERVAL);
+
+        let weak = self.self_weak.clone();
+        self.timers.set_interval(ConsensusTimer::PruneForks, move || {
+            let this = upgrade_weak!(weak);
+            let depth = this.state.read().fork_pruning_depth;
+            this.blockchain.prune_forks(depth);
+        }, Self::FORK_PRUNING_INTERVAL);
+
+        let weak = self.self_weak.clone();
+        self.timers.set_interval(ConsensusTimer::PruneBodies, move || {
+            let this = upgrade_weak!(weak);
+            let depth_opt = this.state.read().body_pruning_depth;
+            if let Some(depth) = depth_opt {
+                this.blockchain.prune_bodies(depth);
+            }
+        }, Self::BODY_PRUNING_INTERVAL);
+    }
+
+    pub fn set_fork_pruning_depth(&self, depth: u32) {
+        self.state.write().fork_pruning_depth = depth;
+    }
+
+    /// Enables pruning mode, in which block bodies older than `depth` blocks are deleted.
+    /// The node should advertise `Services::pruned()` in its network config in this mode.
+    pub fn set_body_pruning_depth(&self, depth: Option<u32>) {
+        if let Some(depth) = depth {
+            assert!(depth >= policy::TRANSACTION_VALIDITY_WINDOW, "Pruning depth must be at least TRANSACTION_VALIDITY_WINDOW");
+        }
+        self.state.write().body_pruning_depth = depth;
+    }
+
+    pub fn established(&self) -> bool {
+        self.state.read().established
+    }
+
+    /// Generates `count` blocks on a development network, see `generator::generate_blocks`.
+    pub fn generate(&self, count: u32, miner: &Address) -> Result<Vec<Blake2bHash>, GenerateError> {
+        generate_blocks(&self.blockchain, &self.mempool, count, miner)
+    }
+
+    /// Stops all timers and persists the mempool.
+    pub fn shutdown(&self) {
+        self.timers.clear_all();
+        self.mempool.persist(&self.mempool_store);
     }
 
     fn init_listeners(this: &Arc<Consensus>) {
@@ -100,6 +183,32 @@ impl Consensus {
                 _ => {}
             }
         });
+
+        let weak = Arc::downgrade(this);
+        this.blockchain.notifier.write().register(move |e: &BlockchainEvent| {
+            let this = upgrade_weak!(weak);
+            match e {
+                BlockchainEvent::Extended(_, block) => this.on_head_changed(block),
+                BlockchainEvent::Rebranched(_, adopted_blocks) => {
+                    if let Some((_, block)) = adopted_blocks.last() {
+                        this.on_head_changed(block);
+                    }
+                },
+            }
+        });
+    }
+
+    /// Announces a new head block to all peers.
+    fn on_head_changed(&self, block: &Block) {
+        // Don't announce head changes if we are not synced yet.
+        let state = self.state.read();
+        if !state.established {
+            return;
+        }
+
+        for agent in state.agents.values() {
+            agent.relay_block(block);
+        }
     }
 
     fn on_peer_joined(&self, peer: Peer) {
@@ -108,6 +217,7 @@ impl Consensus {
             self.blockchain.clone(),
             self.mempool.clone(),
             self.inv_mgr.clone(),
+            self.orphan_pool.clone(),
             peer_arc.clone());
 
         let weak = self.self_weak.clone();
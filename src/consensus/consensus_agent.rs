@@ -2,10 +2,12 @@ use parking_lot::RwLock;
 use std::sync::{Arc, Weak};
 use std::time::Duration;
 
+use crate::consensus::base::block::Block;
 use crate::consensus::base::blockchain::{Blockchain, PushResult};
 use crate::consensus::base::mempool::Mempool;
-use crate::consensus::base::primitive::hash::Blake2bHash;
+use crate::consensus::base::primitive::hash::{Blake2bHash, Hash};
 use crate::consensus::inventory::{InventoryManager, InventoryAgent, InventoryEvent};
+use crate::consensus::orphan_pool::OrphanPool;
 use crate::network::Peer;
 use crate::network::connection::close_type::CloseType;
 use crate::utils::observer::Notifier;
@@ -44,6 +46,7 @@ pub struct ConsensusAgentState {
 pub struct ConsensusAgent {
     blockchain: Arc<Blockchain<'static>>,
     mempool: Arc<Mempool<'static>>,
+    orphan_pool: Arc<OrphanPool>,
     pub peer: Arc<Peer>,
 
     inv_agent: Arc<InventoryAgent>,
@@ -61,13 +64,14 @@ impl ConsensusAgent {
     const GET_BLOCKS_TIMEOUT: Duration = Duration::from_secs(10);
     const GET_BLOCKS_MAX_RESULTS: u16 = 500;
 
-    pub fn new(blockchain: Arc<Blockchain<'static>>, mempool: Arc<Mempool<'static>>, inv_mgr: Arc<RwLock<InventoryManager>>, peer: Arc<Peer>) -> Arc<Self> {
+    pub fn new(blockchain: Arc<Blockchain<'static>>, mempool: Arc<Mempool<'static>>, inv_mgr: Arc<RwLock<InventoryManager>>, orphan_pool: Arc<OrphanPool>, peer: Arc<Peer>) -> Arc<Self> {
         let sync_target = peer.head_hash.clone();
         let peer_arc = peer;
         let inv_agent = InventoryAgent::new(blockchain.clone(), mempool.clone(), inv_mgr,peer_arc.clone());
         let this = Arc::new(ConsensusAgent {
             blockchain,
             mempool,
+            orphan_pool,
             peer: peer_arc.clone(),
             inv_agent,
 
@@ -95,6 +99,15 @@ impl ConsensusAgent {
         self.state.read().synced
     }
 
+    /// Announces a new head block to the peer. Blocks are only relayed once we have synced with
+    /// the peer, before that it learns about them from the sync.
+    pub fn relay_block(&self, block: &Block) -> bool {
+        if !self.synced() {
+            return false;
+        }
+        return self.inv_agent.relay_block(block);
+    }
+
     fn init_listeners(this: &Arc<ConsensusAgent>) {
         unsafe { this.self_weak.replace(Arc::downgrade(this)) };
 
@@ -202,6 +215,7 @@ impl ConsensusAgent {
             InventoryEvent::NoNewObjectsAnnounced => self.on_no_new_objects_announced(),
             InventoryEvent::AllObjectsReceived => self.on_all_objects_received(),
             InventoryEvent::BlockProcessed(hash, result) => self.on_block_processed(hash, result),
+            InventoryEvent::OrphanBlock(block) => self.on_orphan_block(block),
             InventoryEvent::GetBlocksTimeout => self.on_get_blocks_timeout(),
             _ => {}
         }
@@ -233,30 +247,77 @@ impl ConsensusAgent {
                 self.peer.channel.close(CloseType::InvalidBlock);
             },
             PushResult::Extended | PushResult::Rebranched => {
-                let mut state = self.state.write();
-                if state.syncing {
-                    state.num_blocks_extending += 1;
+                {
+                    let mut state = self.state.write();
+                    if state.syncing {
+                        state.num_blocks_extending += 1;
+                    }
                 }
+                self.push_orphans(hash);
             },
             PushResult::Forked => {
-                let mut state = self.state.write();
-                if state.syncing {
-                    state.num_blocks_forking += 1;
-                    state.fork_head = Some(hash.clone());
+                {
+                    let mut state = self.state.write();
+                    if state.syncing {
+                        state.num_blocks_forking += 1;
+                        state.fork_head = Some(hash.clone());
+                    }
                 }
+                self.push_orphans(hash);
             }
             PushResult::Orphan => {
-                self.on_orphan_block(hash);
+                debug!("Orphan block {} from {}", hash, self.peer.peer_address());
             }
             PushResult::Known => {
                 debug!("Known block {} from {}", hash, self.peer.peer_address());
+                self.push_orphans(hash);
+            }
+            PushResult::StoreFailed => {
+                warn!("Failed to store block {} from {}", hash, self.peer.peer_address());
             }
         }
     }
 
-    fn on_orphan_block(&self, hash: &Blake2bHash) {
+    fn on_orphan_block(&self, block: &Block) {
+        let hash: Blake2bHash = block.header.hash();
         debug!("Orphan block {} from {}", hash, self.peer.peer_address());
-        // TODO
+
+        if !self.orphan_pool.add(block.clone()) {
+            return;
+        }
+
+        // The predecessor might have arrived in the meantime.
+        if self.blockchain.contains(&block.header.prev_hash, true) {
+            self.push_orphans(&block.header.prev_hash);
+            return;
+        }
+
+        // Request the missing predecessors from the peer. While syncing, they are requested anyway.
+        if !self.state.read().syncing && !self.inv_agent.is_busy() {
+            self.inv_agent.get_blocks(
+                self.blockchain.get_block_locators(),
+                ConsensusAgent::GET_BLOCKS_MAX_RESULTS,
+                ConsensusAgent::GET_BLOCKS_TIMEOUT);
+        }
+    }
+
+    /// Pushes the orphans that descend from the given block, successor by successor.
+    fn push_orphans(&self, hash: &Blake2bHash) {
+        let mut prev_hashes = vec![hash.clone()];
+        while let Some(prev_hash) = prev_hashes.pop() {
+            for block in self.orphan_pool.take_successors(&prev_hash) {
+                let hash: Blake2bHash = block.header.hash();
+                match self.blockchain.push(block) {
+                    PushResult::Extended | PushResult::Rebranched | PushResult::Forked | PushResult::Known => {
+                        debug!("Resolved orphan block {}", hash);
+                        prev_hashes.push(hash);
+                    },
+                    result => {
+                        debug!("Failed to push orphan block {}: {:?}", hash, result);
+                    }
+                }
+            }
+        }
     }
 
     fn on_get_blocks_timeout(&self) {
@@ -0,0 +1,106 @@
+use beserial::Serialize;
+use std::fmt;
+
+use crate::consensus::base::account::AccountError;
+use crate::consensus::base::block::{Block, BlockBody, BlockHeader, TargetCompact};
+use crate::consensus::base::blockchain::{Blockchain, PushError, PushResult};
+use crate::consensus::base::mempool::Mempool;
+use crate::consensus::base::primitive::Address;
+use crate::consensus::base::primitive::hash::{Blake2bHash, Hash};
+use crate::consensus::networks::{NetworkId, is_development_network};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GenerateError {
+    /// Blocks can only be generated on development networks, where mining is cheap.
+    NotDevelopmentNetwork(NetworkId),
+    AccountsError(AccountError),
+    BlockRejected(PushError),
+    /// The head changed while a block was generated.
+    HeadChanged,
+    /// The generated block could not be written to the database.
+    StoreFailed,
+}
+
+impl fmt::Display for GenerateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GenerateError::NotDevelopmentNetwork(network_id) => write!(f, "Network {:?} is not a development network", network_id),
+            GenerateError::AccountsError(e) => write!(f, "Failed to apply block body: {}", e),
+            GenerateError::BlockRejected(e) => write!(f, "Generated block was rejected: {:?}", e),
+            GenerateError::HeadChanged => write!(f, "Head changed while generating a block"),
+            GenerateError::StoreFailed => write!(f, "Failed to store the generated block"),
+        }
+    }
+}
+
+impl From<AccountError> for GenerateError {
+    fn from(e: AccountError) -> Self {
+        GenerateError::AccountsError(e)
+    }
+}
+
+/// Immediately produces `count` blocks on top of the current head of a development network and
+/// returns their hashes. Each block rewards `miner` and includes as many mempool transactions
+/// as fit. Generated blocks only depend on the chain and the mempool, so they are reproducible.
+pub fn generate_blocks<'env>(blockchain: &Blockchain<'env>, mempool: &Mempool<'env>, count: u32, miner: &Address) -> Result<Vec<Blake2bHash>, GenerateError> {
+    if !is_development_network(blockchain.network_id) {
+        return Err(GenerateError::NotDevelopmentNetwork(blockchain.network_id));
+    }
+
+    let mut hashes = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let block = next_block(blockchain, mempool, miner)?;
+        let hash: Blake2bHash = block.header.hash();
+        match blockchain.push(block) {
+            PushResult::Extended => hashes.push(hash),
+            PushResult::Invalid(e) => return Err(GenerateError::BlockRejected(e)),
+            PushResult::StoreFailed => return Err(GenerateError::StoreFailed),
+            _ => return Err(GenerateError::HeadChanged),
+        }
+    }
+    Ok(hashes)
+}
+
+fn next_block<'env>(blockchain: &Blockchain<'env>, mempool: &Mempool<'env>, miner: &Address) -> Result<Block, GenerateError> {
+    // Don't hold on to the head, pushing needs the blockchain's write lock.
+    let head = blockchain.head().clone();
+    let next_target = blockchain.get_next_target(None);
+    let interlink = head.get_next_interlink(&next_target);
+
+    let mut block = Block {
+        header: BlockHeader {
+            version: Block::VERSION,
+            prev_hash: head.header.hash(),
+            interlink_hash: interlink.hash(blockchain.network_id),
+            body_hash: [0u8; Blake2bHash::SIZE].into(),
+            accounts_hash: [0u8; Blake2bHash::SIZE].into(),
+            n_bits: TargetCompact::from(&next_target),
+            height: head.header.height + 1,
+            // Timestamps only need to be monotonic, keep them independent of the clock.
+            timestamp: head.header.timestamp + 1,
+            nonce: 0,
+        },
+        interlink,
+        body: Some(BlockBody {
+            miner: miner.clone(),
+            extra_data: Vec::new(),
+            transactions: Vec::new(),
+            pruned_accounts: Vec::new(),
+        }),
+    };
+
+    // Fill the space left by the header, interlink and the empty body with transactions.
+    let (transactions, pruned_accounts) = mempool.get_transactions_for_block(Block::MAX_SIZE - block.serialized_size());
+    let mut body = block.body.take().unwrap();
+    body.transactions = transactions;
+    body.pruned_accounts = pruned_accounts;
+
+    block.header.body_hash = body.hash();
+    block.header.accounts_hash = blockchain.accounts_hash_with_block_body(&body, block.header.height)?;
+    block.body = Some(body);
+
+    while !block.header.verify_proof_of_work() {
+        block.header.nonce += 1;
+    }
+    Ok(block)
+}
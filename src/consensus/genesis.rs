@@ -0,0 +1,323 @@
+use beserial::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io;
+
+use crate::consensus::base::account::{Account, AccountError, Accounts};
+use crate::consensus::base::block::{Block, BlockBody, BlockHeader, BlockInterlink, TargetCompact};
+use crate::consensus::base::primitive::{Address, Coin};
+use crate::consensus::base::primitive::hash::{Blake2bHash, Hash};
+use crate::consensus::networks::{NetworkId, NetworkInfo, create_seed_peer_addr, create_seed_peer_addr_ws, register_network};
+use crate::network::address::peer_address::{PeerAddress, PeerAddressType};
+use crate::utils::db::WriteTransaction;
+use crate::utils::db::memory::MemoryEnvironment;
+
+/// Target of development networks, the largest compact target.
+const DEV_N_BITS: u32 = 0x207fffff;
+
+/// A network and its genesis state, as defined by a genesis file.
+///
+/// A genesis file contains one `key: value` entry per line, lines starting with `#` are ignored:
+///
+/// ```text
+/// network: <id>
+/// name: <name>
+/// seed: <ws|wss>://<host>:<port>/<public key hex>     (any number of times)
+/// block: <serialized genesis block in hex>
+/// hash: <genesis block hash in hex>
+/// account: <address hex> <serialized account in hex>  (any number of times)
+/// development: <true|false>                           (optional, defaults to false)
+/// ```
+#[derive(Clone, Debug)]
+pub struct GenesisConfig {
+    pub network_id: NetworkId,
+    pub name: String,
+    pub seed_peers: Vec<PeerAddress>,
+    pub block: Block,
+    pub hash: Blake2bHash,
+    /// Accounts that exist before the genesis block is applied.
+    pub accounts: Vec<(Address, Account)>,
+    /// Whether the network is a development network, see `NetworkInfo::development`.
+    pub development: bool,
+}
+
+impl GenesisConfig {
+    pub fn parse(content: &str) -> Result<Self, GenesisError> {
+        let mut network_id = None;
+        let mut name = None;
+        let mut seed_peers = Vec::new();
+        let mut block = None;
+        let mut hash = None;
+        let mut accounts = Vec::new();
+        let mut development = false;
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut parts = line.splitn(2, ':');
+            let key = parts.next().unwrap_or("").trim();
+            let value = parts.next().unwrap_or("").trim();
+            match key {
+                "network" => network_id = Some(value.parse::<u8>().map(NetworkId::from).map_err(|_| GenesisError::InvalidEntry("network"))?),
+                "name" => name = Some(value.to_string()),
+                "seed" => seed_peers.push(parse_seed_peer(value).ok_or(GenesisError::InvalidEntry("seed"))?),
+                "block" => block = Some(decode_hex::<Block>(value).ok_or(GenesisError::InvalidEntry("block"))?),
+                "hash" => hash = Some(value.parse::<Blake2bHash>().map_err(|_| GenesisError::InvalidEntry("hash"))?),
+                "account" => accounts.push(parse_account(value).ok_or(GenesisError::InvalidEntry("account"))?),
+                "development" => development = value.parse::<bool>().map_err(|_| GenesisError::InvalidEntry("development"))?,
+                _ => return Err(GenesisError::InvalidEntry("key")),
+            }
+        }
+
+        Ok(GenesisConfig {
+            network_id: network_id.ok_or(GenesisError::MissingEntry("network"))?,
+            name: name.ok_or(GenesisError::MissingEntry("name"))?,
+            seed_peers,
+            block: block.ok_or(GenesisError::MissingEntry("block"))?,
+            hash: hash.ok_or(GenesisError::MissingEntry("hash"))?,
+            accounts,
+            development,
+        })
+    }
+
+    pub fn read(path: &str) -> Result<Self, GenesisError> {
+        GenesisConfig::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn write(&self, path: &str) -> Result<(), GenesisError> {
+        fs::write(path, self.to_string())?;
+        Ok(())
+    }
+
+    pub fn into_network_info(self) -> NetworkInfo {
+        NetworkInfo {
+            network_id: self.network_id,
+            name: self.name,
+            seed_peers: self.seed_peers,
+            genesis_block: self.block,
+            genesis_hash: self.hash,
+            genesis_accounts: self.accounts,
+            development: self.development,
+        }
+    }
+}
+
+impl fmt::Display for GenesisConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "network: {}", u8::from(self.network_id))?;
+        writeln!(f, "name: {}", self.name)?;
+        for peer_address in self.seed_peers.iter() {
+            let (scheme, host, port) = match peer_address.ty {
+                PeerAddressType::Ws(ref host, port) => ("ws", host, port),
+                PeerAddressType::Wss(ref host, port) => ("wss", host, port),
+                // Only websocket peers can be seeds.
+                _ => continue,
+            };
+            writeln!(f, "seed: {}://{}:{}/{}", scheme, host, port, ::hex::encode(peer_address.public_key.as_bytes()))?;
+        }
+        writeln!(f, "block: {}", ::hex::encode(self.block.serialize_to_vec()))?;
+        writeln!(f, "hash: {}", self.hash)?;
+        for (address, account) in self.accounts.iter() {
+            writeln!(f, "account: {} {}", address, ::hex::encode(account.serialize_to_vec()))?;
+        }
+        if self.development {
+            writeln!(f, "development: true")?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses a genesis accounts definition for `generate_genesis`. Each line is one of
+///
+/// ```text
+/// basic: <address hex> <balance in luna>
+/// account: <address hex> <serialized account in hex>
+/// ```
+///
+/// Lines starting with `#` are ignored.
+pub fn parse_genesis_accounts(content: &str) -> Result<Vec<(Address, Account)>, GenesisError> {
+    let mut accounts = Vec::new();
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut parts = line.splitn(2, ':');
+        let key = parts.next().unwrap_or("").trim();
+        let value = parts.next().unwrap_or("").trim();
+        let account = match key {
+            "basic" => parse_basic_account(value).ok_or(GenesisError::InvalidEntry("basic"))?,
+            "account" => parse_account(value).ok_or(GenesisError::InvalidEntry("account"))?,
+            _ => return Err(GenesisError::InvalidEntry("key")),
+        };
+        accounts.push(account);
+    }
+    Ok(accounts)
+}
+
+/// Creates the genesis block of a new network: computes the accounts hash after the genesis
+/// accounts and the (empty) genesis body, and mines a nonce that satisfies `n_bits`.
+/// The interlink hash stays zero, which is how `Block::verify_interlink` recognizes a genesis block.
+pub fn generate_genesis(network_id: NetworkId, name: String, accounts: Vec<(Address, Account)>, timestamp: u32, n_bits: TargetCompact, extra_data: Vec<u8>) -> Result<GenesisConfig, GenesisError> {
+    let body = BlockBody {
+        miner: Address::from([0u8; Address::SIZE]),
+        extra_data,
+        transactions: vec![],
+        pruned_accounts: vec![],
+    };
+
+    let accounts_hash = genesis_accounts_hash(&accounts, &body)?;
+
+    let mut header = BlockHeader {
+        version: Block::VERSION,
+        prev_hash: [0u8; Blake2bHash::SIZE].into(),
+        interlink_hash: [0u8; Blake2bHash::SIZE].into(),
+        body_hash: body.hash(),
+        accounts_hash,
+        n_bits,
+        height: 1,
+        timestamp,
+        nonce: 0,
+    };
+    while !header.verify_proof_of_work() {
+        header.nonce = header.nonce.checked_add(1).ok_or(GenesisError::NonceNotFound)?;
+    }
+
+    let hash: Blake2bHash = header.hash();
+    let block = Block {
+        header,
+        interlink: BlockInterlink::new(vec![], &[0u8; Blake2bHash::SIZE].into()),
+        body: Some(body),
+    };
+    Ok(GenesisConfig {
+        network_id,
+        name,
+        seed_peers: Vec::new(),
+        block,
+        hash,
+        accounts,
+        development: false,
+    })
+}
+
+/// Computes the accounts hash after the genesis `accounts` and the genesis `body`, which must
+/// match the accounts hash in the header of the genesis block.
+pub(crate) fn genesis_accounts_hash(accounts: &[(Address, Account)], body: &BlockBody) -> Result<Blake2bHash, GenesisError> {
+    let env = MemoryEnvironment::new();
+    let tree = Accounts::new(&env);
+    let mut txn = WriteTransaction::new(&env);
+    tree.commit_genesis(&mut txn, accounts, body)?;
+    let accounts_hash = tree.hash(Some(&txn));
+    txn.abort();
+    Ok(accounts_hash)
+}
+
+/// Creates the genesis block of a development network, see `NetworkInfo::development`.
+/// Its target is met by about every second hash and never changes.
+pub fn generate_dev_genesis(network_id: NetworkId, name: String, accounts: Vec<(Address, Account)>, timestamp: u32) -> Result<GenesisConfig, GenesisError> {
+    let mut config = generate_genesis(network_id, name, accounts, timestamp, TargetCompact::from(DEV_N_BITS), Vec::new())?;
+    config.development = true;
+    Ok(config)
+}
+
+/// Reads a genesis file and registers its network. Returns the registered network.
+pub fn load_network(path: &str) -> Result<&'static NetworkInfo, GenesisError> {
+    register_network(GenesisConfig::read(path)?.into_network_info())
+}
+
+#[derive(Debug)]
+pub enum GenesisError {
+    IoError(io::Error),
+    InvalidEntry(&'static str),
+    MissingEntry(&'static str),
+    /// The genesis block hashes to this hash instead of the announced one.
+    HashMismatch(Blake2bHash),
+    /// The genesis block has no body.
+    MissingBody,
+    /// The genesis accounts and body hash to this accounts hash instead of the one in the genesis block.
+    AccountsHashMismatch(Blake2bHash),
+    NetworkExists(NetworkId),
+    InvalidAccounts(AccountError),
+    /// No nonce satisfies the target.
+    NonceNotFound,
+}
+
+impl fmt::Display for GenesisError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GenesisError::IoError(e) => write!(f, "I/O error: {}", e),
+            GenesisError::InvalidEntry(key) => write!(f, "Invalid genesis file: invalid {} entry", key),
+            GenesisError::MissingEntry(key) => write!(f, "Invalid genesis file: missing {} entry", key),
+            GenesisError::HashMismatch(hash) => write!(f, "Genesis block hashes to {} instead of the genesis hash", hash),
+            GenesisError::MissingBody => write!(f, "Genesis block has no body"),
+            GenesisError::AccountsHashMismatch(hash) => write!(f, "Genesis accounts hash to {} instead of the accounts hash of the genesis block", hash),
+            GenesisError::NetworkExists(network_id) => write!(f, "Network {:?} is already registered", network_id),
+            GenesisError::InvalidAccounts(e) => write!(f, "Invalid genesis accounts: {}", e),
+            GenesisError::NonceNotFound => write!(f, "Found no nonce that meets the target"),
+        }
+    }
+}
+
+impl From<io::Error> for GenesisError {
+    fn from(e: io::Error) -> Self {
+        GenesisError::IoError(e)
+    }
+}
+
+impl From<AccountError> for GenesisError {
+    fn from(e: AccountError) -> Self {
+        GenesisError::InvalidAccounts(e)
+    }
+}
+
+fn decode_hex<T: Deserialize>(value: &str) -> Option<T> {
+    let bytes = ::hex::decode(value).ok()?;
+    T::deserialize_from_vec(&bytes).ok()
+}
+
+/// Parses `<ws|wss>://<host>:<port>/<public key hex>`.
+fn parse_seed_peer(value: &str) -> Option<PeerAddress> {
+    let mut parts = value.splitn(2, "://");
+    let scheme = parts.next()?;
+    let rest = parts.next()?;
+
+    let mut parts = rest.rsplitn(2, '/');
+    let public_key = parts.next()?;
+    let mut parts = parts.next()?.rsplitn(2, ':');
+    let port = parts.next()?.parse::<u16>().ok()?;
+    let host = parts.next()?;
+    if public_key.len() != 64 || ::hex::decode(public_key).is_err() {
+        return None;
+    }
+
+    match scheme {
+        "ws" => Some(create_seed_peer_addr_ws(host, port, public_key)),
+        "wss" => Some(create_seed_peer_addr(host, port, public_key)),
+        _ => None,
+    }
+}
+
+/// Parses `<address hex> <serialized account in hex>`.
+fn parse_account(value: &str) -> Option<(Address, Account)> {
+    let mut parts = value.split_whitespace();
+    let address = parts.next()?.parse::<Address>().ok()?;
+    let account = decode_hex::<Account>(parts.next()?)?;
+    if parts.next().is_some() {
+        return None;
+    }
+    Some((address, account))
+}
+
+/// Parses `<address hex> <balance in luna>`.
+fn parse_basic_account(value: &str) -> Option<(Address, Account)> {
+    let mut parts = value.split_whitespace();
+    let address = parts.next()?.parse::<Address>().ok()?;
+    let balance = parts.next()?.parse::<u64>().ok()?;
+    if parts.next().is_some() || balance > Coin::MAX_SAFE_VALUE {
+        return None;
+    }
+    Some((address, Account::new_basic(Coin::from(balance))))
+}
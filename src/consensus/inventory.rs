@@ -130,6 +130,7 @@ pub enum InventoryEvent {
     NoNewObjectsAnnounced,
     AllObjectsReceived,
     BlockProcessed(Blake2bHash, PushResult),
+    OrphanBlock(Block),
     GetBlocksTimeout,
 }
 
@@ -231,6 +232,24 @@ impl InventoryAgent {
             |this, _| this.on_close()));
     }
 
+    /// Announces `block` to the peer unless the peer is known to have it already.
+    /// Returns whether the block was announced.
+    pub fn relay_block(&self, block: &Block) -> bool {
+        let vector = InvVector::from_block(block);
+        {
+            let mut state = self.state.write();
+            if state.known_objects.contains(&vector) {
+                return false;
+            }
+
+            // Assume that the peer knows the block from now on.
+            state.known_objects.insert(vector.clone());
+        }
+
+        self.peer.channel.send_or_close(Message::Inv(vec![vector]));
+        return true;
+    }
+
     pub fn get_blocks(&self, locators: Vec<Blake2bHash>, max_results: u16, timeout: Duration) {
         let weak = self.self_weak.clone();
         self.timers.set_delay(InventoryAgentTimer::GetBlocks, move || {
@@ -346,11 +365,17 @@ impl InventoryAgent {
         let height = block.header.height;
         let num_txs = block.body.as_ref().unwrap().transactions.len();
 
+        // Keep a copy of the block if it is likely to be an orphan, so it can be pushed again later.
+        let orphan = if self.blockchain.contains(&block.header.prev_hash, true) { None } else { Some(block.clone()) };
+
         let result = self.blockchain.push(block);
 
         debug!("Block #{} ({} txs) took {}ms to process", height, num_txs, utils::duration_as_millis(&(Instant::now() - start)));
 
-        self.notifier.read().notify(InventoryEvent::BlockProcessed(vector.hash.clone(), result));
+        match (result, orphan) {
+            (PushResult::Orphan, Some(block)) => self.notifier.read().notify(InventoryEvent::OrphanBlock(block)),
+            (result, _) => self.notifier.read().notify(InventoryEvent::BlockProcessed(vector.hash.clone(), result)),
+        }
 
         self.on_object_received(&vector);
     }
@@ -535,6 +560,8 @@ impl InventoryAgent {
             Direction::from(msg.direction),
         );
 
+        // Headers of pruned blocks are kept, so announce them as well. Requests for their
+        // bodies are answered with NotFound by `on_get_data`.
         let vectors = blocks.iter().map(|block| {
             InvVector::from_block(block)
         }).collect();
@@ -1,6 +1,11 @@
 pub mod base;
 pub mod consensus;
 pub mod consensus_agent;
+pub mod generator;
+pub mod genesis;
 pub mod inventory;
 pub mod networks;
+pub mod orphan_pool;
 pub mod policy;
+#[cfg(any(test, feature = "simulation"))]
+pub mod simulation;
@@ -1,44 +1,93 @@
 use std::collections::HashMap;
+use std::fmt;
 
 use beserial::{Deserialize, Serialize};
+use parking_lot::RwLock;
 
+use crate::consensus::base::account::Account;
 use crate::consensus::base::block::{Block, BlockBody, BlockHeader, BlockInterlink};
 use crate::consensus::base::primitive::Address;
 use crate::consensus::base::primitive::crypto::PublicKey;
-use crate::consensus::base::primitive::hash::Blake2bHash;
+use crate::consensus::base::primitive::hash::{Blake2bHash, Hash};
+use crate::consensus::genesis::{GenesisError, genesis_accounts_hash};
 use crate::network::address::net_address::NetAddress;
 use crate::network::address::peer_address::PeerAddress;
 use crate::network::address::peer_address::PeerAddressType;
 use crate::network::address::PeerId;
 use crate::utils::services::ServiceFlags;
 
-#[derive(Serialize, Deserialize, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
-#[repr(u8)]
-pub enum NetworkId {
-    Test = 1,
-    Dev = 2,
-    Bounty = 3,
-    Dummy = 4,
-    Main = 42,
+/// Identifies a network on the wire. Besides the predefined networks, any other id can be used
+/// by networks registered at runtime.
+#[derive(Serialize, Deserialize, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct NetworkId(pub u8);
+
+#[allow(non_upper_case_globals)]
+impl NetworkId {
+    pub const Test: NetworkId = NetworkId(1);
+    pub const Dev: NetworkId = NetworkId(2);
+    pub const Bounty: NetworkId = NetworkId(3);
+    pub const Dummy: NetworkId = NetworkId(4);
+    pub const Main: NetworkId = NetworkId(42);
+}
+
+impl fmt::Debug for NetworkId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            NetworkId::Test => write!(f, "Test"),
+            NetworkId::Dev => write!(f, "Dev"),
+            NetworkId::Bounty => write!(f, "Bounty"),
+            NetworkId::Dummy => write!(f, "Dummy"),
+            NetworkId::Main => write!(f, "Main"),
+            NetworkId(id) => write!(f, "NetworkId({})", id),
+        }
+    }
+}
+
+impl From<u8> for NetworkId {
+    fn from(id: u8) -> Self { NetworkId(id) }
+}
+
+impl From<NetworkId> for u8 {
+    fn from(network_id: NetworkId) -> Self { network_id.0 }
 }
 
 pub struct NetworkInfo {
     pub network_id: NetworkId,
     pub name: String,
-    pub seed_peers: [PeerAddress; 20],
+    pub seed_peers: Vec<PeerAddress>,
     pub genesis_block: Block,
     pub genesis_hash: Blake2bHash,
-    pub genesis_accounts: String, // FIXME
+    /// Accounts that exist before the genesis block is applied.
+    pub genesis_accounts: Vec<(Address, Account)>,
+    /// Development networks never adjust the difficulty. Together with a trivial genesis target,
+    /// this makes blocks cheap enough to be generated on demand.
+    pub development: bool,
+}
+
+/// Decodes genesis accounts in the format of the original implementation:
+/// a `u16` count followed by `(Address, Account)` pairs.
+fn decode_genesis_accounts(accounts_hex: &str) -> Vec<(Address, Account)> {
+    let account_bytes = ::hex::decode(accounts_hex).unwrap();
+    let reader = &mut &account_bytes[..];
+    let count = u16::deserialize(reader).unwrap();
+
+    let mut accounts = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let address = Address::deserialize(reader).unwrap();
+        let account = Account::deserialize(reader).unwrap();
+        accounts.push((address, account));
+    }
+    return accounts;
 }
 
-fn create_seed_peer_addr(url: &str, port: u16, pubkey_hex: &str) -> PeerAddress {
+pub(crate) fn create_seed_peer_addr(url: &str, port: u16, pubkey_hex: &str) -> PeerAddress {
     let mut public_key_bytes : [u8; PublicKey::SIZE] = [0; PublicKey::SIZE];
     public_key_bytes.clone_from_slice(&::hex::decode(pubkey_hex.to_string()).unwrap()[0..]);
     let public_key = PublicKey::from(&public_key_bytes);
     PeerAddress { ty: PeerAddressType::Wss(url.to_string(), port), services: ServiceFlags::FULL, timestamp: 0, net_address: NetAddress::Unspecified, public_key, distance: 0, signature: None, peer_id: PeerId::from(&public_key)}
 }
 
-fn create_seed_peer_addr_ws(url: &str, port: u16, pubkey_hex: &str) -> PeerAddress {
+pub(crate) fn create_seed_peer_addr_ws(url: &str, port: u16, pubkey_hex: &str) -> PeerAddress {
     let mut public_key_bytes : [u8; PublicKey::SIZE] = [0; PublicKey::SIZE];
     public_key_bytes.clone_from_slice(&::hex::decode(pubkey_hex.to_string()).unwrap()[0..]);
     let public_key = PublicKey::from(&public_key_bytes);
@@ -46,16 +95,17 @@ fn create_seed_peer_addr_ws(url: &str, port: u16, pubkey_hex: &str) -> PeerAddre
 }
 
 lazy_static! {
-    static ref NETWORK_MAP: HashMap<NetworkId, NetworkInfo> = {
+    // Network infos are never removed, so they are leaked to hand out references without holding the lock.
+    static ref NETWORK_MAP: RwLock<HashMap<NetworkId, &'static NetworkInfo>> = {
         let mut m = HashMap::new();
-        fn add(m: &mut HashMap<NetworkId, NetworkInfo>, info: NetworkInfo) { m.insert(info.network_id, info); }
+        fn add(m: &mut HashMap<NetworkId, &'static NetworkInfo>, info: NetworkInfo) { m.insert(info.network_id, Box::leak(Box::new(info))); }
 
         add(
             &mut m,
             NetworkInfo {
                 network_id: NetworkId::Main,
                 name: "main".into(),
-                seed_peers: [
+                seed_peers: vec![
                     create_seed_peer_addr("seed-1.nimiq.com", 8443, "b70d0c3e6cdf95485cac0688b086597a5139bc4237173023c83411331ef90507"),
                     create_seed_peer_addr("seed-2.nimiq.com", 8443, "8580275aef426981a04ee5ea948ca3c95944ef1597ad78db9839f810d6c5b461"),
                     create_seed_peer_addr("seed-3.nimiq.com", 8443, "136bdec59f4d37f25ac8393bef193ff2e31c9c0a024b3edbf77fc1cb84e67a15"),
@@ -98,7 +148,7 @@ lazy_static! {
                     }),
                 },
                 genesis_hash: "264aaf8a4f9828a76c550635da078eb466306a189fcc03710bee9f649c869d12".into(),
-                genesis_accounts: "\
+                genesis_accounts: decode_genesis_accounts("\
                     05740fe832581bf6a0892412acfb9651b451c509831d0000000005dbf2a54718ce70a65cb6c7e08a\
                     bd6b24373b33574e16e70d00000000046458a440306108b9072bfcb59984fad0ecba5e6c1de46187\
                     01000000204faa14e0200020d0290be350f9dd1263c9d915cbe23bfb3b000000010001fa40000000\
@@ -1203,12 +1253,75 @@ lazy_static! {
                     493f0000000001fc5e364058ec9228c514efaca08543ba0969b145f1ab039601000000114f91cfc0\
                     ebd4ec914becf85c41585d50a81dcc0f4bae4d3d000000010001fa4000000008a7c8e7e000000011\
                     4f91cfc0ef60558d333e77839026fa1b0574e1b0f1e4d0790000000029ea5e4814f3a531509d46df\
-                    87e058c2762672a51366c3ce3b000000000014345918".into(),
+                    87e058c2762672a51366c3ce3b000000000014345918"),
+                development: false,
             },
         );
 
-        m
+        // A development network for tests, see `ChainBuilder`.
+        // Its genesis block is the one `generate_dev_genesis` creates without accounts.
+        add(
+            &mut m,
+            NetworkInfo {
+                network_id: NetworkId::Dummy,
+                name: "dummy".into(),
+                seed_peers: vec![],
+                genesis_block: Block {
+                    header: BlockHeader {
+                        version: 1,
+                        prev_hash: [0u8; 32].into(),
+                        interlink_hash: [0u8; 32].into(),
+                        body_hash: "8223886130688ca40f9812eae66b0280e6ce2f72a743d08aac819be475b3ec4f".into(),
+                        accounts_hash: "03cdfe5600e1d528a8ddbc57c2a9d62952475fb0d5b3c8b6ad5d25da6d043a0c".into(),
+                        n_bits: 0x207fffff.into(),
+                        height: 1,
+                        timestamp: 1523727000,
+                        nonce: 0,
+                    },
+                    interlink: BlockInterlink::new(vec![], &[0u8; 32].into()),
+                    body: Some(BlockBody {
+                        miner: [0u8; Address::SIZE].into(),
+                        extra_data: vec![],
+                        transactions: vec![],
+                        pruned_accounts: vec![],
+                    }),
+                },
+                genesis_hash: "7b9cc90ab58021befa1c47d9f98755b1d042621079c3084e1cda400c81dc3d3b".into(),
+                genesis_accounts: vec![],
+                development: true,
+            },
+        );
+
+        RwLock::new(m)
     };
 }
 
-pub fn get_network_info<'a>(network_id: NetworkId) -> Option<&'a NetworkInfo> { return NETWORK_MAP.get(&network_id); }
+pub fn get_network_info<'a>(network_id: NetworkId) -> Option<&'a NetworkInfo> { return NETWORK_MAP.read().get(&network_id).map(|info| *info); }
+
+pub fn is_development_network(network_id: NetworkId) -> bool {
+    get_network_info(network_id).map_or(false, |info| info.development)
+}
+
+/// Makes a network known in addition to the predefined ones, e.g. a private test network.
+/// The id must not be in use yet, `genesis_hash` must be the hash of the genesis block and
+/// the genesis accounts must lead to the accounts hash of the genesis block.
+pub fn register_network(info: NetworkInfo) -> Result<&'static NetworkInfo, GenesisError> {
+    let hash: Blake2bHash = info.genesis_block.header.hash();
+    if hash != info.genesis_hash {
+        return Err(GenesisError::HashMismatch(hash));
+    }
+
+    let genesis_body = info.genesis_block.body.as_ref().ok_or(GenesisError::MissingBody)?;
+    let accounts_hash = genesis_accounts_hash(&info.genesis_accounts, genesis_body)?;
+    if accounts_hash != info.genesis_block.header.accounts_hash {
+        return Err(GenesisError::AccountsHashMismatch(accounts_hash));
+    }
+
+    let mut network_map = NETWORK_MAP.write();
+    if network_map.contains_key(&info.network_id) {
+        return Err(GenesisError::NetworkExists(info.network_id));
+    }
+    let info: &'static NetworkInfo = Box::leak(Box::new(info));
+    network_map.insert(info.network_id, info);
+    return Ok(info);
+}
@@ -0,0 +1,91 @@
+use parking_lot::Mutex;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::consensus::base::block::Block;
+use crate::consensus::base::primitive::hash::{Blake2bHash, Hash};
+
+/// Keeps blocks whose predecessor is not known yet, keyed by their `prev_hash`.
+/// Once the pool is full, the oldest orphans are evicted first.
+pub struct OrphanPool {
+    state: Mutex<OrphanPoolState>,
+}
+
+struct OrphanPoolState {
+    blocks_by_prev_hash: HashMap<Blake2bHash, Vec<Block>>,
+    hashes: HashSet<Blake2bHash>,
+    /// (prev_hash, hash) of all orphans in insertion order.
+    insertion_order: VecDeque<(Blake2bHash, Blake2bHash)>,
+}
+
+impl OrphanPool {
+    pub const SIZE_MAX: usize = 100;
+
+    pub fn new() -> Self {
+        OrphanPool {
+            state: Mutex::new(OrphanPoolState {
+                blocks_by_prev_hash: HashMap::new(),
+                hashes: HashSet::new(),
+                insertion_order: VecDeque::new(),
+            }),
+        }
+    }
+
+    /// Adds an orphan block to the pool. Returns false if the block is already in the pool.
+    pub fn add(&self, block: Block) -> bool {
+        let hash: Blake2bHash = block.header.hash();
+        let prev_hash = block.header.prev_hash.clone();
+
+        let mut state = self.state.lock();
+        if state.hashes.contains(&hash) {
+            return false;
+        }
+
+        // Evict the oldest orphans if the pool is full.
+        while state.hashes.len() >= Self::SIZE_MAX {
+            let (oldest_prev_hash, oldest_hash) = state.insertion_order.pop_front().unwrap();
+            OrphanPool::remove(&mut state, &oldest_prev_hash, &oldest_hash);
+        }
+
+        state.hashes.insert(hash.clone());
+        state.insertion_order.push_back((prev_hash.clone(), hash));
+        state.blocks_by_prev_hash.entry(prev_hash).or_insert_with(Vec::new).push(block);
+        true
+    }
+
+    /// Removes and returns all orphans that are immediate successors of the block with the given hash.
+    pub fn take_successors(&self, prev_hash: &Blake2bHash) -> Vec<Block> {
+        let mut state = self.state.lock();
+        let blocks = match state.blocks_by_prev_hash.remove(prev_hash) {
+            Some(blocks) => blocks,
+            None => return Vec::new()
+        };
+
+        for block in blocks.iter() {
+            state.hashes.remove(&block.header.hash::<Blake2bHash>());
+        }
+        state.insertion_order.retain(|(p, _)| p != prev_hash);
+        blocks
+    }
+
+    pub fn contains(&self, hash: &Blake2bHash) -> bool {
+        self.state.lock().hashes.contains(hash)
+    }
+
+    pub fn len(&self) -> usize {
+        self.state.lock().hashes.len()
+    }
+
+    fn remove(state: &mut OrphanPoolState, prev_hash: &Blake2bHash, hash: &Blake2bHash) {
+        state.hashes.remove(hash);
+        let is_empty = match state.blocks_by_prev_hash.get_mut(prev_hash) {
+            Some(blocks) => {
+                blocks.retain(|block| &block.header.hash::<Blake2bHash>() != hash);
+                blocks.is_empty()
+            },
+            None => false
+        };
+        if is_empty {
+            state.blocks_by_prev_hash.remove(prev_hash);
+        }
+    }
+}
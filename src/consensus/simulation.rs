@@ -0,0 +1,175 @@
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use futures::future;
+use tokio::runtime::Runtime;
+
+use crate::consensus::base::primitive::Address;
+use crate::consensus::base::primitive::hash::Blake2bHash;
+use crate::consensus::consensus::Consensus;
+use crate::consensus::generator::GenerateError;
+use crate::consensus::networks::NetworkId;
+use crate::network::address::PeerAddress;
+use crate::network::memory::MemoryTransport;
+use crate::network::network_config::NetworkConfig;
+use crate::utils::db::Environment;
+use crate::utils::db::memory::MemoryEnvironment;
+
+struct SimulatedNode {
+    consensus: Arc<Consensus>,
+    peer_address: Arc<PeerAddress>,
+}
+
+/// Runs several full nodes in one process, connected through a `MemoryTransport` instead of
+/// sockets. Nodes don't connect on their own, tests set up the topology with `connect`.
+pub struct Simulation {
+    network_id: NetworkId,
+    runtime: Runtime,
+    transport: Arc<MemoryTransport>,
+    nodes: Vec<SimulatedNode>,
+}
+
+impl Simulation {
+    const PORT: u16 = 8443;
+    const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+    pub fn new(network_id: NetworkId, num_nodes: usize) -> Self {
+        let mut simulation = Simulation {
+            network_id,
+            runtime: Runtime::new().expect("Failed to start runtime"),
+            transport: MemoryTransport::new(),
+            nodes: Vec::new(),
+        };
+        for _ in 0..num_nodes {
+            simulation.add_node();
+        }
+        simulation
+    }
+
+    /// Starts another node without any connections and returns its index.
+    pub fn add_node(&mut self) -> usize {
+        let index = self.nodes.len();
+        let mut network_config = NetworkConfig::new_memory_network_config(Arc::clone(&self.transport), format!("node{}.memory", index), Self::PORT);
+        network_config.init_volatile();
+        let peer_address = Arc::new(network_config.peer_address());
+
+        // The consensus borrows its environment forever, so every node leaks one.
+        let env: &'static Environment = Box::leak(Box::new(MemoryEnvironment::new()));
+        let consensus = Consensus::new(env, self.network_id, network_config).expect("Failed to create node");
+
+        let node = Arc::clone(&consensus);
+        self.run(move || {
+            node.initialize();
+            node.network.initialize();
+            // Without seed nodes, nobody would establish consensus and start accepting connections.
+            node.network.set_allow_inbound_connections(true);
+        });
+
+        self.nodes.push(SimulatedNode {
+            consensus,
+            peer_address,
+        });
+        index
+    }
+
+    pub fn num_nodes(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn node(&self, index: usize) -> &Arc<Consensus> {
+        &self.nodes[index].consensus
+    }
+
+    pub fn peer_address(&self, index: usize) -> &Arc<PeerAddress> {
+        &self.nodes[index].peer_address
+    }
+
+    /// Opens a connection from node `from` to node `to`.
+    /// Returns false if the connection pool of `from` refused to connect.
+    pub fn connect(&mut self, from: usize, to: usize) -> bool {
+        let network = Arc::clone(&self.nodes[from].consensus.network);
+        let peer_address = Arc::clone(&self.nodes[to].peer_address);
+        self.run(move || network.connect_outbound(peer_address))
+    }
+
+    /// Connects each node to the next one.
+    pub fn connect_line(&mut self) {
+        for i in 1..self.nodes.len() {
+            assert!(self.connect(i - 1, i), "Failed to connect node {} to {}", i - 1, i);
+        }
+    }
+
+    /// Connects every other node to node `center`.
+    pub fn connect_star(&mut self, center: usize) {
+        for i in 0..self.nodes.len() {
+            if i != center {
+                assert!(self.connect(i, center), "Failed to connect node {} to {}", i, center);
+            }
+        }
+    }
+
+    /// Connects every pair of nodes.
+    pub fn connect_all(&mut self) {
+        for i in 0..self.nodes.len() {
+            for j in i + 1..self.nodes.len() {
+                assert!(self.connect(i, j), "Failed to connect node {} to {}", i, j);
+            }
+        }
+    }
+
+    /// The address that blocks generated by node `index` reward.
+    pub fn miner(index: usize) -> Address {
+        assert!(index < 255, "Too many nodes");
+        Address::from([index as u8 + 1; Address::SIZE])
+    }
+
+    /// Generates `count` blocks on node `index`, see `Consensus::generate`. Since each node rewards
+    /// its own miner address, nodes generate different blocks on top of the same head.
+    pub fn generate(&self, index: usize, count: u32) -> Result<Vec<Blake2bHash>, GenerateError> {
+        self.nodes[index].consensus.generate(count, &Self::miner(index))
+    }
+
+    /// Checks `condition` until it holds or `timeout` elapses. Returns whether it held.
+    pub fn wait_until<F: Fn(&Simulation) -> bool>(&self, timeout: Duration, condition: F) -> bool {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if condition(self) {
+                return true;
+            }
+            if Instant::now() >= deadline {
+                return false;
+            }
+            thread::sleep(Self::POLL_INTERVAL);
+        }
+    }
+
+    /// Waits until every node has `hash` as its head.
+    pub fn wait_for_head(&self, hash: &Blake2bHash, timeout: Duration) -> bool {
+        self.wait_until(timeout, |simulation| {
+            simulation.nodes.iter().all(|node| &node.consensus.blockchain.head_hash() == hash)
+        })
+    }
+
+    /// Waits until every node has established consensus.
+    pub fn wait_for_consensus(&self, timeout: Duration) -> bool {
+        self.wait_until(timeout, |simulation| {
+            simulation.nodes.iter().all(|node| node.consensus.established())
+        })
+    }
+
+    /// Runs `f` on the runtime, which is needed for everything that spawns tasks or sets timers.
+    fn run<F, R>(&mut self, f: F) -> R
+        where F: FnOnce() -> R + Send + 'static,
+              R: Send + 'static {
+        self.runtime.block_on(future::lazy(move || Ok::<R, ()>(f()))).expect("Runtime failed")
+    }
+}
+
+impl Drop for Simulation {
+    fn drop(&mut self) {
+        for node in self.nodes.iter() {
+            node.consensus.shutdown();
+        }
+    }
+}
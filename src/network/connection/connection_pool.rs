@@ -23,9 +23,10 @@ use crate::network::Protocol;
 use crate::utils::observer::PassThroughNotifier;
 
 use super::close_type::CloseType;
+use super::connector::Connector;
 use super::connection_info::{ConnectionInfo, ConnectionState};
 use crate::utils::unique_ptr::UniquePtr;
-use crate::network::websocket::websocket_connector::{WebSocketConnector, WebSocketConnectorEvent};
+use crate::network::websocket::websocket_connector::WebSocketConnectorEvent;
 use crate::utils::mutable_once::MutableOnce;
 
 macro_rules! update_checked {
@@ -315,7 +316,7 @@ pub struct ConnectionPool {
     network_config: Arc<NetworkConfig>,
     addresses: Arc<PeerAddressBook>,
 
-    websocket_connector: WebSocketConnector,
+    connector: Connector,
 
     state: RwLock<ConnectionPoolState>,
     change_lock: Mutex<()>,
@@ -334,7 +335,7 @@ impl ConnectionPool {
             network_config: network_config.clone(),
             addresses: peer_address_book,
 
-            websocket_connector: WebSocketConnector::new(network_config),
+            connector: Connector::new(network_config),
 
             state: RwLock::new(ConnectionPoolState {
                 connections: SparseVec::new(),
@@ -372,7 +373,7 @@ impl ConnectionPool {
         {
             unsafe { pool.self_weak.replace(Arc::downgrade(&pool)) };
             let weak = pool.self_weak.clone();
-            pool.websocket_connector.notifier.write().register(move |event| {
+            pool.connector.notifier().write().register(move |event| {
                 let pool = upgrade_weak!(weak);
                 match event {
                     WebSocketConnectorEvent::Connection(conn) => {
@@ -390,7 +391,7 @@ impl ConnectionPool {
     /// Initialises necessary threads.
     pub fn initialize(&self) {
         // Start accepting incoming connections.
-        self.websocket_connector.start();
+        self.connector.start();
     }
 
     /// Initiates a outbound connection.
@@ -408,7 +409,7 @@ impl ConnectionPool {
         let connection_id = state.add(ConnectionInfo::outbound(peer_address.clone()));
 
         // Choose connector type and call.
-        let handle = self.websocket_connector.connect(peer_address);
+        let handle = self.connector.connect(peer_address);
         state.connections.get_mut(connection_id).map(move |info| {
             info.set_connection_handle(handle);
         });
@@ -0,0 +1,51 @@
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::network::address::PeerAddress;
+use crate::network::memory::memory_connector::MemoryConnector;
+use crate::network::network_config::{NetworkConfig, ProtocolConfig};
+use crate::network::websocket::websocket_connector::{ConnectionHandle, WebSocketConnector, WebSocketConnectorEvent};
+use crate::utils::observer::PassThroughNotifier;
+
+/// Accepts inbound and opens outbound connections for the `ConnectionPool`.
+/// The kind of connector is chosen by the protocol config.
+pub enum Connector {
+    WebSocket(WebSocketConnector),
+    Memory(MemoryConnector),
+}
+
+impl Connector {
+    pub fn new(network_config: Arc<NetworkConfig>) -> Self {
+        let in_memory = match network_config.protocol_config() {
+            ProtocolConfig::Memory { .. } => true,
+            _ => false,
+        };
+        if in_memory {
+            Connector::Memory(MemoryConnector::new(network_config))
+        } else {
+            Connector::WebSocket(WebSocketConnector::new(network_config))
+        }
+    }
+
+    pub fn notifier(&self) -> &Arc<RwLock<PassThroughNotifier<'static, WebSocketConnectorEvent>>> {
+        match self {
+            Connector::WebSocket(connector) => &connector.notifier,
+            Connector::Memory(connector) => &connector.notifier,
+        }
+    }
+
+    pub fn start(&self) {
+        match self {
+            Connector::WebSocket(connector) => connector.start(),
+            Connector::Memory(connector) => connector.start(),
+        }
+    }
+
+    pub fn connect(&self, peer_address: Arc<PeerAddress>) -> Arc<ConnectionHandle> {
+        match self {
+            Connector::WebSocket(connector) => connector.connect(peer_address),
+            Connector::Memory(connector) => connector.connect(peer_address),
+        }
+    }
+}
@@ -1,6 +1,7 @@
 pub mod connection_pool;
 pub mod connection_info;
 pub mod close_type;
+pub mod connector;
 pub mod network_connection;
 pub mod network_agent;
 
@@ -0,0 +1,108 @@
+use std::sync::Arc;
+
+use futures::future;
+use parking_lot::RwLock;
+use tokio::io;
+
+use crate::network::{
+    address::{
+        net_address::NetAddress,
+        peer_address::PeerAddressType,
+        PeerAddress,
+    },
+    connection::{
+        AddressInfo,
+        NetworkConnection,
+    },
+    memory::{MemoryStream, MemoryTransport},
+    network_config::{
+        NetworkConfig,
+        ProtocolConfig,
+    },
+    websocket::{
+        NimiqMessageStream,
+        SharedNimiqMessageStream,
+        websocket_connector::{ConnectionHandle, WebSocketConnectorEvent},
+    },
+};
+use crate::utils::observer::PassThroughNotifier;
+
+/// A drop-in replacement for the `WebSocketConnector` that connects to other nodes in the same
+/// process through a `MemoryTransport`. Peers still see each other as `ws://` addresses.
+pub struct MemoryConnector {
+    network_config: Arc<NetworkConfig>,
+    pub notifier: Arc<RwLock<PassThroughNotifier<'static, WebSocketConnectorEvent>>>,
+}
+
+impl MemoryConnector {
+    pub fn new(network_config: Arc<NetworkConfig>) -> MemoryConnector {
+        MemoryConnector {
+            network_config,
+            notifier: Arc::new(RwLock::new(PassThroughNotifier::new())),
+        }
+    }
+
+    fn transport(&self) -> (&Arc<MemoryTransport>, &str, u16) {
+        match self.network_config.protocol_config() {
+            ProtocolConfig::Memory{transport, host, port} => (transport, host.as_str(), *port),
+            _ => panic!("Protocol not supported"),
+        }
+    }
+
+    pub fn start(&self) {
+        let (transport, host, port) = self.transport();
+        transport.listen(host.to_string(), port, Arc::clone(&self.notifier));
+    }
+
+    pub fn connect(&self, peer_address: Arc<PeerAddress>) -> Arc<ConnectionHandle> {
+        let (transport, host, port) = self.transport();
+        let transport = Arc::clone(transport);
+        let net_address = transport.net_address(host, port).unwrap_or(NetAddress::Unspecified);
+        let notifier = Arc::clone(&self.notifier);
+        let connection_handle = Arc::new(ConnectionHandle::new());
+        let connection_handle_for_closure = Arc::clone(&connection_handle);
+
+        // Like the WebSocketConnector, report the outcome asynchronously: The ConnectionPool is
+        // still busy with this connection attempt when this method returns.
+        let connect = future::lazy(move || -> Result<(), ()> {
+            let remote = match peer_address.ty {
+                PeerAddressType::Ws(ref remote_host, remote_port) => transport.net_address(remote_host, remote_port)
+                    .and_then(|remote_net_address| {
+                        transport.notifier(remote_host, remote_port).map(|remote_notifier| (remote_net_address, remote_notifier))
+                    }),
+                _ => None,
+            };
+
+            let (remote_net_address, remote_notifier) = match remote {
+                Some(remote) => remote,
+                None => {
+                    notifier.read().notify(WebSocketConnectorEvent::Error(peer_address, io::ErrorKind::ConnectionRefused));
+                    return Ok(());
+                },
+            };
+            if connection_handle_for_closure.is_aborted() {
+                return Ok(());
+            }
+
+            let (local_stream, remote_stream) = MemoryStream::pair();
+
+            let shared_stream: SharedNimiqMessageStream = NimiqMessageStream::new_memory(remote_stream, net_address, false).into();
+            let net_address = Some(Arc::new(shared_stream.net_address()));
+            let (nc, ncfut) = NetworkConnection::new_connection_setup(shared_stream, AddressInfo::new(net_address, None));
+            remote_notifier.read().notify(WebSocketConnectorEvent::Connection(nc));
+            tokio::spawn(ncfut);
+
+            let shared_stream: SharedNimiqMessageStream = NimiqMessageStream::new_memory(local_stream, remote_net_address, true).into();
+            let net_address = Some(Arc::new(shared_stream.net_address()));
+            let (nc, ncfut) = NetworkConnection::new_connection_setup(shared_stream, AddressInfo::new(net_address, Some(peer_address)));
+            notifier.read().notify(WebSocketConnectorEvent::Connection(nc));
+            tokio::spawn(ncfut);
+
+            Ok(())
+        });
+
+        tokio::spawn(connect);
+
+        connection_handle
+    }
+}
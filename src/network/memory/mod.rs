@@ -0,0 +1,122 @@
+use std::{fmt, io};
+use std::collections::HashMap;
+use std::net::Ipv4Addr;
+use std::sync::Arc;
+
+use futures::prelude::*;
+use futures::sync::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use parking_lot::RwLock;
+use tungstenite::{
+    protocol::Message as WebSocketMessage,
+    error::Error as WsError
+};
+
+use crate::network::address::net_address::NetAddress;
+use crate::network::websocket::websocket_connector::WebSocketConnectorEvent;
+use crate::utils::observer::PassThroughNotifier;
+
+pub mod memory_connector;
+
+/// One end of an in-memory duplex channel. It carries the same binary frames as a WebSocket,
+/// so a `NimiqMessageStream` on top of it goes through the regular chunking and tagging.
+pub struct MemoryStream {
+    sender: Option<UnboundedSender<WebSocketMessage>>,
+    receiver: UnboundedReceiver<WebSocketMessage>,
+}
+
+impl MemoryStream {
+    /// Creates two connected ends, everything sent into one of them is received by the other.
+    pub fn pair() -> (MemoryStream, MemoryStream) {
+        let (tx1, rx1) = unbounded();
+        let (tx2, rx2) = unbounded();
+        (MemoryStream { sender: Some(tx1), receiver: rx2 }, MemoryStream { sender: Some(tx2), receiver: rx1 })
+    }
+
+    fn closed_error() -> WsError {
+        WsError::Io(io::Error::new(io::ErrorKind::BrokenPipe, "Memory stream closed"))
+    }
+}
+
+impl Stream for MemoryStream {
+    type Item = WebSocketMessage;
+    type Error = WsError;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        // The receiver never fails, it ends once the remote end is closed or dropped.
+        self.receiver.poll().map_err(|_| Self::closed_error())
+    }
+}
+
+impl Sink for MemoryStream {
+    type SinkItem = WebSocketMessage;
+    type SinkError = WsError;
+
+    fn start_send(&mut self, item: Self::SinkItem) -> StartSend<Self::SinkItem, Self::SinkError> {
+        match self.sender {
+            Some(ref mut sender) => sender.start_send(item).map_err(|_| Self::closed_error()),
+            None => Err(Self::closed_error()),
+        }
+    }
+
+    fn poll_complete(&mut self) -> Poll<(), Self::SinkError> {
+        match self.sender {
+            Some(ref mut sender) => sender.poll_complete().map_err(|_| Self::closed_error()),
+            None => Ok(Async::Ready(())),
+        }
+    }
+
+    fn close(&mut self) -> Poll<(), Self::SinkError> {
+        // Dropping the sender ends the remote end's stream.
+        self.sender = None;
+        Ok(Async::Ready(()))
+    }
+}
+
+struct MemoryListener {
+    net_address: NetAddress,
+    notifier: Arc<RwLock<PassThroughNotifier<'static, WebSocketConnectorEvent>>>,
+}
+
+/// An in-process replacement for the internet: `MemoryConnector`s listen on a host and port
+/// and connect to each other through it. Every listener gets a distinct IPv4 address in its
+/// own subnet, so that the per-IP and per-subnet connection limits behave like on a real network.
+pub struct MemoryTransport {
+    listeners: RwLock<HashMap<(String, u16), MemoryListener>>,
+}
+
+impl MemoryTransport {
+    pub fn new() -> Arc<Self> {
+        Arc::new(MemoryTransport {
+            listeners: RwLock::new(HashMap::new()),
+        })
+    }
+
+    /// Registers a listener and returns the net address that its peers will see.
+    pub fn listen(&self, host: String, port: u16, notifier: Arc<RwLock<PassThroughNotifier<'static, WebSocketConnectorEvent>>>) -> NetAddress {
+        let mut listeners = self.listeners.write();
+        let index = listeners.len() + 1;
+        assert!(index < 1 << 16, "Too many listeners");
+        let net_address = NetAddress::IPv4(Ipv4Addr::new(10, (index >> 8) as u8, index as u8, 1));
+        let listener = MemoryListener {
+            net_address: net_address.clone(),
+            notifier,
+        };
+        assert!(listeners.insert((host.clone(), port), listener).is_none(), "Already listening on {}:{}", host, port);
+        net_address
+    }
+
+    /// Returns the net address of the listener on the given host and port.
+    pub fn net_address(&self, host: &str, port: u16) -> Option<NetAddress> {
+        self.listeners.read().get(&(host.to_string(), port)).map(|listener| listener.net_address.clone())
+    }
+
+    fn notifier(&self, host: &str, port: u16) -> Option<Arc<RwLock<PassThroughNotifier<'static, WebSocketConnectorEvent>>>> {
+        self.listeners.read().get(&(host.to_string(), port)).map(|listener| Arc::clone(&listener.notifier))
+    }
+}
+
+impl fmt::Debug for MemoryTransport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "MemoryTransport {{ listeners: {} }}", self.listeners.read().len())
+    }
+}
@@ -3,6 +3,7 @@ pub use self::time::NetworkTime;
 pub mod address;
 pub mod message;
 pub mod websocket;
+pub mod memory;
 pub mod peer_channel;
 pub mod peer_scorer;
 pub mod time;
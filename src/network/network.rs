@@ -6,6 +6,7 @@ use atomic::Ordering;
 use parking_lot::RwLock;
 
 use crate::consensus::base::blockchain::Blockchain;
+use crate::network::address::peer_address::PeerAddress;
 use crate::network::address::peer_address_book::PeerAddressBook;
 use crate::network::connection::close_type::CloseType;
 use crate::network::connection::connection_info::ConnectionState;
@@ -61,7 +62,7 @@ impl Network {
 
     pub fn new(blockchain: Arc<Blockchain<'static>>, network_config: NetworkConfig, network_time: Arc<NetworkTime>) -> Arc<Self> {
         let net_config = Arc::new(network_config);
-        let addresses = Arc::new(PeerAddressBook::new(net_config.clone()));
+        let addresses = Arc::new(PeerAddressBook::new(net_config.clone(), blockchain.network_id));
         let connections = ConnectionPool::new(addresses.clone(), net_config.clone(), blockchain);
         let mut this = Arc::new(Network {
             network_config: net_config.clone(),
@@ -264,6 +265,11 @@ impl Network {
         return self.connections.peer_count();
     }
 
+    /// Connects to a specific peer, regardless of whether this network connects automatically.
+    pub fn connect_outbound(&self, peer_address: Arc<PeerAddress>) -> bool {
+        self.connections.connect_outbound(peer_address)
+    }
+
     pub fn set_allow_inbound_connections(&self, allow_inbound_connections: bool) {
         self.connections.set_allow_inbound_connections(allow_inbound_connections);
     }
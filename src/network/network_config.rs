@@ -1,4 +1,5 @@
 use std::fs;
+use std::sync::Arc;
 use std::time::SystemTime;
 
 use beserial::{Deserialize, Serialize};
@@ -7,6 +8,7 @@ use crate::consensus::base::primitive::crypto::{KeyPair, PublicKey};
 use crate::network::address::net_address::NetAddress;
 use crate::network::address::peer_address::{PeerAddress, PeerAddressType};
 use crate::network::address::PeerId;
+use crate::network::memory::MemoryTransport;
 use crate::network::Protocol;
 use crate::utils::services::Services;
 use crate::utils::systemtime_to_timestamp;
@@ -50,6 +52,22 @@ impl NetworkConfig {
         }
     }
 
+    /// Creates a config for a node that is only reachable through the given in-memory transport.
+    /// Other nodes see it as a `ws://host:port` peer.
+    pub fn new_memory_network_config(transport: Arc<MemoryTransport>, host: String, port: u16) -> Self {
+        Self {
+            protocol_mask: ProtocolFlags::WS | ProtocolFlags::WSS,
+            key_pair: None,
+            peer_id: None,
+            services: Services::full(),
+            protocol_config: ProtocolConfig::Memory {
+                transport,
+                host,
+                port,
+            }
+        }
+    }
+
     pub fn new_dumb_network_config() -> Self {
         Self {
             protocol_mask: ProtocolFlags::WS | ProtocolFlags::WSS, // TODO Browsers might not always support WS.
@@ -140,6 +158,11 @@ impl NetworkConfig {
                     port,
                     ..
                 } => PeerAddressType::Wss(host.clone(), port),
+                ProtocolConfig::Memory {
+                    ref host,
+                    port,
+                    ..
+                } => PeerAddressType::Ws(host.clone(), port),
             },
             services: self.services.provided,
             timestamp: systemtime_to_timestamp(SystemTime::now()),
@@ -175,6 +198,11 @@ pub enum ProtocolConfig {
         identity_file: String,
     },
     Rtc,
+    Memory {
+        transport: Arc<MemoryTransport>,
+        host: String,
+        port: u16,
+    },
 }
 
 impl From<&ProtocolConfig> for Protocol {
@@ -184,6 +212,7 @@ impl From<&ProtocolConfig> for Protocol {
             ProtocolConfig::Rtc => Protocol::Rtc,
             ProtocolConfig::Ws { .. } => Protocol::Ws,
             ProtocolConfig::Wss { .. } => Protocol::Wss,
+            ProtocolConfig::Memory { .. } => Protocol::Ws,
         }
     }
 }
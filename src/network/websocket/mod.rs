@@ -23,12 +23,55 @@ use tokio_tungstenite::{
 use crate::utils::locking::MultiLock;
 use crate::network::{
     address::net_address::NetAddress,
+    memory::MemoryStream,
     message::Message as NimiqMessage
 };
 
 pub mod websocket_connector;
 
-type WebSocketLayer = WebSocketStream<MaybeTlsStream<TcpStream>>;
+/// The transport below the message framing: Either a real WebSocket or an in-memory channel.
+enum WebSocketLayer {
+    Tcp(WebSocketStream<MaybeTlsStream<TcpStream>>),
+    Memory(MemoryStream),
+}
+
+impl Stream for WebSocketLayer {
+    type Item = WebSocketMessage;
+    type Error = WsError;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        match self {
+            WebSocketLayer::Tcp(ws_socket) => ws_socket.poll(),
+            WebSocketLayer::Memory(stream) => stream.poll(),
+        }
+    }
+}
+
+impl Sink for WebSocketLayer {
+    type SinkItem = WebSocketMessage;
+    type SinkError = WsError;
+
+    fn start_send(&mut self, item: Self::SinkItem) -> StartSend<Self::SinkItem, Self::SinkError> {
+        match self {
+            WebSocketLayer::Tcp(ws_socket) => ws_socket.start_send(item),
+            WebSocketLayer::Memory(stream) => stream.start_send(item),
+        }
+    }
+
+    fn poll_complete(&mut self) -> Poll<(), Self::SinkError> {
+        match self {
+            WebSocketLayer::Tcp(ws_socket) => ws_socket.poll_complete(),
+            WebSocketLayer::Memory(stream) => stream.poll_complete(),
+        }
+    }
+
+    fn close(&mut self) -> Poll<(), Self::SinkError> {
+        match self {
+            WebSocketLayer::Tcp(ws_socket) => ws_socket.close(),
+            WebSocketLayer::Memory(stream) => stream.close(),
+        }
+    }
+}
 
 pub trait IntoData {
     fn into_data(self) -> Vec<u8>;
@@ -67,16 +110,27 @@ pub struct NimiqMessageStream {
 impl NimiqMessageStream {
     fn new(ws_socket: WebSocketStream<MaybeTlsStream<TcpStream>>, outbound: bool) -> Self {
         let peer_addr = ws_socket.get_ref().peer_addr().unwrap();
+        let net_address = match peer_addr.ip() {
+            net::IpAddr::V4(ip4) => NetAddress::IPv4(ip4),
+            net::IpAddr::V6(ip6) => NetAddress::IPv6(ip6),
+        };
+        NimiqMessageStream::with_layer(WebSocketLayer::Tcp(ws_socket), net_address, outbound)
+    }
+
+    /// Creates a message stream on top of one end of an in-memory channel.
+    /// `net_address` is the address of the remote end.
+    pub fn new_memory(stream: MemoryStream, net_address: NetAddress, outbound: bool) -> Self {
+        NimiqMessageStream::with_layer(WebSocketLayer::Memory(stream), net_address, outbound)
+    }
+
+    fn with_layer(inner: WebSocketLayer, net_address: NetAddress, outbound: bool) -> Self {
         return NimiqMessageStream {
-            inner: ws_socket,
+            inner,
             receiving_tag: 254,
             sending_tag: 0,
             ws_queue: VecDeque::new(),
             msg_buf: None,
-            net_address: match peer_addr.ip() {
-                net::IpAddr::V4(ip4) => NetAddress::IPv4(ip4),
-                net::IpAddr::V6(ip6) => NetAddress::IPv6(ip6),
-            },
+            net_address,
             outbound,
             last_chunk_received_at: None,
         };
@@ -53,6 +53,10 @@ use crate::utils::observer::PassThroughNotifier;
 pub struct ConnectionHandle(AtomicBool);
 
 impl ConnectionHandle {
+    pub fn new() -> Self {
+        ConnectionHandle(AtomicBool::new(false))
+    }
+
     pub fn abort(&self) {
         self.0.store(true, Ordering::Release);
     }
@@ -161,7 +165,7 @@ impl WebSocketConnector {
         let url = Url::parse(&peer_address.as_uri()).unwrap();
         let error_notifier = Arc::clone(&self.notifier);
         let error_peer_address = Arc::clone(&peer_address);
-        let connection_handle = Arc::new(ConnectionHandle(AtomicBool::new(false)));
+        let connection_handle = Arc::new(ConnectionHandle::new());
         let connection_handle_for_closure = Arc::clone(&connection_handle);
 
         let connect = nimiq_connect_async(url)
@@ -1,33 +1,125 @@
 use super::*;
 use lmdb_zero::traits::LmdbResultExt;
 use std::fs;
+use std::path::Path;
 use fs2;
 use rand::{thread_rng, Rng};
 use std::cmp;
 use lmdb_zero;
 use parking_lot;
 use std::fmt;
+use std::cell::Cell;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Duration;
+
+thread_local! {
+    /// Number of LMDB transactions the current thread has open, in any environment.
+    static OPEN_TRANSACTIONS: Cell<usize> = Cell::new(0);
+}
+
+/// Counts an open transaction of the current thread for as long as it lives.
+struct OpenTransaction;
+
+impl OpenTransaction {
+    fn new() -> Self {
+        OPEN_TRANSACTIONS.with(|count| count.set(count.get() + 1));
+        OpenTransaction
+    }
+
+    fn any() -> bool {
+        OPEN_TRANSACTIONS.with(|count| count.get() > 0)
+    }
+}
+
+impl Drop for OpenTransaction {
+    fn drop(&mut self) {
+        OPEN_TRANSACTIONS.with(|count| count.set(count.get() - 1));
+    }
+}
+
+#[derive(Debug)]
+pub enum ResizeError {
+    InsufficientSpace(usize, usize),
+    LmdbError(lmdb_zero::Error),
+}
+
+impl fmt::Display for ResizeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ResizeError::InsufficientSpace(available, needed) => write!(f, "Insufficient free space to extend database: {} MB available, {} MB needed", available >> 20, needed >> 20),
+            ResizeError::LmdbError(e) => write!(f, "LMDB error: {}", e),
+        }
+    }
+}
+
+impl From<lmdb_zero::Error> for ResizeError {
+    fn from(e: lmdb_zero::Error) -> Self {
+        ResizeError::LmdbError(e)
+    }
+}
 
 #[derive(Debug)]
 pub struct LmdbEnvironment {
     env: lmdb_zero::Environment,
     creation_gate: parking_lot::RwLock<()>,
+    resize_step: usize,
+    /// Set when a write ran out of map space, so that the map grows before the next write transaction.
+    map_full: AtomicBool,
+    /// Number of running copies. They hold the transaction lock, so resizes are postponed meanwhile.
+    copies: AtomicUsize,
+    /// Read-only environments neither create databases nor grow the map.
+    read_only: bool,
 }
 
 impl LmdbEnvironment {
+    /// The map grows by at least this many bytes at a time. A write transaction only starts
+    /// once at least this much space is left, so it is also the most a transaction can write.
+    pub const DEFAULT_RESIZE_STEP: usize = 1 << 30;
+    /// How long to wait for open transactions to finish before resizing.
+    const RESIZE_LOCK_TIMEOUT: Duration = Duration::from_secs(1);
+    /// Readers reserve this much address space, so that they rarely have to adopt the map size
+    /// after the writing process grew the map.
+    #[cfg(target_pointer_width = "64")]
+    const READ_ONLY_MAP_SIZE: usize = 1 << 40;
+    /// 32-bit processes can't reserve much address space, so readers adopt the map size more often.
+    #[cfg(not(target_pointer_width = "64"))]
+    const READ_ONLY_MAP_SIZE: usize = 1 << 30;
+
     pub fn new(path: &str, size: usize, max_dbs: u32, flags: lmdb_zero::open::Flags) -> Result<Environment, lmdb_zero::Error> {
-        return Ok(Environment::Persistent(LmdbEnvironment::new_lmdb_environment(path, size, max_dbs, flags)?));
+        return LmdbEnvironment::new_with_resize_step(path, size, max_dbs, flags, LmdbEnvironment::DEFAULT_RESIZE_STEP);
+    }
+
+    pub fn new_with_resize_step(path: &str, size: usize, max_dbs: u32, flags: lmdb_zero::open::Flags, resize_step: usize) -> Result<Environment, lmdb_zero::Error> {
+        return Ok(Environment::Persistent(LmdbEnvironment::new_lmdb_environment(path, size, max_dbs, flags, resize_step)?));
+    }
+
+    /// Opens the environment in `path` for reading while another process may keep writing to it.
+    /// Every read transaction sees the latest committed state.
+    pub fn open_read_only(path: &str, max_dbs: u32) -> Result<ReadOnlyEnvironment, lmdb_zero::Error> {
+        let env = LmdbEnvironment::new_lmdb_environment(path, LmdbEnvironment::READ_ONLY_MAP_SIZE, max_dbs, lmdb_zero::open::RDONLY, LmdbEnvironment::DEFAULT_RESIZE_STEP)?;
+        return Ok(ReadOnlyEnvironment(Environment::Persistent(env)));
     }
 
-    pub(in super) fn new_lmdb_environment(path: &str, size: usize, max_dbs: u32, flags: lmdb_zero::open::Flags) -> Result<Self, lmdb_zero::Error> {
-        fs::create_dir_all(path).unwrap();
+    pub(in super) fn new_lmdb_environment(path: &str, size: usize, max_dbs: u32, flags: lmdb_zero::open::Flags, resize_step: usize) -> Result<Self, lmdb_zero::Error> {
+        let read_only = flags.contains(lmdb_zero::open::RDONLY);
+        if !read_only {
+            fs::create_dir_all(path).unwrap();
+        }
 
         let mut env = lmdb_zero::EnvBuilder::new()?;
         env.set_maxdbs(max_dbs)?;
+        if read_only && size > 0 {
+            // The map of a read-only environment can't be grown later, only adopted.
+            env.set_mapsize(size)?;
+        }
         let env = unsafe {
             env.open(path, flags, 0o600)?
         };
 
+        if read_only {
+            return Ok(LmdbEnvironment { env, creation_gate: parking_lot::RwLock::new(()), resize_step, map_full: AtomicBool::new(false), copies: AtomicUsize::new(0), read_only });
+        }
+
         let info = env.info()?;
         let cur_mapsize = info.mapsize;
         if cur_mapsize < size {
@@ -37,19 +129,26 @@ impl LmdbEnvironment {
             info!("LMDB memory map size: {}", cur_mapsize);
         }
 
-        let lmdb = LmdbEnvironment { env, creation_gate: parking_lot::RwLock::new(()) };
-        if lmdb.need_resize(0) {
+        let lmdb = LmdbEnvironment { env, creation_gate: parking_lot::RwLock::new(()), resize_step, map_full: AtomicBool::new(false), copies: AtomicUsize::new(0), read_only };
+        if lmdb.need_resize(lmdb.resize_step) {
             info!("LMDB memory needs to be resized.");
-            lmdb.do_resize(0);
+            if let Err(e) = lmdb.do_resize(0) {
+                error!("Failed to resize LMDB memory map: {}", e);
+            }
         }
 
         return Ok(lmdb);
     }
 
     pub(in super) fn open_database<'env>(&'env self, name: String, flags: DatabaseFlags) -> LmdbDatabase<'env> {
+        return self.open_existing_database(name, flags).expect("Database does not exist");
+    }
+
+    /// Like `open_database`, but returns `None` if a read-only environment doesn't contain the database.
+    pub(in super) fn open_existing_database<'env>(&'env self, name: String, flags: DatabaseFlags) -> Option<LmdbDatabase<'env>> {
         // This is an implicit transaction, so take the lock first.
-        let guard = self.creation_gate.read();
-        let mut db_flags = lmdb_zero::db::CREATE;
+        let guard = self.lock_creation_gate();
+        let mut db_flags = if self.read_only { lmdb_zero::db::Flags::empty() } else { lmdb_zero::db::CREATE };
 
         // Translate flags.
         if flags.contains(DatabaseFlags::DUPLICATE_KEYS) {
@@ -67,21 +166,107 @@ impl LmdbEnvironment {
             db_flags.insert(lmdb_zero::db::INTEGERKEY);
         }
 
-        return LmdbDatabase { db: lmdb_zero::Database::open(&self.env, Some(&name), &lmdb_zero::DatabaseOptions::new(db_flags)).unwrap() };
+        let db = lmdb_zero::Database::open(&self.env, Some(&name), &lmdb_zero::DatabaseOptions::new(db_flags)).to_opt().unwrap()?;
+        return Some(LmdbDatabase { db });
     }
 
     pub(in super) fn drop_database(self) -> io::Result<()> {
         return fs::remove_dir_all(self.path().as_ref());
     }
 
+    /// Writes a compacted copy of the environment into the directory `path`.
+    /// The copy is taken from a read snapshot, so readers and writers can continue meanwhile.
+    pub(in super) fn copy_to(&self, path: &str) -> io::Result<()> {
+        fs::create_dir_all(path)?;
+        if Path::new(path).join("data.mdb").exists() {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, "Target directory already contains a database"));
+        }
+
+        // The copy runs in an implicit read transaction, so the map can't be resized until it is done.
+        // Run a pending resize first, so that writers have at least one resize step of space meanwhile.
+        self.resize_if_needed();
+        let _guard = self.lock_creation_gate();
+        self.copies.fetch_add(1, Ordering::AcqRel);
+        let result = self.env.copy(path, lmdb_zero::copy::COMPACT);
+        self.copies.fetch_sub(1, Ordering::AcqRel);
+        result.map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+    }
+
     fn path(&self) -> Cow<str> {
         return self.env.path().unwrap().to_string_lossy();
     }
 
-    pub fn do_resize(&self, increase_size: usize) {
+    pub fn do_resize(&self, increase_size: usize) -> Result<(), ResizeError> {
         // Lock creation of new transactions until resize is finished.
         let guard = self.creation_gate.write();
-        let add_size: usize = cmp::max(1 << 30, increase_size);
+        self.resize(increase_size)
+    }
+
+    /// Takes the transaction lock for a new transaction. Threads that already hold it don't
+    /// queue up behind a waiting resize, which in turn waits for their transactions.
+    fn lock_creation_gate(&self) -> parking_lot::RwLockReadGuard<()> {
+        if OpenTransaction::any() {
+            self.creation_gate.read_recursive()
+        } else {
+            self.creation_gate.read()
+        }
+    }
+
+    /// Grows the map if less than one resize step is left or a write ran out of space.
+    /// Resizing waits for the transactions of other threads, so it is postponed if the calling
+    /// thread has transactions open itself, a copy is running or the others don't finish in time.
+    /// A write that runs out of space meanwhile fails its transaction with `WriteError::MapFull`.
+    fn resize_if_needed(&self) {
+        if !self.map_full.load(Ordering::Acquire) && !self.need_resize(self.resize_step) {
+            return;
+        }
+        if OpenTransaction::any() {
+            warn!("Postponing LMDB resize, the current thread has open transactions");
+            return;
+        }
+        if self.copies.load(Ordering::Acquire) > 0 {
+            warn!("Postponing LMDB resize until the running copy is done");
+            return;
+        }
+
+        let mapsize = self.env.info().unwrap().mapsize;
+        match self.creation_gate.try_write_for(LmdbEnvironment::RESIZE_LOCK_TIMEOUT) {
+            Some(_guard) => {
+                // Another thread might have resized the map in the meantime.
+                if self.env.info().unwrap().mapsize != mapsize {
+                    self.map_full.store(false, Ordering::Release);
+                    return;
+                }
+                match self.resize(0) {
+                    Ok(()) => self.map_full.store(false, Ordering::Release),
+                    Err(e) => error!("Failed to resize LMDB memory map: {}", e),
+                }
+            },
+            None => warn!("Postponing LMDB resize, open transactions did not finish in time"),
+        }
+    }
+
+    /// Adopts the map size after another process has grown the map. This waits until the other
+    /// threads have closed their transactions. The calling thread must not have any open.
+    fn adopt_map_size(&self) {
+        assert!(!OpenTransaction::any(), "LMDB map was resized by another process while this thread has transactions open");
+
+        let mapsize = self.env.info().unwrap().mapsize;
+        loop {
+            if let Some(_guard) = self.creation_gate.try_write_for(LmdbEnvironment::RESIZE_LOCK_TIMEOUT) {
+                // Another thread might have adopted the size in the meantime.
+                if self.env.info().unwrap().mapsize == mapsize {
+                    unsafe { self.env.set_mapsize(0).unwrap() };
+                }
+                return;
+            }
+            warn!("Waiting for open transactions to adopt the LMDB map size");
+        }
+    }
+
+    /// Must be called while holding the write lock of `creation_gate`.
+    fn resize(&self, increase_size: usize) -> Result<(), ResizeError> {
+        let add_size: usize = cmp::max(self.resize_step, increase_size);
 
         let available_space = fs2::available_space(self.path().as_ref());
         match available_space {
@@ -89,8 +274,7 @@ impl LmdbEnvironment {
                 let available_space = available_space as usize;
                 // Check disk capacity.
                 if available_space < add_size {
-                    error!("Insufficient free space to extend database: {} MB available, {} MB needed.", available_space >> 20, add_size >> 20);
-                    return;
+                    return Err(ResizeError::InsufficientSpace(available_space, add_size));
                 }
             },
             Err(e) => {
@@ -98,18 +282,18 @@ impl LmdbEnvironment {
             },
         }
 
-        let info = self.env.info().unwrap();
-        let stat = self.env.stat().unwrap();
+        let info = self.env.info()?;
+        let stat = self.env.stat()?;
 
         let mut new_mapsize = info.mapsize + add_size;
         new_mapsize += new_mapsize % (stat.psize as usize);
 
-        // TODO: Should we handle the error?
         unsafe {
-            self.env.set_mapsize(new_mapsize).unwrap();
+            self.env.set_mapsize(new_mapsize)?;
         }
 
         info!("LMDB Mapsize increased. Old: {} MiB, New: {} MiB", info.mapsize / (1024 * 1024), new_mapsize / (1024 * 1024));
+        Ok(())
     }
 
     pub fn need_resize(&self, threshold_size: usize) -> bool {
@@ -151,13 +335,23 @@ pub struct LmdbDatabase<'env> {
 pub struct LmdbReadTransaction<'env> {
     txn: lmdb_zero::ReadTransaction<'env>,
     guard: parking_lot::RwLockReadGuard<'env, ()>,
+    open: OpenTransaction,
 }
 
 impl<'env> LmdbReadTransaction<'env> {
     pub(in super) fn new(env: &'env LmdbEnvironment) -> Self {
-        // This is an implicit transaction, so take the lock first.
-        let guard = env.creation_gate.read();
-        return LmdbReadTransaction { txn: lmdb_zero::ReadTransaction::new(&env.env).unwrap(), guard };
+        loop {
+            // This is an implicit transaction, so take the lock first.
+            let guard = env.lock_creation_gate();
+            match lmdb_zero::ReadTransaction::new(&env.env) {
+                // Another process has grown the map beyond our reservation, adopt its size and try again.
+                Err(lmdb_zero::Error::Code(lmdb_zero::error::MAP_RESIZED)) => {
+                    drop(guard);
+                    env.adopt_map_size();
+                },
+                result => return LmdbReadTransaction { txn: result.unwrap(), guard, open: OpenTransaction::new() },
+            }
+        }
     }
 
     pub(in super) fn get<K, V>(&self, db: &LmdbDatabase<'env>, key: &K) -> Option<V> where K: AsDatabaseBytes + ?Sized, V: FromDatabaseValue {
@@ -182,69 +376,136 @@ impl<'env> fmt::Debug for LmdbReadTransaction<'env> {
 }
 
 pub struct LmdbWriteTransaction<'env> {
-    txn: lmdb_zero::WriteTransaction<'env>,
+    env: &'env LmdbEnvironment,
+    /// Only `None` while a failed transaction is being replaced.
+    txn: Option<lmdb_zero::WriteTransaction<'env>>,
     guard: parking_lot::RwLockReadGuard<'env, ()>,
+    open: OpenTransaction,
+    /// The first write that failed. The changes made up to then were discarded with it.
+    error: Option<lmdb_zero::Error>,
 }
 
 impl<'env> LmdbWriteTransaction<'env> {
     pub(in super) fn new(env: &'env LmdbEnvironment) -> Self {
-        // Check for enough space before every write transaction.
-        if env.need_resize(0) {
-            env.do_resize(0);
-        }
-        let guard = env.creation_gate.read();
-        return LmdbWriteTransaction { txn: lmdb_zero::WriteTransaction::new(&env.env).unwrap(), guard };
+        // Make sure there is enough space before every write transaction.
+        env.resize_if_needed();
+        let guard = env.lock_creation_gate();
+        let txn = lmdb_zero::WriteTransaction::new(&env.env).unwrap();
+        return LmdbWriteTransaction { env, txn: Some(txn), guard, open: OpenTransaction::new(), error: None };
     }
 
     pub(in super) fn get<K, V>(&self, db: &LmdbDatabase<'env>, key: &K) -> Option<V> where K: AsDatabaseBytes + ?Sized, V: FromDatabaseValue {
-        let access = self.txn.access();
+        let access = self.txn().access();
         let result: Option<&[u8]> = access.get(&db.db, AsDatabaseBytes::as_database_bytes(key).as_ref()).to_opt().unwrap();
         return Some(FromDatabaseValue::copy_from_database(result?).unwrap());
     }
 
     pub(in super) fn put_reserve<K, V>(&mut self, db: &LmdbDatabase, key: &K, value: &V) where K: AsDatabaseBytes + ?Sized, V: IntoDatabaseValue + ?Sized {
+        if self.error.is_some() {
+            return;
+        }
+
         let key = AsDatabaseBytes::as_database_bytes(key);
         let value_size = IntoDatabaseValue::database_byte_size(value);
-        unsafe {
-            let mut access = self.txn.access();
-            let mut bytes: &mut [u8] = access.put_reserve_unsized(&db.db, key.as_ref(), value_size, lmdb_zero::put::Flags::empty()).unwrap();
-            IntoDatabaseValue::copy_into_database(value, &mut bytes);
-        }
+        let result = unsafe {
+            let mut access = self.txn().access();
+            let reserved: Result<&mut [u8], lmdb_zero::Error> = access.put_reserve_unsized(&db.db, key.as_ref(), value_size, lmdb_zero::put::Flags::empty());
+            reserved.map(|bytes| IntoDatabaseValue::copy_into_database(value, bytes))
+        };
+        self.check_write(result);
     }
 
     pub(in super) fn put<K, V>(&mut self, db: &LmdbDatabase, key: &K, value: &V) where K: AsDatabaseBytes + ?Sized, V: AsDatabaseBytes + ?Sized {
+        if self.error.is_some() {
+            return;
+        }
+
         let key = AsDatabaseBytes::as_database_bytes(key);
         let value = AsDatabaseBytes::as_database_bytes(value);
-        let mut access = self.txn.access();
-        access.put(&db.db, key.as_ref(), value.as_ref(), lmdb_zero::put::Flags::empty()).unwrap();
+        let result = self.txn().access().put(&db.db, key.as_ref(), value.as_ref(), lmdb_zero::put::Flags::empty());
+        self.check_write(result);
     }
 
     pub(in super) fn remove<K>(&mut self, db: &LmdbDatabase, key: &K) where K: AsDatabaseBytes + ?Sized {
-        let mut access = self.txn.access();
-        access.del_key(&db.db, AsDatabaseBytes::as_database_bytes(key).as_ref()).to_opt().unwrap();
+        if self.error.is_some() {
+            return;
+        }
+
+        let result = self.txn().access().del_key(&db.db, AsDatabaseBytes::as_database_bytes(key).as_ref()).to_opt();
+        self.check_write(result);
     }
 
     pub(in super) fn remove_item<K, V>(&mut self, db: &LmdbDatabase, key: &K, value: &V) where K: AsDatabaseBytes + ?Sized, V: AsDatabaseBytes + ?Sized {
-        let mut access = self.txn.access();
-        access.del_item(&db.db, AsDatabaseBytes::as_database_bytes(key).as_ref(), AsDatabaseBytes::as_database_bytes(value).as_ref()).to_opt().unwrap();
+        if self.error.is_some() {
+            return;
+        }
+
+        let result = self.txn().access().del_item(&db.db, AsDatabaseBytes::as_database_bytes(key).as_ref(), AsDatabaseBytes::as_database_bytes(value).as_ref()).to_opt();
+        self.check_write(result);
+    }
+
+    pub(in super) fn clear_database(&mut self, db: &LmdbDatabase) {
+        if self.error.is_some() {
+            return;
+        }
+
+        let result = self.txn().access().clear_db(&db.db);
+        self.check_write(result);
     }
 
-    pub(in super) fn commit(self) {
-        self.txn.commit().unwrap();
+    pub(in super) fn failed(&self) -> bool {
+        self.error.is_some()
+    }
+
+    pub(in super) fn commit(mut self) -> Result<(), WriteError> {
+        if let Some(e) = self.error.take() {
+            return Err(WriteError::from(e));
+        }
+
+        let result = self.txn.take().unwrap().commit();
+        if let Err(lmdb_zero::Error::Code(lmdb_zero::error::MAP_FULL)) = result {
+            self.env.map_full.store(true, Ordering::Release);
+        }
+        return result.map_err(WriteError::from);
     }
 
     pub(in super) fn cursor<'txn, 'db>(&'txn self, db: &'db Database<'env>) -> LmdbCursor<'txn, 'db> {
-        let cursor = self.txn.cursor(&db.persistent().unwrap().db).unwrap();
+        let txn = self.txn();
+        let cursor = txn.cursor(&db.persistent().unwrap().db).unwrap();
         LmdbCursor {
             cursor,
-            txn: &self.txn,
+            txn,
         }
     }
+
+    fn txn(&self) -> &lmdb_zero::WriteTransaction<'env> {
+        return self.txn.as_ref().unwrap();
+    }
+
+    /// Discards the transaction after a failed write, so that `commit` fails. LMDB refuses any
+    /// further access to a failed transaction, so later reads go to a new transaction that doesn't
+    /// see the discarded changes, and later writes are skipped.
+    fn check_write<T>(&mut self, result: Result<T, lmdb_zero::Error>) {
+        let e = match result {
+            Ok(_) => return,
+            Err(e) => e,
+        };
+
+        error!("Failed to write to LMDB, discarding the transaction: {}", e);
+        if let lmdb_zero::Error::Code(lmdb_zero::error::MAP_FULL) = e {
+            self.env.map_full.store(true, Ordering::Release);
+        }
+
+        // Abort the failed transaction before starting the new one.
+        self.txn = None;
+        self.txn = Some(lmdb_zero::WriteTransaction::new(&self.env.env).unwrap());
+        self.error = Some(e);
+    }
 }
 
 impl<'env> fmt::Debug for LmdbWriteTransaction<'env> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "LmdbWriteTransaction {{ txn: {:?} }}", self.txn)
+        write!(f, "LmdbWriteTransaction {{ txn: {:?}, error: {:?} }}", self.txn, self.error)
     }
 }
 
@@ -375,6 +636,8 @@ impl<'txn, 'db> LmdbCursor<'txn, 'db> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::Arc;
+    use std::thread;
 
     #[test]
     fn it_can_save_basic_objects() {
@@ -398,7 +661,7 @@ mod tests {
             // Overwrite and read value.
             tx.put_reserve(&db, "test", "two");
             assert_eq!(tx.get::<str, String>(&db, "test"), Some("two".to_string()));
-            tx.commit();
+            tx.commit().unwrap();
 
             // Read value.
             let tx = ReadTransaction::new(&env);
@@ -409,7 +672,7 @@ mod tests {
             let mut tx = WriteTransaction::new(&env);
             tx.remove(&db, "test");
             assert!(tx.get::<str, String>(&db, "test").is_none());
-            tx.commit();
+            tx.commit().unwrap();
 
             // Check removal.
             {
@@ -450,7 +713,7 @@ mod tests {
             assert!(tx.get::<str, String>(&db, "test").is_none());
 
             // Commit WriteTransaction.
-            txw.commit();
+            txw.commit().unwrap();
 
             // ReadTransaction should still have the old state.
             assert!(tx.get::<str, String>(&db, "test").is_none());
@@ -474,7 +737,7 @@ mod tests {
             assert!(txw.get::<str, u32>(&db, "test").is_none());
             txw.put::<str, u32>(&db, "test", &125);
             assert_eq!(txw.get::<str, u32>(&db, "test"), Some(125));
-            txw.commit();
+            txw.commit().unwrap();
 
             // Have a new ReadTransaction read the new state.
             {
@@ -487,7 +750,7 @@ mod tests {
             assert_eq!(txw.get::<str, u32>(&db, "test"), Some(125));
             txw.put::<str, u32>(&db, "test", &12);
             assert_eq!(txw.get::<str, u32>(&db, "test"), Some(12));
-            txw.commit();
+            txw.commit().unwrap();
 
             // Have a new ReadTransaction read the smaller value.
             {
@@ -501,7 +764,7 @@ mod tests {
             txw.remove_item::<str, u32>(&db, "test", &12);
             txw.put::<str, u32>(&db, "test", &5783);
             assert_eq!(txw.get::<str, u32>(&db, "test"), Some(125));
-            txw.commit();
+            txw.commit().unwrap();
 
             // Have a new ReadTransaction read the smallest value.
             {
@@ -514,7 +777,7 @@ mod tests {
             assert_eq!(txw.get::<str, u32>(&db, "test"), Some(125));
             txw.remove::<str>(&db, "test");
             assert!(txw.get::<str, u32>(&db, "test").is_none());
-            txw.commit();
+            txw.commit().unwrap();
 
             // Have a new ReadTransaction read the new state.
             {
@@ -542,7 +805,7 @@ mod tests {
             txw.put::<str, u32>(&db, "test1", &12);
             txw.put::<str, u32>(&db, "test1", &5783);
             txw.put::<str, u32>(&db, "test2", &5783);
-            txw.commit();
+            txw.commit().unwrap();
 
             // Have a new ReadTransaction read the new state.
             let tx = ReadTransaction::new(&env);
@@ -571,4 +834,115 @@ mod tests {
 
         env.drop_database().unwrap();
     }
+
+    #[test]
+    fn it_grows_the_map_before_write_transactions() {
+        let env = LmdbEnvironment::new_with_resize_step("./test5", 0, 1, lmdb_zero::open::NOTLS, 1 << 20).unwrap();
+        {
+            let db = env.open_database("test".to_string());
+            let value = vec![42u8; 4096];
+
+            // Write more than the initial map size, in transactions smaller than the resize step.
+            for chunk in 0..64u32 {
+                let mut txw = WriteTransaction::new(&env);
+                for i in chunk * 64..(chunk + 1) * 64 {
+                    txw.put::<str, [u8]>(&db, &i.to_string(), &value[..]);
+                }
+                txw.commit().unwrap();
+            }
+
+            let tx = ReadTransaction::new(&env);
+            assert_eq!(tx.get::<str, Vec<u8>>(&db, "0"), Some(value.clone()));
+            assert_eq!(tx.get::<str, Vec<u8>>(&db, "4095"), Some(value.clone()));
+        }
+
+        env.drop_database().unwrap();
+    }
+
+    #[test]
+    fn it_fails_transactions_that_outgrow_the_map() {
+        let env = LmdbEnvironment::new_with_resize_step("./test6", 0, 1, lmdb_zero::open::NOTLS, 1 << 20).unwrap();
+        {
+            let db = env.open_database("test".to_string());
+            let value = vec![42u8; 4096];
+
+            // A single transaction that writes more than the space left fails without storing anything.
+            let mut txw = WriteTransaction::new(&env);
+            for i in 0..1024u32 {
+                txw.put::<str, [u8]>(&db, &i.to_string(), &value[..]);
+            }
+            match txw.commit() {
+                Err(WriteError::MapFull) => {},
+                result => panic!("Expected the map to be full, got {:?}", result),
+            }
+
+            let tx = ReadTransaction::new(&env);
+            assert!(tx.get::<str, Vec<u8>>(&db, "0").is_none());
+            tx.close();
+
+            // The map grows before the next transaction.
+            let mut txw = WriteTransaction::new(&env);
+            for i in 0..64u32 {
+                txw.put::<str, [u8]>(&db, &i.to_string(), &value[..]);
+            }
+            txw.commit().unwrap();
+        }
+
+        env.drop_database().unwrap();
+    }
+
+    #[test]
+    fn it_lets_readers_adopt_the_map_size_of_a_concurrent_writer() {
+        let env = Arc::new(LmdbEnvironment::new_with_resize_step("./test7", 0, 1, lmdb_zero::open::NOTLS, 1 << 20).unwrap());
+        {
+            let db = env.open_database("test".to_string());
+            let mut txw = WriteTransaction::new(&env);
+            txw.put::<str, [u8]>(&db, "key", &[1u8][..]);
+            txw.commit().unwrap();
+        }
+
+        // The reader reserves less address space than the writer is going to use.
+        let reader = ReadOnlyEnvironment(Environment::Persistent(LmdbEnvironment::new_lmdb_environment("./test7", 1 << 20, 1, lmdb_zero::open::RDONLY, LmdbEnvironment::DEFAULT_RESIZE_STEP).unwrap()));
+        let reader_db = reader.open_database("test".to_string());
+
+        let writer_env = env.clone();
+        let done = Arc::new(AtomicBool::new(false));
+        let writer_done = done.clone();
+        let writer = thread::spawn(move || {
+            let db = writer_env.open_database("test".to_string());
+            let value = vec![42u8; 4096];
+            let mut result = Ok(());
+            for chunk in 0..64u32 {
+                let mut txw = WriteTransaction::new(&writer_env);
+                for i in chunk * 64..(chunk + 1) * 64 {
+                    txw.put::<str, [u8]>(&db, &i.to_string(), &value[..]);
+                }
+                result = txw.commit();
+                if result.is_err() {
+                    break;
+                }
+            }
+            // Always stop the reader, even if a write failed.
+            writer_done.store(true, Ordering::SeqCst);
+            result
+        });
+
+        // Read while the writer grows the map.
+        let mut num_reads = 0;
+        while !done.load(Ordering::SeqCst) || num_reads == 0 {
+            let tx = reader.read_transaction();
+            assert_eq!(tx.get::<str, Vec<u8>>(&reader_db, "key"), Some(vec![1u8]));
+            num_reads += 1;
+        }
+        writer.join().unwrap().unwrap();
+
+        // The writes ended beyond the map of the reader, so it has to adopt the new size to see them.
+        let tx = reader.read_transaction();
+        assert_eq!(tx.get::<str, Vec<u8>>(&reader_db, "4095"), Some(vec![42u8; 4096]));
+        tx.close();
+
+        drop(reader_db);
+        drop(reader);
+        Arc::try_unwrap(env).unwrap().drop_database().unwrap();
+    }
 }
@@ -0,0 +1,698 @@
+use super::*;
+use parking_lot::{Mutex, MutexGuard, RwLock};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
+use std::collections::Bound::{self, Excluded, Included, Unbounded};
+use std::mem;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+/// An environment that keeps all databases in ordered in-memory maps.
+///
+/// Readers work on an immutable snapshot of the committed state. The single writer records its
+/// changes per entry on top of the snapshot and publishes them as a new layer on commit, so
+/// neither a write nor a commit copies a whole database.
+#[derive(Debug)]
+pub struct MemoryEnvironment {
+    databases: Mutex<Vec<(String, DatabaseFlags)>>,
+    snapshot: RwLock<Arc<Snapshot>>,
+    write_lock: Mutex<()>,
+}
+
+type Snapshot = Vec<Table>;
+
+impl MemoryEnvironment {
+    pub fn new() -> Environment {
+        return Environment::Memory(MemoryEnvironment {
+            databases: Mutex::new(Vec::new()),
+            snapshot: RwLock::new(Arc::new(Vec::new())),
+            write_lock: Mutex::new(()),
+        });
+    }
+
+    pub(in super) fn open_database<'env>(&'env self, name: String, flags: DatabaseFlags) -> MemoryDatabase<'env> {
+        let mut databases = self.databases.lock();
+        let id = match databases.iter().position(|(db_name, _)| db_name == &name) {
+            Some(id) => {
+                // Like LMDB, refuse to reopen a database with a different layout.
+                assert!(databases[id].1 == flags, "Database {} was opened with different flags", name);
+                id
+            },
+            None => {
+                databases.push((name, flags));
+                databases.len() - 1
+            }
+        };
+        return MemoryDatabase { id, _env: PhantomData };
+    }
+
+    fn current_snapshot(&self) -> Arc<Snapshot> {
+        return self.snapshot.read().clone();
+    }
+
+    fn flags(&self, id: usize) -> DatabaseFlags {
+        return self.databases.lock()[id].1;
+    }
+}
+
+#[derive(Debug)]
+pub struct MemoryDatabase<'env> {
+    id: usize,
+    _env: PhantomData<&'env MemoryEnvironment>,
+}
+
+/// A key or value. Keys of `UINT_KEYS` and values of `DUP_UINT_VALUES` databases are ordered
+/// by their length first and then as native unsigned integers, everything else byte-wise.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Item {
+    bytes: Vec<u8>,
+    integer: bool,
+}
+
+impl Item {
+    fn new(bytes: &[u8], integer: bool) -> Self {
+        return Item { bytes: bytes.to_vec(), integer };
+    }
+
+    fn as_uint(&self) -> Option<u64> {
+        match self.bytes.len() {
+            4 => {
+                let mut buf = [0u8; 4];
+                buf.copy_from_slice(&self.bytes);
+                Some(u64::from(u32::from_ne_bytes(buf)))
+            },
+            8 => {
+                let mut buf = [0u8; 8];
+                buf.copy_from_slice(&self.bytes);
+                Some(u64::from_ne_bytes(buf))
+            },
+            _ => None,
+        }
+    }
+}
+
+impl Ord for Item {
+    fn cmp(&self, other: &Item) -> Ordering {
+        if self.integer && other.integer {
+            // Integers of different sizes must not be compared by value, that wouldn't be transitive
+            // together with the byte-wise order of items that aren't integers.
+            return self.bytes.len().cmp(&other.bytes.len()).then_with(|| {
+                match (self.as_uint(), other.as_uint()) {
+                    (Some(a), Some(b)) => a.cmp(&b),
+                    _ => self.bytes.cmp(&other.bytes),
+                }
+            });
+        }
+        return self.bytes.cmp(&other.bytes);
+    }
+}
+
+impl PartialOrd for Item {
+    fn partial_cmp(&self, other: &Item) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Maps each key to its values, or to `None` if the key was removed.
+type Entries = BTreeMap<Item, Option<BTreeSet<Item>>>;
+
+/// Committed changes on top of an older layer. A layer is merged into its parent once it has
+/// grown as large as the parent, which keeps the number of layers logarithmic in the entries.
+#[derive(Debug)]
+struct Layer {
+    entries: Entries,
+    parent: Option<Arc<Layer>>,
+}
+
+/// A database maps each key to a non-empty, ordered set of values.
+/// Without `DUPLICATE_KEYS`, the set holds exactly one value.
+///
+/// The uncommitted `changes` of a write transaction shadow the committed layers, the most
+/// recent layer shadows older ones.
+#[derive(Clone, Debug)]
+struct Table {
+    flags: DatabaseFlags,
+    changes: Entries,
+    layer: Option<Arc<Layer>>,
+}
+
+impl Table {
+    fn new(flags: DatabaseFlags) -> Self {
+        return Table { flags, changes: BTreeMap::new(), layer: None };
+    }
+
+    fn key(&self, bytes: &[u8]) -> Item {
+        return Item::new(bytes, self.flags.contains(DatabaseFlags::UINT_KEYS));
+    }
+
+    fn value(&self, bytes: &[u8]) -> Item {
+        return Item::new(bytes, self.flags.contains(DatabaseFlags::DUP_UINT_VALUES));
+    }
+
+    /// The changes and layers of this table, most recent first.
+    fn levels(&self) -> Vec<&Entries> {
+        let mut levels = vec![&self.changes];
+        let mut layer = self.layer.as_ref();
+        while let Some(current) = layer {
+            levels.push(&current.entries);
+            layer = current.parent.as_ref();
+        }
+        return levels;
+    }
+
+    fn lookup(&self, key: &Item) -> Option<(&Item, &BTreeSet<Item>)> {
+        for level in self.levels() {
+            if let Some((key, values)) = level.range((Included(key), Included(key))).next() {
+                return values.as_ref().map(|values| (key, values));
+            }
+        }
+        return None;
+    }
+
+    fn values(&self, key: &Item) -> Option<&BTreeSet<Item>> {
+        return self.lookup(key).map(|(_, values)| values);
+    }
+
+    fn get_entry(&self, key: &[u8]) -> Option<(&Item, &BTreeSet<Item>)> {
+        return self.lookup(&self.key(key));
+    }
+
+    fn get(&self, key: &[u8]) -> Option<&Item> {
+        return self.values(&self.key(key))?.iter().next();
+    }
+
+    /// Returns the first entry after `lower`, skipping removed keys.
+    fn next_entry(&self, lower: Bound<&Item>) -> Option<(&Item, &BTreeSet<Item>)> {
+        let levels = self.levels();
+        let mut lower = lower;
+        loop {
+            let key = levels.iter()
+                .filter_map(|&level| level.range((lower, Unbounded)).next().map(|(key, _)| key))
+                .min()?;
+            match self.lookup(key) {
+                Some(entry) => return Some(entry),
+                None => lower = Excluded(key),
+            }
+        }
+    }
+
+    /// Returns the last entry before `upper`, skipping removed keys.
+    fn prev_entry(&self, upper: Bound<&Item>) -> Option<(&Item, &BTreeSet<Item>)> {
+        let levels = self.levels();
+        let mut upper = upper;
+        loop {
+            let key = levels.iter()
+                .filter_map(|&level| level.range((Unbounded, upper)).next_back().map(|(key, _)| key))
+                .max()?;
+            match self.lookup(key) {
+                Some(entry) => return Some(entry),
+                None => upper = Excluded(key),
+            }
+        }
+    }
+
+    fn put(&mut self, key: &[u8], value: &[u8]) {
+        let (key, value) = (self.key(key), self.value(value));
+        // Only the values of this key are copied.
+        let mut values = match self.flags.contains(DatabaseFlags::DUPLICATE_KEYS) {
+            true => self.values(&key).cloned().unwrap_or_default(),
+            false => BTreeSet::new(),
+        };
+        values.insert(value);
+        self.changes.insert(key, Some(values));
+    }
+
+    fn remove(&mut self, key: &[u8]) {
+        let key = self.key(key);
+        self.changes.insert(key, None);
+    }
+
+    fn remove_item(&mut self, key: &[u8], value: &[u8]) {
+        if !self.flags.contains(DatabaseFlags::DUPLICATE_KEYS) {
+            return self.remove(key);
+        }
+
+        let (key, value) = (self.key(key), self.value(value));
+        let mut values = match self.values(&key) {
+            Some(values) => values.clone(),
+            None => return,
+        };
+        values.remove(&value);
+        let values = if values.is_empty() { None } else { Some(values) };
+        self.changes.insert(key, values);
+    }
+
+    fn clear(&mut self) {
+        self.changes.clear();
+        self.layer = None;
+    }
+
+    /// Turns the uncommitted changes into the most recent layer.
+    fn commit(&mut self) {
+        if self.changes.is_empty() {
+            return;
+        }
+
+        let mut layer = Layer { entries: mem::replace(&mut self.changes, BTreeMap::new()), parent: self.layer.take() };
+        while let Some(parent) = layer.parent.take() {
+            if parent.entries.len() > layer.entries.len() {
+                layer.parent = Some(parent);
+                break;
+            }
+
+            // The parent may still be part of a reader's snapshot, so merge into a copy.
+            let mut entries = parent.entries.clone();
+            entries.extend(layer.entries);
+            if parent.parent.is_none() {
+                // Nothing left to shadow.
+                entries = entries.into_iter().filter(|(_, values)| values.is_some()).collect();
+            }
+            layer = Layer { entries, parent: parent.parent.clone() };
+        }
+        self.layer = Some(Arc::new(layer));
+    }
+}
+
+fn get_table<'a>(snapshot: &'a Snapshot, db: &MemoryDatabase) -> Option<&'a Table> {
+    return snapshot.get(db.id);
+}
+
+fn decode<T: FromDatabaseValue>(item: &Item) -> T {
+    return FromDatabaseValue::copy_from_database(&item.bytes).unwrap();
+}
+
+#[derive(Debug)]
+pub struct MemoryReadTransaction<'env> {
+    snapshot: Arc<Snapshot>,
+    _env: PhantomData<&'env MemoryEnvironment>,
+}
+
+impl<'env> MemoryReadTransaction<'env> {
+    pub(in super) fn new(env: &'env MemoryEnvironment) -> Self {
+        return MemoryReadTransaction { snapshot: env.current_snapshot(), _env: PhantomData };
+    }
+
+    pub(in super) fn get<K, V>(&self, db: &MemoryDatabase, key: &K) -> Option<V> where K: AsDatabaseBytes + ?Sized, V: FromDatabaseValue {
+        let key = AsDatabaseBytes::as_database_bytes(key);
+        return get_table(&self.snapshot, db)?.get(key.as_ref()).map(decode);
+    }
+
+    pub(in super) fn cursor<'txn>(&'txn self, db: &Database<'env>) -> MemoryCursor<'txn> {
+        return MemoryCursor::new(get_table(&self.snapshot, db.memory().unwrap()));
+    }
+}
+
+#[derive(Debug)]
+pub struct MemoryWriteTransaction<'env> {
+    env: &'env MemoryEnvironment,
+    tables: Snapshot,
+    guard: MutexGuard<'env, ()>,
+}
+
+impl<'env> MemoryWriteTransaction<'env> {
+    pub(in super) fn new(env: &'env MemoryEnvironment) -> Self {
+        // Only one write transaction at a time, just like LMDB.
+        let guard = env.write_lock.lock();
+        // Only clones the handles of the tables.
+        let tables = env.current_snapshot().as_ref().clone();
+        return MemoryWriteTransaction { env, tables, guard };
+    }
+
+    pub(in super) fn get<K, V>(&self, db: &MemoryDatabase, key: &K) -> Option<V> where K: AsDatabaseBytes + ?Sized, V: FromDatabaseValue {
+        let key = AsDatabaseBytes::as_database_bytes(key);
+        return get_table(&self.tables, db)?.get(key.as_ref()).map(decode);
+    }
+
+    pub(in super) fn put_reserve<K, V>(&mut self, db: &MemoryDatabase, key: &K, value: &V) where K: AsDatabaseBytes + ?Sized, V: IntoDatabaseValue + ?Sized {
+        let mut bytes = vec![0u8; IntoDatabaseValue::database_byte_size(value)];
+        IntoDatabaseValue::copy_into_database(value, &mut bytes);
+        let key = AsDatabaseBytes::as_database_bytes(key);
+        self.table_mut(db).put(key.as_ref(), &bytes);
+    }
+
+    pub(in super) fn put<K, V>(&mut self, db: &MemoryDatabase, key: &K, value: &V) where K: AsDatabaseBytes + ?Sized, V: AsDatabaseBytes + ?Sized {
+        let key = AsDatabaseBytes::as_database_bytes(key);
+        let value = AsDatabaseBytes::as_database_bytes(value);
+        self.table_mut(db).put(key.as_ref(), value.as_ref());
+    }
+
+    pub(in super) fn remove<K>(&mut self, db: &MemoryDatabase, key: &K) where K: AsDatabaseBytes + ?Sized {
+        let key = AsDatabaseBytes::as_database_bytes(key);
+        self.table_mut(db).remove(key.as_ref());
+    }
+
+    pub(in super) fn remove_item<K, V>(&mut self, db: &MemoryDatabase, key: &K, value: &V) where K: AsDatabaseBytes + ?Sized, V: AsDatabaseBytes + ?Sized {
+        let key = AsDatabaseBytes::as_database_bytes(key);
+        let value = AsDatabaseBytes::as_database_bytes(value);
+        self.table_mut(db).remove_item(key.as_ref(), value.as_ref());
+    }
+
+    pub(in super) fn clear_database(&mut self, db: &MemoryDatabase) {
+        self.table_mut(db).clear();
+    }
+
+    pub(in super) fn failed(&self) -> bool {
+        false
+    }
+
+    pub(in super) fn commit(mut self) -> Result<(), WriteError> {
+        for table in self.tables.iter_mut() {
+            table.commit();
+        }
+        *self.env.snapshot.write() = Arc::new(self.tables);
+        return Ok(());
+    }
+
+    pub(in super) fn cursor<'txn>(&'txn self, db: &Database<'env>) -> MemoryCursor<'txn> {
+        return MemoryCursor::new(get_table(&self.tables, db.memory().unwrap()));
+    }
+
+    fn table_mut(&mut self, db: &MemoryDatabase) -> &mut Table {
+        // Databases opened after the transaction started are not part of its snapshot yet.
+        while self.tables.len() <= db.id {
+            let flags = self.env.flags(self.tables.len());
+            self.tables.push(Table::new(flags));
+        }
+        return &mut self.tables[db.id];
+    }
+}
+
+pub struct MemoryCursor<'txn> {
+    table: Option<&'txn Table>,
+    position: Option<(&'txn Item, &'txn Item)>,
+}
+
+impl<'txn> MemoryCursor<'txn> {
+    fn new(table: Option<&'txn Table>) -> Self {
+        return MemoryCursor { table, position: None };
+    }
+
+    /// Moves the cursor to the given item and returns it. The position is kept if there is none.
+    fn move_to<K, V>(&mut self, item: Option<(&'txn Item, &'txn Item)>) -> Option<(K, V)> where K: FromDatabaseValue, V: FromDatabaseValue {
+        let (key, value) = item?;
+        self.position = Some((key, value));
+        return Some((decode(key), decode(value)));
+    }
+
+    fn values(&self, key: &Item) -> Option<&'txn BTreeSet<Item>> {
+        return self.table?.values(key);
+    }
+
+    fn first_item(&self) -> Option<(&'txn Item, &'txn Item)> {
+        let (key, values) = self.table?.next_entry(Unbounded)?;
+        return Some((key, values.iter().next()?));
+    }
+
+    fn last_item(&self) -> Option<(&'txn Item, &'txn Item)> {
+        let (key, values) = self.table?.prev_entry(Unbounded)?;
+        return Some((key, values.iter().next_back()?));
+    }
+
+    fn next_key_item(&self, key: &Item) -> Option<(&'txn Item, &'txn Item)> {
+        let (key, values) = self.table?.next_entry(Excluded(key))?;
+        return Some((key, values.iter().next()?));
+    }
+
+    fn prev_key_item(&self, key: &Item) -> Option<(&'txn Item, &'txn Item)> {
+        let (key, values) = self.table?.prev_entry(Excluded(key))?;
+        return Some((key, values.iter().next_back()?));
+    }
+
+    fn next_duplicate_item(&self) -> Option<(&'txn Item, &'txn Item)> {
+        let (key, value) = self.position?;
+        let next = self.values(key)?.range((Excluded(value), Unbounded)).next()?;
+        return Some((key, next));
+    }
+
+    fn prev_duplicate_item(&self) -> Option<(&'txn Item, &'txn Item)> {
+        let (key, value) = self.position?;
+        let prev = self.values(key)?.range((Unbounded, Excluded(value))).next_back()?;
+        return Some((key, prev));
+    }
+
+    fn seek_item(&self, key: &[u8]) -> Option<(&'txn Item, &'txn Item)> {
+        let (key, values) = self.table?.get_entry(key)?;
+        return Some((key, values.iter().next()?));
+    }
+
+    pub(in super) fn first<K, V>(&mut self) -> Option<(K, V)> where K: FromDatabaseValue, V: FromDatabaseValue {
+        let item = self.first_item();
+        self.move_to(item)
+    }
+
+    pub(in super) fn first_duplicate<V>(&mut self) -> Option<(V)> where V: FromDatabaseValue {
+        let (key, _) = self.position?;
+        let item = self.values(key)?.iter().next().map(|value| (key, value));
+        self.move_to::<Vec<u8>, V>(item).map(|(_, value)| value)
+    }
+
+    pub(in super) fn last<K, V>(&mut self) -> Option<(K, V)> where K: FromDatabaseValue, V: FromDatabaseValue {
+        let item = self.last_item();
+        self.move_to(item)
+    }
+
+    pub(in super) fn last_duplicate<V>(&mut self) -> Option<(V)> where V: FromDatabaseValue {
+        let (key, _) = self.position?;
+        let item = self.values(key)?.iter().next_back().map(|value| (key, value));
+        self.move_to::<Vec<u8>, V>(item).map(|(_, value)| value)
+    }
+
+    pub(in super) fn seek_key_value<K, V>(&mut self, key: &K, value: &V) -> bool where K: AsDatabaseBytes + ?Sized, V: AsDatabaseBytes + ?Sized {
+        let key = AsDatabaseBytes::as_database_bytes(key);
+        let value = AsDatabaseBytes::as_database_bytes(value);
+        let table = match self.table {
+            Some(table) => table,
+            None => return false,
+        };
+        let item = table.get_entry(key.as_ref())
+            .and_then(|(key, values)| values.get(&table.value(value.as_ref())).map(|value| (key, value)));
+        self.move_to::<Vec<u8>, Vec<u8>>(item).is_some()
+    }
+
+    pub(in super) fn seek_key_nearest_value<K, V>(&mut self, key: &K, value: &V) -> Option<V> where K: AsDatabaseBytes + ?Sized, V: AsDatabaseBytes + FromDatabaseValue {
+        let key = AsDatabaseBytes::as_database_bytes(key);
+        let value = AsDatabaseBytes::as_database_bytes(value);
+        let table = self.table?;
+        let (key, values) = table.get_entry(key.as_ref())?;
+        let nearest = values.range((Included(&table.value(value.as_ref())), Unbounded)).next()?;
+        self.move_to::<Vec<u8>, V>(Some((key, nearest))).map(|(_, value)| value)
+    }
+
+    pub(in super) fn get_current<K, V>(&mut self) -> Option<(K, V)> where K: FromDatabaseValue, V: FromDatabaseValue {
+        let (key, value) = self.position?;
+        Some((decode(key), decode(value)))
+    }
+
+    pub(in super) fn next<K, V>(&mut self) -> Option<(K, V)> where K: FromDatabaseValue, V: FromDatabaseValue {
+        let item = match self.position {
+            Some((key, _)) => self.next_duplicate_item().or_else(|| self.next_key_item(key)),
+            None => self.first_item(),
+        };
+        self.move_to(item)
+    }
+
+    pub(in super) fn next_duplicate<K, V>(&mut self) -> Option<(K, V)> where K: FromDatabaseValue, V: FromDatabaseValue {
+        let item = self.next_duplicate_item();
+        self.move_to(item)
+    }
+
+    pub(in super) fn next_no_duplicate<K, V>(&mut self) -> Option<(K, V)> where K: FromDatabaseValue, V: FromDatabaseValue {
+        let item = match self.position {
+            Some((key, _)) => self.next_key_item(key),
+            None => self.first_item(),
+        };
+        self.move_to(item)
+    }
+
+    pub(in super) fn prev<K, V>(&mut self) -> Option<(K, V)> where K: FromDatabaseValue, V: FromDatabaseValue {
+        let item = match self.position {
+            Some((key, _)) => self.prev_duplicate_item().or_else(|| self.prev_key_item(key)),
+            None => self.last_item(),
+        };
+        self.move_to(item)
+    }
+
+    pub(in super) fn prev_duplicate<K, V>(&mut self) -> Option<(K, V)> where K: FromDatabaseValue, V: FromDatabaseValue {
+        let item = self.prev_duplicate_item();
+        self.move_to(item)
+    }
+
+    pub(in super) fn prev_no_duplicate<K, V>(&mut self) -> Option<(K, V)> where K: FromDatabaseValue, V: FromDatabaseValue {
+        let item = match self.position {
+            Some((key, _)) => self.prev_key_item(key),
+            None => self.last_item(),
+        };
+        self.move_to(item)
+    }
+
+    pub(in super) fn seek_key<K, V>(&mut self, key: &K) -> Option<V> where K: AsDatabaseBytes + ?Sized, V: FromDatabaseValue {
+        let key = AsDatabaseBytes::as_database_bytes(key);
+        let item = self.seek_item(key.as_ref());
+        self.move_to::<Vec<u8>, V>(item).map(|(_, value)| value)
+    }
+
+    pub(in super) fn seek_key_both<K, V>(&mut self, key: &K) -> Option<(K, V)> where K: AsDatabaseBytes + FromDatabaseValue, V: FromDatabaseValue {
+        let key = AsDatabaseBytes::as_database_bytes(key);
+        let item = self.seek_item(key.as_ref());
+        self.move_to(item)
+    }
+
+    pub(in super) fn seek_range_key<K, V>(&mut self, key: &K) -> Option<(K, V)> where K: AsDatabaseBytes + FromDatabaseValue, V: FromDatabaseValue {
+        let key = AsDatabaseBytes::as_database_bytes(key);
+        let table = self.table?;
+        let item = table.next_entry(Included(&table.key(key.as_ref())))
+            .and_then(|(key, values)| values.iter().next().map(|value| (key, value)));
+        self.move_to(item)
+    }
+
+    pub(in super) fn count_duplicates(&mut self) -> usize {
+        match self.position {
+            Some((key, _)) => self.values(key).map_or(0, BTreeSet::len),
+            None => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_can_read_and_write() {
+        let env = MemoryEnvironment::new();
+        let db = env.open_database("test".to_string());
+
+        let mut txw = WriteTransaction::new(&env);
+        txw.put_reserve(&db, "test", "one");
+        assert_eq!(txw.get::<str, String>(&db, "test"), Some("one".to_string()));
+        txw.put_reserve(&db, "test", "two");
+        assert_eq!(txw.get::<str, String>(&db, "test"), Some("two".to_string()));
+        txw.commit().unwrap();
+
+        let tx = ReadTransaction::new(&env);
+        assert_eq!(tx.get::<str, String>(&db, "test"), Some("two".to_string()));
+        tx.close();
+
+        let mut txw = WriteTransaction::new(&env);
+        txw.remove(&db, "test");
+        txw.commit().unwrap();
+        assert!(ReadTransaction::new(&env).get::<str, String>(&db, "test").is_none());
+
+        // Aborted writes are discarded.
+        let mut txw = WriteTransaction::new(&env);
+        txw.put_reserve(&db, "test", "three");
+        txw.abort();
+        assert!(ReadTransaction::new(&env).get::<str, String>(&db, "test").is_none());
+    }
+
+    #[test]
+    fn it_isolates_readers() {
+        let env = MemoryEnvironment::new();
+        let db = env.open_database("test".to_string());
+
+        let tx = ReadTransaction::new(&env);
+        let mut txw = WriteTransaction::new(&env);
+        txw.put_reserve(&db, "test", "one");
+        assert!(tx.get::<str, String>(&db, "test").is_none());
+        txw.commit().unwrap();
+
+        // The old snapshot is unaffected by the commit.
+        assert!(tx.get::<str, String>(&db, "test").is_none());
+        assert_eq!(ReadTransaction::new(&env).get::<str, String>(&db, "test"), Some("one".to_string()));
+    }
+
+    #[test]
+    fn it_keeps_snapshots_across_many_commits() {
+        let env = MemoryEnvironment::new();
+        let db = env.open_database_with_flags("test".to_string(), DatabaseFlags::UINT_KEYS);
+
+        let mut snapshots = Vec::new();
+        for i in 0..100u32 {
+            let mut txw = WriteTransaction::new(&env);
+            txw.put::<u32, u32>(&db, &i, &i);
+            if i % 3 == 1 {
+                txw.remove::<u32>(&db, &(i / 2));
+            }
+            txw.commit().unwrap();
+            snapshots.push((i, ReadTransaction::new(&env)));
+        }
+
+        for (i, tx) in snapshots.iter() {
+            assert_eq!(tx.get::<u32, u32>(&db, i), Some(*i));
+            assert!(tx.get::<u32, u32>(&db, &(i + 1)).is_none());
+        }
+
+        let tx = ReadTransaction::new(&env);
+        let mut cursor = tx.cursor(&db);
+        let mut keys = Vec::new();
+        let mut item = cursor.first::<u32, u32>();
+        while let Some((key, _)) = item {
+            keys.push(key);
+            item = cursor.next::<u32, u32>();
+        }
+        let expected: Vec<u32> = (0..100).filter(|key| !(0..100).any(|i| i % 3 == 1 && i / 2 == *key)).collect();
+        assert_eq!(keys, expected);
+    }
+
+    #[test]
+    fn it_supports_duplicate_keys() {
+        let env = MemoryEnvironment::new();
+        let db = env.open_database_with_flags("test".to_string(), DatabaseFlags::DUPLICATE_KEYS | DatabaseFlags::DUP_UINT_VALUES);
+        let test1: String = "test1".to_string();
+        let test2: String = "test2".to_string();
+
+        let mut txw = WriteTransaction::new(&env);
+        txw.put::<str, u32>(&db, "test1", &125);
+        txw.put::<str, u32>(&db, "test1", &12);
+        txw.put::<str, u32>(&db, "test1", &5783);
+        txw.put::<str, u32>(&db, "test2", &5783);
+        assert_eq!(txw.get::<str, u32>(&db, "test1"), Some(12));
+        txw.commit().unwrap();
+
+        let tx = ReadTransaction::new(&env);
+        let mut cursor = tx.cursor(&db);
+        assert_eq!(cursor.first::<String, u32>(), Some((test1.clone(), 12)));
+        assert_eq!(cursor.last::<String, u32>(), Some((test2.clone(), 5783)));
+        assert_eq!(cursor.prev::<String, u32>(), Some((test1.clone(), 5783)));
+        assert_eq!(cursor.first_duplicate::<u32>(), Some(12));
+        assert_eq!(cursor.next_duplicate::<String, u32>(), Some((test1.clone(), 125)));
+        assert_eq!(cursor.prev_duplicate::<String, u32>(), Some((test1.clone(), 12)));
+        assert_eq!(cursor.next_no_duplicate::<String, u32>(), Some((test2.clone(), 5783)));
+        assert!(cursor.seek_key::<str, u32>("test").is_none());
+        assert_eq!(cursor.seek_key::<str, u32>("test1"), Some(12));
+        assert_eq!(cursor.count_duplicates(), 3);
+        assert_eq!(cursor.last_duplicate::<u32>(), Some(5783));
+        assert!(!cursor.seek_key_value::<str, u32>("test1", &15));
+        assert!(cursor.seek_key_value::<str, u32>("test1", &125));
+        assert_eq!(cursor.get_current::<String, u32>(), Some((test1.clone(), 125)));
+        assert_eq!(cursor.seek_key_nearest_value::<str, u32>("test1", &126), Some(5783));
+        assert_eq!(cursor.get_current::<String, u32>(), Some((test1.clone(), 5783)));
+        assert!(cursor.prev_no_duplicate::<String, u32>().is_none());
+        assert_eq!(cursor.next::<String, u32>(), Some((test2.clone(), 5783)));
+
+        let mut txw = WriteTransaction::new(&env);
+        txw.remove_item::<str, u32>(&db, "test1", &12);
+        assert_eq!(txw.get::<str, u32>(&db, "test1"), Some(125));
+        txw.remove::<str>(&db, "test1");
+        assert!(txw.get::<str, u32>(&db, "test1").is_none());
+        txw.commit().unwrap();
+    }
+
+    #[test]
+    fn it_orders_integers_of_different_sizes_consistently() {
+        let small = Item::new(&5u32.to_ne_bytes(), true);
+        let large = Item::new(&3u64.to_ne_bytes(), true);
+        let odd = Item::new(&[0u8; 6], true);
+        assert!(small < odd);
+        assert!(odd < large);
+        assert!(small < large);
+    }
+
+    #[test]
+    #[should_panic]
+    fn it_refuses_to_reopen_a_database_with_different_flags() {
+        let env = MemoryEnvironment::new();
+        env.open_database_with_flags("test".to_string(), DatabaseFlags::UINT_KEYS);
+        env.open_database("test".to_string());
+    }
+}
@@ -1,7 +1,10 @@
 pub mod lmdb;
 pub mod volatile;
+pub mod memory;
+pub mod table;
 
 use lmdb_zero;
+use std::fmt;
 use std::io;
 use std::borrow::Cow;
 use std::ops::Deref;
@@ -35,10 +38,38 @@ bitflags! {
     }
 }
 
+/// Error of a write transaction that could not be committed. None of its changes are stored.
+#[derive(Debug)]
+pub enum WriteError {
+    /// The memory map ran out of space. It grows before the next write transaction starts,
+    /// so the changes can be written again.
+    MapFull,
+    LmdbError(lmdb_zero::Error),
+}
+
+impl fmt::Display for WriteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WriteError::MapFull => write!(f, "Database map is full"),
+            WriteError::LmdbError(e) => write!(f, "LMDB error: {}", e),
+        }
+    }
+}
+
+impl From<lmdb_zero::Error> for WriteError {
+    fn from(e: lmdb_zero::Error) -> Self {
+        match e {
+            lmdb_zero::Error::Code(lmdb_zero::error::MAP_FULL) => WriteError::MapFull,
+            e => WriteError::LmdbError(e),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum Environment {
     Volatile(volatile::VolatileEnvironment),
     Persistent(lmdb::LmdbEnvironment),
+    Memory(memory::MemoryEnvironment),
 }
 
 impl Environment {
@@ -46,6 +77,7 @@ impl Environment {
         match *self {
             Environment::Volatile(ref env) => { return Database::Volatile(env.open_database(name, Default::default())); }
             Environment::Persistent(ref env) => { return Database::Persistent(env.open_database(name, Default::default())); }
+            Environment::Memory(ref env) => { return Database::Memory(env.open_database(name, Default::default())); }
         }
     }
 
@@ -53,15 +85,71 @@ impl Environment {
         match *self {
             Environment::Volatile(ref env) => { return Database::Volatile(env.open_database(name, flags)); }
             Environment::Persistent(ref env) => { return Database::Persistent(env.open_database(name, flags)); }
+            Environment::Memory(ref env) => { return Database::Memory(env.open_database(name, flags)); }
         }
     }
 
     pub fn close(self) {}
 
+    /// Writes a consistent, compacted copy of the environment into the directory `path`
+    /// without blocking readers or writers.
+    pub fn copy_to(&self, path: &str) -> io::Result<()> {
+        match *self {
+            Environment::Volatile(ref env) => { return env.copy_to(path); }
+            Environment::Persistent(ref env) => { return env.copy_to(path); }
+            Environment::Memory(_) => { return Err(io::Error::new(io::ErrorKind::Other, "In-memory environments cannot be copied")); }
+        }
+    }
+
     pub fn drop_database(self) -> io::Result<()> {
         match self {
             Environment::Volatile(env) => { return Ok(()); }
             Environment::Persistent(env) => { return env.drop_database(); }
+            Environment::Memory(env) => { return Ok(()); }
+        }
+    }
+}
+
+/// An environment that only hands out read transactions, e.g. for inspecting the database
+/// of a running node from another process. It can't be used to create write transactions.
+#[derive(Debug)]
+pub struct ReadOnlyEnvironment(Environment);
+
+impl ReadOnlyEnvironment {
+    pub fn open_database(&self, name: String) -> Database {
+        self.0.open_database(name)
+    }
+
+    pub fn open_database_with_flags(&self, name: String, flags: DatabaseFlags) -> Database {
+        self.0.open_database_with_flags(name, flags)
+    }
+
+    /// Opens a database, returns `None` if the writer never created it.
+    pub fn open_existing_database(&self, name: String) -> Option<Database> {
+        match self.0 {
+            Environment::Persistent(ref env) => env.open_existing_database(name, Default::default()).map(Database::Persistent),
+            _ => Some(self.0.open_database(name)),
+        }
+    }
+
+    /// Starts a read transaction on the latest committed state.
+    pub fn read_transaction(&self) -> ReadTransaction {
+        ReadTransaction::new(&self.0)
+    }
+}
+
+/// An environment of either kind, for stores that only start read transactions by themselves.
+#[derive(Debug, Clone, Copy)]
+pub enum ReadableEnvironment<'env> {
+    Writable(&'env Environment),
+    ReadOnly(&'env ReadOnlyEnvironment),
+}
+
+impl<'env> ReadableEnvironment<'env> {
+    pub fn read_transaction(&self) -> ReadTransaction<'env> {
+        match *self {
+            ReadableEnvironment::Writable(env) => ReadTransaction::new(env),
+            ReadableEnvironment::ReadOnly(env) => env.read_transaction(),
         }
     }
 }
@@ -70,6 +158,7 @@ impl Environment {
 pub enum Database<'env> {
     Volatile(volatile::VolatileDatabase<'env>),
     Persistent(lmdb::LmdbDatabase<'env>),
+    Memory(memory::MemoryDatabase<'env>),
 }
 
 impl<'env> Database<'env> {
@@ -84,8 +173,16 @@ impl<'env> Database<'env> {
         match self {
             Database::Persistent(ref db) => Some(db),
             Database::Volatile(ref db) => Some(db.as_lmdb()),
+            Database::Memory(_) => None,
         }
     }
+
+    fn memory(&self) -> Option<&memory::MemoryDatabase> {
+        if let Database::Memory(ref db) = self {
+            return Some(db);
+        }
+        return None;
+    }
 }
 
 #[derive(Debug)]
@@ -94,15 +191,26 @@ pub enum Transaction<'env> {
     VolatileWrite(volatile::VolatileWriteTransaction<'env>),
     PersistentRead(lmdb::LmdbReadTransaction<'env>),
     PersistentWrite(lmdb::LmdbWriteTransaction<'env>),
+    MemoryRead(memory::MemoryReadTransaction<'env>),
+    MemoryWrite(memory::MemoryWriteTransaction<'env>),
 }
 
 impl<'env> Transaction<'env> {
+    pub fn is_write(&self) -> bool {
+        match *self {
+            Transaction::VolatileWrite(_) | Transaction::PersistentWrite(_) | Transaction::MemoryWrite(_) => true,
+            _ => false,
+        }
+    }
+
     pub fn get<K, V>(&self, db: &Database, key: &K) -> Option<V> where K: AsDatabaseBytes + ?Sized, V: FromDatabaseValue {
         match *self {
             Transaction::VolatileRead(ref txn) => { return txn.get(db.volatile().unwrap(), key); }
             Transaction::VolatileWrite(ref txn) => { return txn.get(db.volatile().unwrap(), key); }
             Transaction::PersistentRead(ref txn) => { return txn.get(db.persistent().unwrap(), key); }
             Transaction::PersistentWrite(ref txn) => { return txn.get(db.persistent().unwrap(), key); }
+            Transaction::MemoryRead(ref txn) => { return txn.get(db.memory().unwrap(), key); }
+            Transaction::MemoryWrite(ref txn) => { return txn.get(db.memory().unwrap(), key); }
         }
     }
 
@@ -112,6 +220,8 @@ impl<'env> Transaction<'env> {
             Transaction::VolatileWrite(ref txn) => { return Cursor::VolatileCursor(txn.cursor(db)); }
             Transaction::PersistentRead(ref txn) => { return Cursor::PersistentCursor(txn.cursor(db)); }
             Transaction::PersistentWrite(ref txn) => { return Cursor::PersistentCursor(txn.cursor(db)); }
+            Transaction::MemoryRead(ref txn) => { return Cursor::MemoryCursor(txn.cursor(db)); }
+            Transaction::MemoryWrite(ref txn) => { return Cursor::MemoryCursor(txn.cursor(db)); }
         }
     }
 }
@@ -124,6 +234,7 @@ impl<'env> ReadTransaction<'env> {
         match *env {
             Environment::Volatile(ref env) => { return ReadTransaction(Transaction::VolatileRead(volatile::VolatileReadTransaction::new(env))); }
             Environment::Persistent(ref env) => { return ReadTransaction(Transaction::PersistentRead(lmdb::LmdbReadTransaction::new(env))); }
+            Environment::Memory(ref env) => { return ReadTransaction(Transaction::MemoryRead(memory::MemoryReadTransaction::new(env))); }
         }
     }
 
@@ -154,6 +265,7 @@ impl<'env> WriteTransaction<'env> {
         match *env {
             Environment::Volatile(ref env) => { return WriteTransaction(Transaction::VolatileWrite(volatile::VolatileWriteTransaction::new(env))); }
             Environment::Persistent(ref env) => { return WriteTransaction(Transaction::PersistentWrite(lmdb::LmdbWriteTransaction::new(env))); }
+            Environment::Memory(ref env) => { return WriteTransaction(Transaction::MemoryWrite(memory::MemoryWriteTransaction::new(env))); }
         }
     }
 
@@ -168,6 +280,7 @@ impl<'env> WriteTransaction<'env> {
         match self.0 {
             Transaction::VolatileWrite(ref mut txn) => { return txn.put_reserve(db.volatile().unwrap(), key, value); }
             Transaction::PersistentWrite(ref mut txn) => { return txn.put_reserve(db.persistent().unwrap(), key, value); }
+            Transaction::MemoryWrite(ref mut txn) => { return txn.put_reserve(db.memory().unwrap(), key, value); }
             _ => { unreachable!(); }
         }
     }
@@ -180,6 +293,7 @@ impl<'env> WriteTransaction<'env> {
         match self.0 {
             Transaction::VolatileWrite(ref mut txn) => { return txn.put(db.volatile().unwrap(), key, value); }
             Transaction::PersistentWrite(ref mut txn) => { return txn.put(db.persistent().unwrap(), key, value); }
+            Transaction::MemoryWrite(ref mut txn) => { return txn.put(db.memory().unwrap(), key, value); }
             _ => { unreachable!(); }
         }
     }
@@ -188,6 +302,7 @@ impl<'env> WriteTransaction<'env> {
         match self.0 {
             Transaction::VolatileWrite(ref mut txn) => { return txn.remove(db.volatile().unwrap(), key); }
             Transaction::PersistentWrite(ref mut txn) => { return txn.remove(db.persistent().unwrap(), key); }
+            Transaction::MemoryWrite(ref mut txn) => { return txn.remove(db.memory().unwrap(), key); }
             _ => { unreachable!(); }
         }
     }
@@ -196,14 +311,39 @@ impl<'env> WriteTransaction<'env> {
         match self.0 {
             Transaction::VolatileWrite(ref mut txn) => { return txn.remove_item(db.volatile().unwrap(), key, value); }
             Transaction::PersistentWrite(ref mut txn) => { return txn.remove_item(db.persistent().unwrap(), key, value); }
+            Transaction::MemoryWrite(ref mut txn) => { return txn.remove_item(db.memory().unwrap(), key, value); }
             _ => { unreachable!(); }
         }
     }
 
-    pub fn commit(self) {
+    /// Removes all entries from the database.
+    pub fn clear_database(&mut self, db: &Database) {
+        match self.0 {
+            Transaction::VolatileWrite(ref mut txn) => { return txn.clear_database(db.volatile().unwrap()); }
+            Transaction::PersistentWrite(ref mut txn) => { return txn.clear_database(db.persistent().unwrap()); }
+            Transaction::MemoryWrite(ref mut txn) => { return txn.clear_database(db.memory().unwrap()); }
+            _ => { unreachable!(); }
+        }
+    }
+
+    /// Whether a write of the transaction failed. Reads then no longer see any of its changes
+    /// and `commit` returns the error.
+    pub fn failed(&self) -> bool {
+        match self.0 {
+            Transaction::VolatileWrite(ref txn) => { return txn.failed(); }
+            Transaction::PersistentWrite(ref txn) => { return txn.failed(); }
+            Transaction::MemoryWrite(ref txn) => { return txn.failed(); }
+            _ => { unreachable!(); }
+        }
+    }
+
+    /// Stores all changes of the transaction. Fails if any of its writes failed, in which case
+    /// nothing is stored.
+    pub fn commit(self) -> Result<(), WriteError> {
         match self.0 {
             Transaction::VolatileWrite(txn) => { return txn.commit(); }
             Transaction::PersistentWrite(txn) => { return txn.commit(); }
+            Transaction::MemoryWrite(txn) => { return txn.commit(); }
             _ => { unreachable!(); }
         }
     }
@@ -226,6 +366,7 @@ impl<'env> Deref for WriteTransaction<'env> {
 pub enum Cursor<'txn, 'db> {
     VolatileCursor(volatile::VolatileCursor<'txn, 'db>),
     PersistentCursor(lmdb::LmdbCursor<'txn, 'db>),
+    MemoryCursor(memory::MemoryCursor<'txn>),
 }
 
 macro_rules! gen_cursor_match {
@@ -237,6 +378,9 @@ macro_rules! gen_cursor_match {
             Cursor::VolatileCursor(ref mut cursor) => {
                 cursor.$f()
             },
+            Cursor::MemoryCursor(ref mut cursor) => {
+                cursor.$f()
+            },
         }
     };
     ($self: ident, $f: ident, $k: expr) => {
@@ -247,6 +391,9 @@ macro_rules! gen_cursor_match {
             Cursor::VolatileCursor(ref mut cursor) => {
                 cursor.$f($k)
             },
+            Cursor::MemoryCursor(ref mut cursor) => {
+                cursor.$f($k)
+            },
         }
     };
     ($self: ident, $f: ident, $k: expr, $v: expr) => {
@@ -257,6 +404,9 @@ macro_rules! gen_cursor_match {
             Cursor::VolatileCursor(ref mut cursor) => {
                 cursor.$f($k, $v)
             },
+            Cursor::MemoryCursor(ref mut cursor) => {
+                cursor.$f($k, $v)
+            },
         }
     };
 }
@@ -357,6 +507,16 @@ impl IntoDatabaseValue for str {
     }
 }
 
+impl IntoDatabaseValue for String {
+    fn database_byte_size(&self) -> usize {
+        self.as_str().database_byte_size()
+    }
+
+    fn copy_into_database(&self, bytes: &mut [u8]) {
+        self.as_str().copy_into_database(bytes)
+    }
+}
+
 impl FromDatabaseValue for String {
     fn copy_from_database(bytes: &[u8]) -> io::Result<Self> where Self: Sized {
         return Ok(String::from_utf8(bytes.to_vec()).unwrap());
@@ -0,0 +1,188 @@
+use super::*;
+use std::marker::PhantomData;
+
+/// Declares a table: its database name, flags and the types of its keys and values.
+///
+/// ```ignore
+/// struct HeightIndex;
+///
+/// impl TableSpec for HeightIndex {
+///     const NAME: &'static str = "HeightIdx";
+///     type Key = u32;
+///     type Value = Blake2bHash;
+///
+///     fn flags() -> DatabaseFlags {
+///         DatabaseFlags::DUPLICATE_KEYS | DatabaseFlags::DUP_FIXED_SIZE_VALUES
+///     }
+/// }
+/// ```
+pub trait TableSpec {
+    const NAME: &'static str;
+    type Key: AsDatabaseBytes + FromDatabaseValue;
+    type Value: IntoDatabaseValue + FromDatabaseValue;
+
+    fn flags() -> DatabaseFlags {
+        DatabaseFlags::default()
+    }
+}
+
+/// A database whose keys and values are encoded according to its `TableSpec`.
+#[derive(Debug)]
+pub struct Table<'env, T: TableSpec> {
+    db: Database<'env>,
+    _spec: PhantomData<T>,
+}
+
+impl<'env, T: TableSpec> Table<'env, T> {
+    pub fn open(env: &'env Environment) -> Self {
+        let db = env.open_database_with_flags(T::NAME.to_string(), T::flags());
+        return Table { db, _spec: PhantomData };
+    }
+
+    /// Opens the table of a read-only environment.
+    pub fn open_read_only(env: &'env ReadOnlyEnvironment) -> Self {
+        let db = env.open_database_with_flags(T::NAME.to_string(), T::flags());
+        return Table { db, _spec: PhantomData };
+    }
+
+    /// Returns the value stored under `key`, or its first value in a table with duplicate keys.
+    pub fn get(&self, txn: &Transaction, key: &T::Key) -> Option<T::Value> {
+        txn.get(&self.db, key)
+    }
+
+    /// Returns all values stored under `key` in a table with duplicate keys, in the order of their encoding.
+    pub fn get_all(&self, txn: &Transaction, key: &T::Key) -> Vec<T::Value> {
+        let mut values = Vec::new();
+        let mut cursor = txn.cursor(&self.db);
+        let mut value_opt = cursor.seek_key::<T::Key, T::Value>(key);
+        while let Some(value) = value_opt {
+            values.push(value);
+            value_opt = cursor.next_duplicate::<T::Key, T::Value>().map(|(_, value)| value);
+        }
+        return values;
+    }
+
+    /// Stores `value` under `key`. In a table with duplicate keys, the value is added to the existing ones.
+    pub fn put(&self, txn: &mut WriteTransaction, key: &T::Key, value: &T::Value) {
+        if T::flags().contains(DatabaseFlags::DUPLICATE_KEYS) {
+            // Values can't be reserved in tables with duplicate keys.
+            txn.put(&self.db, key, &Self::encode_value(value)[..]);
+        } else {
+            txn.put_reserve(&self.db, key, value);
+        }
+    }
+
+    /// Removes `key` and all its values.
+    pub fn remove(&self, txn: &mut WriteTransaction, key: &T::Key) {
+        txn.remove(&self.db, key);
+    }
+
+    /// Removes a single value of `key` from a table with duplicate keys.
+    pub fn remove_item(&self, txn: &mut WriteTransaction, key: &T::Key, value: &T::Value) {
+        txn.remove_item(&self.db, key, &Self::encode_value(value)[..]);
+    }
+
+    /// Removes all entries.
+    pub fn clear(&self, txn: &mut WriteTransaction) {
+        txn.clear_database(&self.db);
+    }
+
+    /// Iterates over all entries in key order.
+    pub fn iter<'txn>(&'txn self, txn: &'txn Transaction<'env>) -> TableIter<'txn, T> {
+        let mut cursor = txn.cursor(&self.db);
+        let first = cursor.first();
+        return TableIter::new(cursor, first);
+    }
+
+    /// Iterates over all entries in key order, starting at the first key greater than or equal to `start`.
+    pub fn range<'txn>(&'txn self, txn: &'txn Transaction<'env>, start: &T::Key) -> TableIter<'txn, T> {
+        let mut cursor = txn.cursor(&self.db);
+        let first = cursor.seek_range_key(start);
+        return TableIter::new(cursor, first);
+    }
+
+    /// The underlying database, for operations the typed interface doesn't cover.
+    pub fn database(&self) -> &Database<'env> {
+        &self.db
+    }
+
+    fn encode_value(value: &T::Value) -> Vec<u8> {
+        let mut bytes = vec![0u8; IntoDatabaseValue::database_byte_size(value)];
+        IntoDatabaseValue::copy_into_database(value, &mut bytes);
+        bytes
+    }
+}
+
+/// Iterator over the `(key, value)` pairs of a table, including all values of duplicate keys.
+pub struct TableIter<'txn, T: TableSpec> {
+    cursor: Cursor<'txn, 'txn>,
+    pending: Option<(T::Key, T::Value)>,
+    exhausted: bool,
+}
+
+impl<'txn, T: TableSpec> TableIter<'txn, T> {
+    fn new(cursor: Cursor<'txn, 'txn>, first: Option<(T::Key, T::Value)>) -> Self {
+        // An unpositioned cursor would restart at the first entry, so stop right away.
+        let exhausted = first.is_none();
+        return TableIter { cursor, pending: first, exhausted };
+    }
+}
+
+impl<'txn, T: TableSpec> Iterator for TableIter<'txn, T> {
+    type Item = (T::Key, T::Value);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(item) = self.pending.take() {
+            return Some(item);
+        }
+        if self.exhausted {
+            return None;
+        }
+
+        let item = self.cursor.next();
+        self.exhausted = item.is_none();
+        item
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::memory::MemoryEnvironment;
+
+    struct Names;
+
+    impl TableSpec for Names {
+        const NAME: &'static str = "names";
+        type Key = u32;
+        type Value = String;
+
+        fn flags() -> DatabaseFlags {
+            DatabaseFlags::DUPLICATE_KEYS | DatabaseFlags::UINT_KEYS
+        }
+    }
+
+    #[test]
+    fn it_can_scan_ranges() {
+        let env = MemoryEnvironment::new();
+        let table: Table<Names> = Table::open(&env);
+
+        let mut txn = WriteTransaction::new(&env);
+        table.put(&mut txn, &1, &"one".to_string());
+        table.put(&mut txn, &256, &"a".to_string());
+        table.put(&mut txn, &256, &"b".to_string());
+        table.put(&mut txn, &1000, &"c".to_string());
+        table.remove_item(&mut txn, &1000, &"c".to_string());
+        txn.commit().unwrap();
+
+        let txn = ReadTransaction::new(&env);
+        assert_eq!(table.get(&txn, &256), Some("a".to_string()));
+        assert_eq!(table.get_all(&txn, &256), vec!["a".to_string(), "b".to_string()]);
+        assert!(table.get_all(&txn, &1000).is_empty());
+        assert_eq!(table.iter(&txn).count(), 3);
+
+        let values: Vec<String> = table.range(&txn, &2).map(|(_, value)| value).collect();
+        assert_eq!(values, vec!["a".to_string(), "b".to_string()]);
+        assert_eq!(table.range(&txn, &257).next(), None);
+    }
+}
@@ -16,12 +16,15 @@ pub enum VolatileDatabaseError {
 }
 
 impl VolatileEnvironment {
+    /// Temporary environments only hold little data, so they grow in smaller steps than `LmdbEnvironment::DEFAULT_RESIZE_STEP`.
+    const RESIZE_STEP: usize = 1 << 24;
+
     pub fn new(max_dbs: u32) -> Result<Environment, VolatileDatabaseError> {
         let temp_dir = TempDir::new("volatile-core").map_err(|e| VolatileDatabaseError::IoError(e))?;
         let path = temp_dir.path().to_str().ok_or(VolatileDatabaseError::IoError(io::Error::new(io::ErrorKind::InvalidInput, "Path cannot be converted into a string.")))?.to_string();
         return Ok(Environment::Volatile(VolatileEnvironment {
             temp_dir,
-            env: LmdbEnvironment::new_lmdb_environment(&path, 0, max_dbs, lmdb_zero::open::NOSYNC | lmdb_zero::open::WRITEMAP).map_err(|e| VolatileDatabaseError::LmdbError(e))?,
+            env: LmdbEnvironment::new_lmdb_environment(&path, 0, max_dbs, lmdb_zero::open::NOSYNC | lmdb_zero::open::WRITEMAP, VolatileEnvironment::RESIZE_STEP).map_err(|e| VolatileDatabaseError::LmdbError(e))?,
         }));
     }
 
@@ -30,7 +33,7 @@ impl VolatileEnvironment {
         let path = temp_dir.path().to_str().ok_or(VolatileDatabaseError::IoError(io::Error::new(io::ErrorKind::InvalidInput, "Path cannot be converted into a string.")))?.to_string();
         return Ok(Environment::Volatile(VolatileEnvironment {
             temp_dir,
-            env: LmdbEnvironment::new_lmdb_environment(&path, 0, max_dbs, flags | lmdb_zero::open::NOSYNC | lmdb_zero::open::WRITEMAP).map_err(|e| VolatileDatabaseError::LmdbError(e))?,
+            env: LmdbEnvironment::new_lmdb_environment(&path, 0, max_dbs, flags | lmdb_zero::open::NOSYNC | lmdb_zero::open::WRITEMAP, VolatileEnvironment::RESIZE_STEP).map_err(|e| VolatileDatabaseError::LmdbError(e))?,
         }));
     }
 
@@ -38,6 +41,10 @@ impl VolatileEnvironment {
         return VolatileDatabase(self.env.open_database(name, flags));
     }
 
+    pub(in super) fn copy_to(&self, path: &str) -> io::Result<()> {
+        return self.env.copy_to(path);
+    }
+
     pub(in super) fn drop_database(self) -> io::Result<()> {
         return Ok(());
     }
@@ -96,7 +103,15 @@ impl<'env> VolatileWriteTransaction<'env> {
         self.0.remove_item(&db.0, key, value)
     }
 
-    pub(in super) fn commit(self) {
+    pub(in super) fn clear_database(&mut self, db: &VolatileDatabase) {
+        self.0.clear_database(&db.0)
+    }
+
+    pub(in super) fn failed(&self) -> bool {
+        self.0.failed()
+    }
+
+    pub(in super) fn commit(self) -> Result<(), WriteError> {
         self.0.commit()
     }
 
@@ -203,7 +218,7 @@ mod tests {
             // Overwrite and read value.
             tx.put_reserve(&db, "test", "two");
             assert_eq!(tx.get::<str, String>(&db, "test"), Some("two".to_string()));
-            tx.commit();
+            tx.commit().unwrap();
 
             // Read value.
             let tx = ReadTransaction::new(&env);
@@ -214,7 +229,7 @@ mod tests {
             let mut tx = WriteTransaction::new(&env);
             tx.remove(&db, "test");
             assert!(tx.get::<str, String>(&db, "test").is_none());
-            tx.commit();
+            tx.commit().unwrap();
 
             // Check removal.
             {
@@ -255,7 +270,7 @@ mod tests {
             assert!(tx.get::<str, String>(&db, "test").is_none());
 
             // Commit WriteTransaction.
-            txw.commit();
+            txw.commit().unwrap();
 
             // ReadTransaction should still have the old state.
             assert!(tx.get::<str, String>(&db, "test").is_none());
@@ -279,7 +294,7 @@ mod tests {
             assert!(txw.get::<str, u32>(&db, "test").is_none());
             txw.put::<str, u32>(&db, "test", &125);
             assert_eq!(txw.get::<str, u32>(&db, "test"), Some(125));
-            txw.commit();
+            txw.commit().unwrap();
 
             // Have a new ReadTransaction read the new state.
             {
@@ -292,7 +307,7 @@ mod tests {
             assert_eq!(txw.get::<str, u32>(&db, "test"), Some(125));
             txw.put::<str, u32>(&db, "test", &12);
             assert_eq!(txw.get::<str, u32>(&db, "test"), Some(12));
-            txw.commit();
+            txw.commit().unwrap();
 
             // Have a new ReadTransaction read the smaller value.
             {
@@ -306,7 +321,7 @@ mod tests {
             txw.remove_item::<str, u32>(&db, "test", &12);
             txw.put::<str, u32>(&db, "test", &5783);
             assert_eq!(txw.get::<str, u32>(&db, "test"), Some(125));
-            txw.commit();
+            txw.commit().unwrap();
 
             // Have a new ReadTransaction read the smallest value.
             {
@@ -319,7 +334,7 @@ mod tests {
             assert_eq!(txw.get::<str, u32>(&db, "test"), Some(125));
             txw.remove::<str>(&db, "test");
             assert!(txw.get::<str, u32>(&db, "test").is_none());
-            txw.commit();
+            txw.commit().unwrap();
 
             // Have a new ReadTransaction read the new state.
             {
@@ -347,7 +362,7 @@ mod tests {
             txw.put::<str, u32>(&db, "test1", &12);
             txw.put::<str, u32>(&db, "test1", &5783);
             txw.put::<str, u32>(&db, "test2", &5783);
-            txw.commit();
+            txw.commit().unwrap();
 
             // Have a new ReadTransaction read the new state.
             let tx = ReadTransaction::new(&env);
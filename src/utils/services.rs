@@ -7,6 +7,8 @@ bitflags! {
         const NANO  = 0b00000001;
         const LIGHT = 0b00000010;
         const FULL  = 0b00000100;
+        /// Set in addition to `FULL` by full nodes that only keep the bodies of recent blocks.
+        const PRUNED = 0b00001000;
     }
 }
 
@@ -22,6 +24,10 @@ impl ServiceFlags {
     pub fn is_nano_node(&self) -> bool {
         self.contains(ServiceFlags::NANO)
     }
+
+    pub fn is_pruned_node(&self) -> bool {
+        self.contains(ServiceFlags::PRUNED)
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -44,4 +50,12 @@ impl Services {
             accepted: ServiceFlags::FULL,
         }
     }
+
+    /// Validates everything like a full node, but can't serve old block bodies.
+    pub fn pruned() -> Self {
+        Services {
+            provided: ServiceFlags::FULL | ServiceFlags::PRUNED,
+            accepted: ServiceFlags::FULL,
+        }
+    }
 }
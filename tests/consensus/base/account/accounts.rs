@@ -6,7 +6,7 @@ use nimiq::consensus::base::transaction::{SignatureProof, Transaction};
 use nimiq::consensus::networks::NetworkId;
 use nimiq::consensus::policy;
 use nimiq::utils::db::volatile::VolatileEnvironment;
-use nimiq::utils::db::WriteTransaction;
+use nimiq::utils::db::{ReadTransaction, WriteTransaction};
 
 #[test]
 fn it_can_commit_and_revert_a_block_body() {
@@ -26,7 +26,7 @@ fn it_can_commit_and_revert_a_block_body() {
     {
         let mut txn = WriteTransaction::new(&env);
         assert!(accounts.commit_block_body(&mut txn, &body, 1).is_ok());
-        txn.commit();
+        txn.commit().unwrap();
     }
 
     assert_eq!(accounts.get(&address_miner, None).balance(), policy::block_reward_at(1));
@@ -46,7 +46,7 @@ fn it_can_commit_and_revert_a_block_body() {
     {
         let mut txn = WriteTransaction::new(&env);
         assert!(accounts.commit_block_body(&mut txn, &body, 2).is_ok());
-        txn.commit();
+        txn.commit().unwrap();
     }
 
     assert_eq!(accounts.get(&address_recipient, None).balance(), Coin::from(10));
@@ -57,7 +57,7 @@ fn it_can_commit_and_revert_a_block_body() {
     {
         let mut txn = WriteTransaction::new(&env);
         assert!(accounts.revert_block_body(&mut txn, &body, 2).is_ok());
-        txn.commit();
+        txn.commit().unwrap();
     }
 
     assert_eq!(accounts.get(&address_recipient, None).balance(), Coin::ZERO);
@@ -91,7 +91,7 @@ fn it_correctly_rewards_miners() {
     {
         let mut txn = WriteTransaction::new(&env);
         assert!(accounts.commit_block_body(&mut txn, &body, 1).is_ok());
-        txn.commit();
+        txn.commit().unwrap();
     }
 
     assert_eq!(accounts.get(&address_miner1, None).balance(), policy::block_reward_at(1));
@@ -125,7 +125,7 @@ fn it_correctly_rewards_miners() {
     {
         let mut txn = WriteTransaction::new(&env);
         assert!(accounts.commit_block_body(&mut txn, &body, 2).is_ok());
-        txn.commit();
+        txn.commit().unwrap();
     }
 
     assert_eq!(accounts.get(&address_miner1, None).balance(), policy::block_reward_at(1) - value1 - fee1 - value2 - fee2);
@@ -177,7 +177,7 @@ fn it_checks_for_sufficient_funds() {
     {
         let mut txn = WriteTransaction::new(&env);
         assert!(accounts.commit_block_body(&mut txn, &body, 1).is_ok());
-        txn.commit();
+        txn.commit().unwrap();
     }
 
     assert_eq!(accounts.get(&address_sender, None).balance(), policy::block_reward_at(1));
@@ -236,7 +236,7 @@ fn it_correctly_prunes_account() {
     {
         let mut txn = WriteTransaction::new(&env);
         assert!(accounts.commit_block_body(&mut txn, &body, 1).is_ok());
-        txn.commit();
+        txn.commit().unwrap();
     }
 
     // Create vesting contract
@@ -250,7 +250,7 @@ fn it_correctly_prunes_account() {
     {
         let mut txn = WriteTransaction::new(&env);
         assert!(accounts.commit_block_body(&mut txn, &body, 2).is_ok());
-        txn.commit();
+        txn.commit().unwrap();
     }
 
     // Create a block stub from it (for later use)
@@ -289,7 +289,7 @@ fn it_correctly_prunes_account() {
     {
         let mut txn = WriteTransaction::new(&env);
         assert!(accounts.commit_block_body(&mut txn, &body, 3).is_ok());
-        txn.commit();
+        txn.commit().unwrap();
     }
 
     // Check that the account was pruned correctly
@@ -301,7 +301,7 @@ fn it_correctly_prunes_account() {
     {
         let mut txn = WriteTransaction::new(&env);
         assert!(accounts.revert_block_body(&mut txn, &body, 3).is_ok());
-        txn.commit();
+        txn.commit().unwrap();
     }
 
     // Check that the account was recovered correctly
@@ -316,7 +316,7 @@ fn it_correctly_prunes_account() {
     {
         let mut txn = WriteTransaction::new(&env);
         assert!(accounts.revert_block(&mut txn, &block).is_ok());
-        txn.commit();
+        txn.commit().unwrap();
     }
 
     // Check that the account is really gone
@@ -324,3 +324,32 @@ fn it_correctly_prunes_account() {
     assert_eq!(account_after_prune.account_type(), AccountType::Basic);
     assert_eq!(account_after_prune.balance(), Coin::from(0));
 }
+
+#[test]
+fn it_can_aggregate_accounts() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let accounts = Accounts::new(&env);
+
+    let mut txn = WriteTransaction::new(&env);
+    for (i, miner) in [[1u8; Address::SIZE], [2u8; Address::SIZE], [3u8; Address::SIZE]].iter().enumerate() {
+        let body = BlockBody {
+            miner: Address::from(*miner),
+            extra_data: Vec::new(),
+            transactions: Vec::new(),
+            pruned_accounts: Vec::new()
+        };
+        assert!(accounts.commit_block_body(&mut txn, &body, i as u32 + 1).is_ok());
+    }
+    txn.commit().unwrap();
+
+    let txn = ReadTransaction::new(&env);
+    let total = policy::block_reward_at(1) + policy::block_reward_at(2) + policy::block_reward_at(3);
+    assert_eq!(accounts.total_balance(&txn), total);
+    assert_eq!(accounts.count_by_type(&txn).get(&AccountType::Basic), Some(&3));
+    assert_eq!(accounts.count_by_type(&txn).get(&AccountType::Vesting), None);
+
+    // Block rewards decrease, so the first miner has the highest balance.
+    let top: Vec<Address> = accounts.top_balances(&txn, 2).into_iter().map(|(address, _)| address).collect();
+    assert_eq!(top, vec![Address::from([1u8; Address::SIZE]), Address::from([2u8; Address::SIZE])]);
+    assert_eq!(accounts.top_balances(&txn, 0), vec![]);
+}
@@ -0,0 +1,85 @@
+use std::sync::Arc;
+
+use nimiq::consensus::base::account::{Accounts, AccountsStateError, AccountsStateHeader, export_accounts, import_accounts};
+use nimiq::consensus::base::blockchain::Blockchain;
+use nimiq::consensus::base::primitive::hash::Blake2bHash;
+use nimiq::consensus::networks::{NetworkId, get_network_info};
+use nimiq::network::NetworkTime;
+use nimiq::utils::db::volatile::VolatileEnvironment;
+use nimiq::utils::db::{ReadTransaction, WriteTransaction};
+
+fn genesis_state() -> Vec<u8> {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let accounts = Accounts::new(&env);
+    let mut txn = WriteTransaction::new(&env);
+    accounts.init(&mut txn, NetworkId::Main);
+    txn.commit().unwrap();
+
+    let genesis = get_network_info(NetworkId::Main).unwrap();
+    let header = AccountsStateHeader {
+        version: AccountsStateHeader::VERSION,
+        network_id: NetworkId::Main,
+        block_hash: genesis.genesis_hash.clone(),
+        block_height: 1,
+        accounts_hash: genesis.genesis_block.header.accounts_hash.clone(),
+    };
+
+    let mut data = Vec::new();
+    let txn = ReadTransaction::new(&env);
+    let num_accounts = export_accounts(&accounts, &txn, &header, &mut data).unwrap();
+    assert_eq!(num_accounts as usize, accounts.iter(&txn).count());
+    data
+}
+
+#[test]
+fn it_can_export_and_import_accounts() {
+    let data = genesis_state();
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let header = import_accounts(&env, NetworkId::Main, &mut &data[..]).unwrap();
+    assert_eq!(header.block_height, 1);
+    assert_eq!(Accounts::new(&env).hash(None), header.accounts_hash);
+}
+
+#[test]
+fn it_rejects_accounts_not_matching_the_header() {
+    let mut data = genesis_state();
+    // Drop the last byte of the last account.
+    data.pop();
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    match import_accounts(&env, NetworkId::Main, &mut &data[..]) {
+        Err(AccountsStateError::IoError(_)) | Err(AccountsStateError::SerializingError(_)) => {},
+        result => panic!("Unexpected result: {:?}", result),
+    }
+
+    // Corrupt the announced accounts hash.
+    let mut data = genesis_state();
+    let hash_offset = 2 + 1 + Blake2bHash::SIZE + 4;
+    data[hash_offset] ^= 0xff;
+    match import_accounts(&env, NetworkId::Main, &mut &data[..]) {
+        Err(AccountsStateError::HashMismatch(_)) => {},
+        result => panic!("Unexpected result: {:?}", result),
+    }
+    assert_eq!(Accounts::new(&env).iter(&ReadTransaction::new(&env)).count(), 0);
+}
+
+#[test]
+fn it_only_imports_accounts_of_the_chain_head() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap();
+    let header = import_accounts(&env, NetworkId::Main, &mut &genesis_state()[..]).unwrap();
+    assert_eq!(header.block_hash, blockchain.head_hash());
+
+    // Claim that the accounts belong to a different block.
+    let mut data = genesis_state();
+    let block_hash_offset = 2 + 1;
+    data[block_hash_offset] ^= 0xff;
+    match import_accounts(&env, NetworkId::Main, &mut &data[..]) {
+        Err(AccountsStateError::HeadMismatch(hash, height)) => {
+            assert_eq!(hash, blockchain.head_hash());
+            assert_eq!(height, 1);
+        },
+        result => panic!("Unexpected result: {:?}", result),
+    }
+}
@@ -1,4 +1,5 @@
 mod tree;
 mod accounts;
+mod export;
 mod htlc_contract;
 mod vesting_contract;
@@ -214,3 +214,117 @@ fn it_can_merge_nodes_while_pruning() {
 
     txn.abort();
 }
+
+#[test]
+fn it_iterates_over_accounts_in_address_order() {
+    let address1 = Address::from(&hex::decode("1200000000000000000000000000000000000000").unwrap()[..]);
+    let account1 = Account::Basic(BasicAccount { balance: 5.into() });
+    let address2 = Address::from(&hex::decode("0000000000000000000000000000000000000000").unwrap()[..]);
+    let account2 = Account::Basic(BasicAccount { balance: 55.into() });
+    let address3 = Address::from(&hex::decode("1000000000000000000000000000000000000000").unwrap()[..]);
+    let account3 = Account::Basic(BasicAccount { balance: 555.into() });
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let tree = AccountsTree::new(&env);
+    let mut txn = WriteTransaction::new(&env);
+    assert_eq!(tree.iter(&txn).count(), 0);
+
+    let mut batch = tree.batch();
+    batch.put(&txn, &address1, account1.clone());
+    batch.put(&txn, &address2, account2.clone());
+    batch.put(&txn, &address3, account3.clone());
+    batch.finalize(&mut txn);
+
+    let accounts: Vec<(Address, Account)> = tree.iter(&txn).collect();
+    assert_eq!(accounts, vec![(address2, account2), (address3, account3), (address1, account1)]);
+
+    txn.abort();
+}
+
+#[test]
+fn it_iterates_over_accounts_by_prefix() {
+    let address1 = Address::from(&hex::decode("1200000000000000000000000000000000000000").unwrap()[..]);
+    let address2 = Address::from(&hex::decode("1234000000000000000000000000000000000000").unwrap()[..]);
+    let address3 = Address::from(&hex::decode("1300000000000000000000000000000000000000").unwrap()[..]);
+    let account = Account::Basic(BasicAccount { balance: 5.into() });
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let tree = AccountsTree::new(&env);
+    let mut txn = WriteTransaction::new(&env);
+    let mut batch = tree.batch();
+    batch.put(&txn, &address1, account.clone());
+    batch.put(&txn, &address2, account.clone());
+    batch.put(&txn, &address3, account.clone());
+    batch.finalize(&mut txn);
+
+    let addresses = |prefix: &[u8]| -> Vec<Address> { tree.iter_prefix(&txn, prefix).map(|(address, _)| address).collect() };
+    assert_eq!(addresses(&[]), vec![address1.clone(), address2.clone(), address3.clone()]);
+    assert_eq!(addresses(&[0x12]), vec![address1.clone(), address2.clone()]);
+    assert_eq!(addresses(&[0x12, 0x34]), vec![address2.clone()]);
+    assert_eq!(addresses(&[0x13]), vec![address3.clone()]);
+    assert_eq!(addresses(&[0x14]), Vec::<Address>::new());
+    assert_eq!(addresses(address1.as_bytes()), vec![address1.clone()]);
+}
+
+#[test]
+fn it_computes_the_same_hash_for_large_batches() {
+    let addresses: Vec<Address> = (0..600u32).map(|i| {
+        let mut bytes = [0u8; Address::SIZE];
+        bytes[0] = (i * 37) as u8;
+        bytes[1] = (i >> 3) as u8;
+        bytes[19] = i as u8;
+        Address::from(bytes)
+    }).collect();
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let tree = AccountsTree::new(&env);
+
+    // Put each account individually.
+    let mut txn = WriteTransaction::new(&env);
+    for (i, address) in addresses.iter().enumerate() {
+        tree.put(&mut txn, address, Account::Basic(BasicAccount { balance: (i as u64 + 1).into() }));
+    }
+    for address in addresses.iter().step_by(3) {
+        tree.put(&mut txn, address, Account::INITIAL);
+    }
+    let expected_hash = tree.root_hash(&txn);
+    txn.abort();
+
+    // Put all accounts in a single batch, pruning some of them before finalizing.
+    let mut txn = WriteTransaction::new(&env);
+    let mut batch = tree.batch();
+    for (i, address) in addresses.iter().enumerate() {
+        batch.put(&txn, address, Account::Basic(BasicAccount { balance: (i as u64 + 1).into() }));
+    }
+    for address in addresses.iter().step_by(3) {
+        batch.put(&txn, address, Account::INITIAL);
+    }
+    assert!(batch.get(&txn, &addresses[0]).is_none());
+    assert_eq!(batch.get(&txn, &addresses[1]), Some(Account::Basic(BasicAccount { balance: 2.into() })));
+    // Staged changes are only visible through the batch.
+    assert!(tree.get(&txn, &addresses[1]).is_none());
+    batch.finalize(&mut txn);
+    assert_eq!(tree.root_hash(&txn), expected_hash);
+    txn.abort();
+}
+
+#[test]
+fn it_can_discard_a_batch() {
+    let address = Address::from(&hex::decode("0000000000000000000000000000000000000000").unwrap()[..]);
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let tree = AccountsTree::new(&env);
+    let mut txn = WriteTransaction::new(&env);
+    let empty_hash = tree.root_hash(&txn);
+
+    {
+        let mut batch = tree.batch();
+        batch.put(&txn, &address, Account::Basic(BasicAccount { balance: 5.into() }));
+        assert!(batch.get(&txn, &address).is_some());
+        // Dropping the batch discards its changes.
+    }
+
+    assert!(tree.get(&txn, &address).is_none());
+    assert_eq!(tree.root_hash(&txn), empty_hash);
+    txn.abort();
+}
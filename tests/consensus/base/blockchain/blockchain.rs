@@ -3,17 +3,18 @@ use beserial::{Deserialize, Serialize};
 use std::sync::Arc;
 use nimiq::consensus::base::account::{AccountType, AccountError};
 use nimiq::consensus::base::block::{Block, BlockError};
-use nimiq::consensus::base::blockchain::{Blockchain, BlockchainEvent, PushResult, PushError};
+use nimiq::consensus::base::blockchain::{AccountHistoryError, Blockchain, BlockchainEvent, PushResult, PushError, BootstrapError, BootstrapHeader, ImportProgress, import_blocks};
 use nimiq::consensus::base::primitive::Address;
 use nimiq::consensus::base::primitive::crypto::{KeyPair, PrivateKey};
 use nimiq::consensus::base::primitive::hash::Hash;
 use nimiq::consensus::base::transaction::{Transaction, SignatureProof};
 use nimiq::consensus::networks::NetworkId;
 use nimiq::network::NetworkTime;
+use nimiq::utils::db::memory::MemoryEnvironment;
 use nimiq::utils::db::volatile::VolatileEnvironment;
 
 
-const BLOCK_2: &str = "0001264aaf8a4f9828a76c550635da078eb466306a189fcc03710bee9f649c869d120492e3986e75ac0d1466b5d6a7694c86839767a30980f8ba0d8c6e48631bc9cdd8a3eb957567d76963ad10d11e65453f763928fb9619e5f396a0906e946cce3ca7fcbb5fb2e35055de071e868381ba426a8d79d97cb48dab8345baeb9a9abb091f010000000000025ad23a98000046fe0180010000000000000000000000000000000000000000184d696e65642077697468206c6f766520627920526963687900000000";
+pub const BLOCK_2: &str = "0001264aaf8a4f9828a76c550635da078eb466306a189fcc03710bee9f649c869d120492e3986e75ac0d1466b5d6a7694c86839767a30980f8ba0d8c6e48631bc9cdd8a3eb957567d76963ad10d11e65453f763928fb9619e5f396a0906e946cce3ca7fcbb5fb2e35055de071e868381ba426a8d79d97cb48dab8345baeb9a9abb091f010000000000025ad23a98000046fe0180010000000000000000000000000000000000000000184d696e65642077697468206c6f766520627920526963687900000000";
 const BLOCK_3: &str = "0001bab534467866d83060b1af0b3493dd0f97d7071b16e1562cf4b18bdf73e71ccb4aa1fea2b8cdf2a63411776c6391a7659aef4dd25317a615499c7b461e9a0405385dbed68e76f74317cc6f4cd40db832eb71b8338fad024ddbb88f9abc79f199dd6a3500aeb5479eb460afeab3363783e243a6e551536c3c01c8fca21d7afbbb1f00fddd000000035ad23a980000968102c0010000000000000000000000000000000000000000184d696e65642077697468206c6f76652062792054616d6d6f00000000";
 const BLOCK_4: &str = "0001622b0536bbe764a5723f17cde03d2fa2b67a3f42f7cab082c72222eb1e48db7a607f7686d7636b500cfa620567ede30a15a12f69e22d35dd004bbdbfcaefc12520428a900c8dfb339b99aebb1d14cc4d5cebedf562aa1806f272deecbf3c5263b62534d1cda41d1a7bf70a6850c6c82936adb9b2ef66b7421ca3c55664c1417f1f00fbb7000000045ad23a9800022dc60280bab534467866d83060b1af0b3493dd0f97d7071b16e1562cf4b18bdf73e71ccb0100000000000000000000000000000000000000001b4d696e65642077697468206c6f7665206279204372697374696e6100000000";
 const BLOCK_5: &str = "000184d5a44ba5ae9961837e7fb19c176a19f77b2e0655873149017351e17b622cef4aa1fea2b8cdf2a63411776c6391a7659aef4dd25317a615499c7b461e9a0405b32082f43aae5c61bf1171e85650b550bcc2b8d020365619ecaeb924c4562770cbadc05e0c4117bf975bc3d7e55d2f3a13efe1a9baf17c0b2c3c42faee9414b31f00f98c000000055ad23a9800013f5602c0010000000000000000000000000000000000000000174d696e65642077697468206c6f7665206279204174756100000000";
@@ -27,12 +28,30 @@ fn it_can_load_a_stored_chain() {
     let hash = block.header.hash();
 
     {
-        let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())));
+        let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap());
         let status = blockchain.push(block);
         assert_eq!(status, PushResult::Extended);
     }
 
-    let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())));
+    let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap());
+    assert_eq!(blockchain.height(), 2);
+    assert_eq!(blockchain.head_hash(), hash);
+}
+
+#[test]
+fn it_can_load_a_stored_chain_from_memory() {
+    crate::setup();
+
+    let env = MemoryEnvironment::new();
+    let block = Block::deserialize_from_vec(&hex::decode(BLOCK_2).unwrap()).unwrap();
+    let hash = block.header.hash();
+
+    {
+        let blockchain = Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap();
+        assert_eq!(blockchain.push(block), PushResult::Extended);
+    }
+
+    let blockchain = Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap();
     assert_eq!(blockchain.height(), 2);
     assert_eq!(blockchain.head_hash(), hash);
 }
@@ -42,7 +61,7 @@ fn it_can_extend_the_main_chain() {
     crate::setup();
 
     let env = VolatileEnvironment::new(10).unwrap();
-    let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())));
+    let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap());
 
     let mut block = Block::deserialize_from_vec(&hex::decode(BLOCK_2).unwrap()).unwrap();
     let mut status = blockchain.push(block);
@@ -66,7 +85,7 @@ fn it_detects_known_blocks() {
     crate::setup();
 
     let env = VolatileEnvironment::new(10).unwrap();
-    let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())));
+    let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap());
 
     let block = Block::deserialize_from_vec(&hex::decode(BLOCK_2).unwrap()).unwrap();
     let mut status = blockchain.push(block.clone());
@@ -81,7 +100,7 @@ fn it_rejects_orphan_blocks() {
     crate::setup();
 
     let env = VolatileEnvironment::new(10).unwrap();
-    let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())));
+    let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap());
 
     let block = Block::deserialize_from_vec(&hex::decode(BLOCK_3).unwrap()).unwrap();
     let status = blockchain.push(block);
@@ -93,7 +112,7 @@ fn it_rejects_intrisically_invalid_blocks() {
     crate::setup();
 
     let env = VolatileEnvironment::new(10).unwrap();
-    let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())));
+    let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap());
 
     let mut block = Block::deserialize_from_vec(&hex::decode(BLOCK_2).unwrap()).unwrap();
     block.header.nonce = 1;
@@ -106,7 +125,7 @@ fn it_rejects_invalid_successors() {
     crate::setup();
 
     let env = VolatileEnvironment::new(10).unwrap();
-    let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())));
+    let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap());
 
     let mut block = Block::deserialize_from_vec(&hex::decode(BLOCK_2).unwrap()).unwrap();
     block.header.timestamp = 5000;
@@ -121,7 +140,7 @@ fn it_rejects_blocks_with_invalid_difficulty() {
     crate::setup();
 
     let env = VolatileEnvironment::new(10).unwrap();
-    let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())));
+    let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap());
 
     let mut block = Block::deserialize_from_vec(&hex::decode(BLOCK_2).unwrap()).unwrap();
     block.header.n_bits = 0x1f051234.into();
@@ -138,7 +157,7 @@ fn it_rejects_blocks_with_duplicate_transactions() {
     let keypair: KeyPair = PrivateKey::from([1u8; PrivateKey::SIZE]).into();
 
     let env = VolatileEnvironment::new(10).unwrap();
-    let blockchain = Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new()));
+    let blockchain = Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap();
 
     let miner = Address::from(&keypair.public);
     let block2 = crate::next_block(&blockchain)
@@ -183,7 +202,7 @@ fn it_rejects_blocks_if_body_cannot_be_applied() {
     let keypair: KeyPair = PrivateKey::from([1u8; PrivateKey::SIZE]).into();
 
     let env = VolatileEnvironment::new(10).unwrap();
-    let blockchain = Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new()));
+    let blockchain = Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap();
 
     let miner = Address::from(&keypair.public);
     let block2 = crate::next_block(&blockchain)
@@ -235,7 +254,7 @@ fn it_rejects_blocks_if_body_cannot_be_applied() {
 #[test]
 fn it_detects_fork_blocks() {
     let env = VolatileEnvironment::new(10).unwrap();
-    let blockchain = Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new()));
+    let blockchain = Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap();
 
     let mut block = crate::next_block(&blockchain)
         .with_nonce(83054)
@@ -251,7 +270,7 @@ fn it_rebranches_to_the_harder_chain() {
     crate::setup();
 
     let env = VolatileEnvironment::new(10).unwrap();
-    let blockchain = Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new()));
+    let blockchain = Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap();
 
     let block1_2 = crate::next_block(&blockchain)
         .with_nonce(83054)
@@ -289,3 +308,142 @@ fn it_rebranches_to_the_harder_chain() {
     assert_eq!(blockchain.push(block2_4), PushResult::Rebranched);
     assert!(listener_called.load(Ordering::Relaxed));
 }
+
+#[test]
+fn it_prunes_stale_forks() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap();
+
+    let block1_2 = crate::next_block(&blockchain)
+        .with_nonce(83054)
+        .build();
+    assert_eq!(blockchain.push(block1_2.clone()), PushResult::Extended);
+
+    let block1_3 = crate::next_block(&blockchain)
+        .with_nonce(23192)
+        .build();
+    assert_eq!(blockchain.push(block1_3.clone()), PushResult::Extended);
+
+    let block1_4 = crate::next_block(&blockchain)
+        .with_nonce(39719)
+        .build();
+    assert_eq!(blockchain.push(block1_4.clone()), PushResult::Extended);
+
+    let block2_2 = Block::deserialize_from_vec(&hex::decode(BLOCK_2).unwrap()).unwrap();
+    assert_eq!(blockchain.push(block2_2.clone()), PushResult::Forked);
+    let block2_3 = Block::deserialize_from_vec(&hex::decode(BLOCK_3).unwrap()).unwrap();
+    assert_eq!(blockchain.push(block2_3.clone()), PushResult::Forked);
+    let block2_4 = Block::deserialize_from_vec(&hex::decode(BLOCK_4).unwrap()).unwrap();
+    assert_eq!(blockchain.push(block2_4.clone()), PushResult::Rebranched);
+    let block2_5 = Block::deserialize_from_vec(&hex::decode(BLOCK_5).unwrap()).unwrap();
+    assert_eq!(blockchain.push(block2_5.clone()), PushResult::Extended);
+
+    // Nothing is behind far enough yet.
+    assert_eq!(blockchain.prune_forks(4), 0);
+    assert!(blockchain.contains(&block1_2.header.hash(), true));
+
+    // The fork is cut off at height 2, the blocks building on it are removed as well.
+    assert_eq!(blockchain.prune_forks(3), 3);
+    assert!(!blockchain.contains(&block1_2.header.hash(), true));
+    assert!(!blockchain.contains(&block1_3.header.hash(), true));
+    assert!(!blockchain.contains(&block1_4.header.hash(), true));
+    assert!(blockchain.contains(&block2_2.header.hash(), false));
+    assert!(blockchain.contains(&block2_5.header.hash(), false));
+    assert_eq!(blockchain.height(), 5);
+
+    // A fork block that arrives below the watermark is pruned by the next run.
+    assert_eq!(blockchain.push(block1_2.clone()), PushResult::Forked);
+    assert_eq!(blockchain.prune_forks(3), 1);
+    assert!(!blockchain.contains(&block1_2.header.hash(), true));
+
+    // The watermark survives a restart.
+    drop(blockchain);
+    let blockchain = Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap();
+    assert_eq!(blockchain.prune_forks(3), 0);
+    assert_eq!(blockchain.push(block1_2.clone()), PushResult::Forked);
+    assert_eq!(blockchain.prune_forks(3), 1);
+}
+
+#[test]
+fn it_can_export_and_import_a_bootstrap_file() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap();
+    for block in [BLOCK_2, BLOCK_3, BLOCK_4, BLOCK_5].iter() {
+        let block = Block::deserialize_from_vec(&hex::decode(block).unwrap()).unwrap();
+        assert_eq!(blockchain.push(block), PushResult::Extended);
+    }
+
+    let mut file = Vec::new();
+    assert_eq!(blockchain.export_range(1, 5, &mut file).unwrap(), 5);
+    assert!(blockchain.export_range(1, 6, &mut Vec::new()).is_err());
+
+    let env2 = VolatileEnvironment::new(10).unwrap();
+    let blockchain2 = Blockchain::new(&env2, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap();
+    let mut heights = Vec::new();
+    let progress = import_blocks(&blockchain2, &mut &file[..], |progress: &ImportProgress| heights.push(progress.height)).unwrap();
+
+    assert_eq!(progress.num_blocks, 5);
+    assert_eq!(progress.num_known, 1); // Genesis block
+    assert_eq!(heights, vec![1, 2, 3, 4, 5]);
+    assert_eq!(blockchain2.head_hash(), blockchain.head_hash());
+
+    // Bootstrap files of other networks are rejected.
+    let env3 = VolatileEnvironment::new(10).unwrap();
+    let blockchain3 = Blockchain::new(&env3, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap();
+    let mut other_file = BootstrapHeader::new(NetworkId::Test).serialize_to_vec();
+    other_file.extend_from_slice(&file[3..]);
+    match import_blocks(&blockchain3, &mut &other_file[..], |_| {}) {
+        Err(BootstrapError::NetworkMismatch(NetworkId::Test)) => {},
+        result => panic!("Unexpected result {:?}", result),
+    }
+
+    // Blocks larger than the maximum block size are rejected before reading them.
+    let mut oversized_file = BootstrapHeader::new(NetworkId::Main).serialize_to_vec();
+    oversized_file.extend_from_slice(&[0xff; 4]);
+    match import_blocks(&blockchain3, &mut &oversized_file[..], |_| {}) {
+        Err(BootstrapError::BlockTooLarge(0xffffffff)) => {},
+        result => panic!("Unexpected result {:?}", result),
+    }
+}
+
+#[test]
+fn it_can_get_accounts_at_past_heights() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap();
+
+    let block = Block::deserialize_from_vec(&hex::decode(BLOCK_2).unwrap()).unwrap();
+    let miner = block.body.as_ref().unwrap().miner.clone();
+    let genesis_account = blockchain.accounts().get(&miner, None);
+
+    assert_eq!(blockchain.push(block), PushResult::Extended);
+    let head_account = blockchain.accounts().get(&miner, None);
+    assert_ne!(genesis_account, head_account);
+
+    assert_eq!(blockchain.get_account_at(&miner, 1), Ok(genesis_account));
+    assert_eq!(blockchain.get_account_at(&miner, 2), Ok(head_account.clone()));
+    assert_eq!(blockchain.get_account_at(&miner, 3), Err(AccountHistoryError::AboveHead(3)));
+
+    // The query must not change the current state.
+    assert_eq!(blockchain.accounts().get(&miner, None), head_account);
+    assert_eq!(blockchain.height(), 2);
+}
+
+#[test]
+fn it_keeps_the_supply_difference_constant() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap();
+    let genesis_audit = blockchain.audit_supply();
+    assert_eq!(genesis_audit.block_height, 1);
+    assert!(genesis_audit.is_consistent(), "{}", genesis_audit);
+
+    for block in [BLOCK_2, BLOCK_3, BLOCK_4, BLOCK_5].iter() {
+        let block = Block::deserialize_from_vec(&hex::decode(block).unwrap()).unwrap();
+        assert_eq!(blockchain.push(block), PushResult::Extended);
+    }
+
+    // Block rewards are minted exactly as the policy says.
+    let audit = blockchain.audit_supply();
+    assert_eq!(audit.block_height, 5);
+    assert!(audit.is_consistent(), "{}", audit);
+    assert_eq!(audit.difference(), 0);
+}
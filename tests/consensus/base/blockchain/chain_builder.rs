@@ -0,0 +1,104 @@
+use beserial::Serialize;
+use std::sync::Arc;
+use nimiq::consensus::base::account::Account;
+use nimiq::consensus::base::blockchain::{Blockchain, ChainBuilder, Defect, PushError, PushResult};
+use nimiq::consensus::base::primitive::{Address, Coin};
+use nimiq::consensus::base::primitive::crypto::KeyPair;
+use nimiq::consensus::base::primitive::hash::{Blake2bHash, Hash};
+use nimiq::consensus::base::transaction::{SignatureProof, Transaction};
+use nimiq::consensus::networks::NetworkId;
+use nimiq::consensus::policy;
+use nimiq::network::NetworkTime;
+use nimiq::utils::db::volatile::VolatileEnvironment;
+
+fn signed_transaction(keypair: &KeyPair, value: u64, validity_start_height: u32) -> Transaction {
+    let mut tx = Transaction::new_basic(Address::from(&keypair.public), Address::from([2u8; Address::SIZE]), Coin::from(value), Coin::ZERO, validity_start_height, NetworkId::Dummy);
+    let signature_proof = SignatureProof::from(keypair.public.clone(), keypair.sign(&tx.serialize_content()));
+    tx.proof = signature_proof.serialize_to_vec();
+    tx
+}
+
+#[test]
+fn it_builds_blocks_with_transactions() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = Blockchain::new(&env, NetworkId::Dummy, Arc::new(NetworkTime::new())).unwrap();
+    let builder = ChainBuilder::new(&blockchain);
+
+    let keypair = KeyPair::generate();
+    let address = Address::from(&keypair.public);
+    let block = builder.next_block().with_miner(address.clone()).build();
+    assert_eq!(blockchain.push(block), PushResult::Extended);
+    let reward = u64::from(policy::block_reward_at(2));
+
+    let block = builder.next_block()
+        .with_transactions(vec![signed_transaction(&keypair, 100, 2), signed_transaction(&keypair, 200, 2)])
+        .build();
+    assert_eq!(blockchain.push(block), PushResult::Extended);
+
+    assert_eq!(blockchain.height(), 3);
+    assert_eq!(blockchain.accounts().get(&address, None), Account::new_basic(Coin::from(reward - 300)));
+    assert_eq!(blockchain.accounts().get(&Address::from([2u8; Address::SIZE]), None), Account::new_basic(Coin::from(300)));
+}
+
+#[test]
+fn it_builds_forks_from_any_ancestor() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = Blockchain::new(&env, NetworkId::Dummy, Arc::new(NetworkTime::new())).unwrap();
+    let builder = ChainBuilder::new(&blockchain);
+
+    let main_chain = builder.extend(3);
+    assert_eq!(blockchain.height(), 4);
+
+    // Fork off the first block, the fork becomes harder with its third block.
+    let fork = builder.chain_on(&main_chain[0].header.hash(), 3);
+    assert_eq!(blockchain.push(fork[0].clone()), PushResult::Forked);
+    assert_eq!(blockchain.push(fork[1].clone()), PushResult::Forked);
+    assert_eq!(blockchain.push(fork[2].clone()), PushResult::Rebranched);
+    assert_eq!(blockchain.head_hash(), fork[2].header.hash::<Blake2bHash>());
+    assert_eq!(blockchain.height(), 5);
+
+    // The former main chain can be extended as well.
+    let old_head: Blake2bHash = main_chain[2].header.hash();
+    let block = builder.block_on(&old_head).build();
+    assert_eq!(blockchain.push(block), PushResult::Forked);
+    assert_eq!(builder.extend(1).len(), 1);
+}
+
+#[test]
+fn it_builds_each_kind_of_invalid_block() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = Blockchain::new(&env, NetworkId::Dummy, Arc::new(NetworkTime::new())).unwrap();
+    let builder = ChainBuilder::new(&blockchain);
+
+    // Include a transaction, so that there is one to replay.
+    let keypair = KeyPair::generate();
+    let block = builder.next_block().with_miner(Address::from(&keypair.public)).build();
+    assert_eq!(blockchain.push(block), PushResult::Extended);
+    let block = builder.next_block().with_transactions(vec![signed_transaction(&keypair, 100, 2)]).build();
+    assert_eq!(blockchain.push(block), PushResult::Extended);
+
+    let head_hash = blockchain.head_hash();
+    for defect in Defect::ALL.iter() {
+        let block = builder.next_block().build_invalid(*defect);
+        assert_eq!(blockchain.push(block), PushResult::Invalid(defect.expected_error()), "{:?}", defect);
+        assert_eq!(blockchain.head_hash(), head_hash);
+    }
+}
+
+#[test]
+fn it_builds_invalid_forks() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = Blockchain::new(&env, NetworkId::Dummy, Arc::new(NetworkTime::new())).unwrap();
+    let builder = ChainBuilder::new(&blockchain);
+
+    let genesis_hash = blockchain.head_hash();
+    builder.extend(2);
+
+    // Bodies of fork blocks are only applied when rebranching.
+    let invalid = builder.block_on(&genesis_hash).build_invalid(Defect::AccountsError);
+    assert_eq!(blockchain.push(invalid.clone()), PushResult::Forked);
+    let fork = builder.chain_on(&invalid.header.hash(), 2);
+    assert_eq!(blockchain.push(fork[0].clone()), PushResult::Forked);
+    assert_eq!(blockchain.push(fork[1].clone()), PushResult::Invalid(PushError::InvalidFork));
+    assert_eq!(blockchain.height(), 3);
+}
@@ -13,7 +13,7 @@ fn it_can_store_the_chain_head() {
     let mut txn = WriteTransaction::new(&env);
     let head = Blake2bHash::from([1u8; Blake2bHash::SIZE]);
     store.set_head(&mut txn, &head);
-    txn.commit();
+    txn.commit().unwrap();
 
     assert_eq!(store.get_head(None).unwrap(), head);
 }
@@ -29,7 +29,7 @@ fn it_can_store_chain_info_with_body() {
 
     let mut txn = WriteTransaction::new(&env);
     store.put_chain_info(&mut txn, &genesis_hash, &chain_info, true);
-    txn.commit();
+    txn.commit().unwrap();
 
     let mut chain_info_no_body = chain_info.clone();
     chain_info_no_body.head.body = None;
@@ -49,7 +49,7 @@ fn it_can_store_chain_info_without_body() {
 
     let mut txn = WriteTransaction::new(&env);
     store.put_chain_info(&mut txn, &genesis_hash, &chain_info, false);
-    txn.commit();
+    txn.commit().unwrap();
 
     let mut chain_info_no_body = chain_info.clone();
     chain_info_no_body.head.body = None;
@@ -93,7 +93,7 @@ fn it_can_retrieve_chain_info_by_height() {
     store.put_chain_info(&mut txn, &hash2, &info2, false);
     store.put_chain_info(&mut txn, &hash3_1, &info3_1, true);
     store.put_chain_info(&mut txn, &hash3_2, &info3_2, true);
-    txn.commit();
+    txn.commit().unwrap();
 
     let mut info2_no_body = info2.clone();
     info2_no_body.head.body = None;
@@ -125,7 +125,7 @@ fn it_can_get_blocks_backward() {
         store.put_chain_info(&mut txn, &hash, &ChainInfo::initial(b.clone()), true);
         block = b;
     }
-    txn.commit();
+    txn.commit().unwrap();
 
     let mut blocks = store.get_blocks_backward(&block.header.prev_hash, 10, true, None);
     assert_eq!(blocks.len(), 10);
@@ -200,7 +200,7 @@ fn it_can_get_blocks_forward() {
         let hash = chain_info.head.header.hash();
         store.put_chain_info(&mut txn, &hash, chain_info, true);
     }
-    txn.commit();
+    txn.commit().unwrap();
 
     let second_block_hash = chain_infos.first().unwrap().main_chain_successor.as_ref().unwrap();
 
@@ -250,3 +250,90 @@ fn it_can_get_blocks_forward() {
     blocks = store.get_blocks_forward(&chain_infos[20].head.header.hash(), 20, false, None);
     assert_eq!(blocks.len(), 0);
 }
+
+#[test]
+fn it_can_prune_bodies() {
+    let env = VolatileEnvironment::new(3).unwrap();
+    let store = ChainStore::new(&env);
+
+    let mut txn = WriteTransaction::new(&env);
+    let mut block = get_network_info(NetworkId::Main).unwrap().genesis_block.clone();
+    let mut hashes = vec![block.header.hash::<Blake2bHash>()];
+    store.put_chain_info(&mut txn, &hashes[0], &ChainInfo::initial(block.clone()), true);
+
+    for _ in 0..9 {
+        let mut b = block.clone();
+        b.header.prev_hash = block.header.hash();
+        b.header.height = block.header.height + 1;
+        let hash = b.header.hash::<Blake2bHash>();
+        store.put_chain_info(&mut txn, &hash, &ChainInfo::initial(b.clone()), true);
+        hashes.push(hash);
+        block = b;
+    }
+    txn.commit().unwrap();
+    assert_eq!(store.get_pruned_height(None), 0);
+
+    let mut txn = WriteTransaction::new(&env);
+    assert_eq!(store.prune_bodies(&mut txn, 4), 4);
+    txn.commit().unwrap();
+
+    assert_eq!(store.get_pruned_height(None), 4);
+    assert!(store.get_block(&hashes[3], true, None).is_none());
+    assert!(store.get_block(&hashes[3], false, None).is_some());
+    assert!(store.get_block(&hashes[4], true, None).is_some());
+
+    // Pruning is incremental.
+    let mut txn = WriteTransaction::new(&env);
+    assert_eq!(store.prune_bodies(&mut txn, 3), 0);
+    assert_eq!(store.prune_bodies(&mut txn, 6), 2);
+    txn.commit().unwrap();
+    assert_eq!(store.get_pruned_height(None), 6);
+    assert!(store.get_block(&hashes[5], true, None).is_none());
+}
+
+#[test]
+fn it_can_remove_chain_info() {
+    let env = VolatileEnvironment::new(3).unwrap();
+    let store = ChainStore::new(&env);
+
+    let block1 = get_network_info(NetworkId::Main).unwrap().genesis_block.clone();
+    let mut block2 = block1.clone();
+    block2.header.interlink_hash = [2u8; Blake2bHash::SIZE].into();
+    let hash1 = block1.header.hash::<Blake2bHash>();
+    let hash2 = block2.header.hash::<Blake2bHash>();
+    let mut info2 = ChainInfo::initial(block2);
+    info2.on_main_chain = false;
+
+    let mut txn = WriteTransaction::new(&env);
+    store.put_chain_info(&mut txn, &hash1, &ChainInfo::initial(block1), true);
+    store.put_chain_info(&mut txn, &hash2, &info2, true);
+    txn.commit().unwrap();
+    assert_eq!(store.get_block_hashes_at(1, None).len(), 2);
+
+    let mut txn = WriteTransaction::new(&env);
+    store.remove_chain_info(&mut txn, &hash2, 1);
+    txn.commit().unwrap();
+
+    assert_eq!(store.get_block_hashes_at(1, None), vec![hash1.clone()]);
+    assert!(store.get_chain_info(&hash2, false, None).is_none());
+    assert!(store.get_block(&hash2, true, None).is_none());
+    assert!(store.get_chain_info(&hash1, true, None).is_some());
+}
+
+#[test]
+fn it_lowers_the_fork_pruning_height_when_touched() {
+    let env = VolatileEnvironment::new(3).unwrap();
+    let store = ChainStore::new(&env);
+    assert_eq!(store.get_fork_pruning_height(None), 0);
+
+    let mut txn = WriteTransaction::new(&env);
+    store.set_fork_pruning_height(&mut txn, 10);
+    store.touch_fork_height(&mut txn, 11);
+    txn.commit().unwrap();
+    assert_eq!(store.get_fork_pruning_height(None), 10);
+
+    let mut txn = WriteTransaction::new(&env);
+    store.touch_fork_height(&mut txn, 7);
+    txn.commit().unwrap();
+    assert_eq!(store.get_fork_pruning_height(None), 6);
+}
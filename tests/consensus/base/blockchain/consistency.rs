@@ -0,0 +1,83 @@
+use beserial::Deserialize;
+use std::sync::Arc;
+use nimiq::consensus::base::account::Accounts;
+use nimiq::consensus::base::block::Block;
+use nimiq::consensus::base::blockchain::{Blockchain, BlockchainError, ChainStore, Inconsistency, PushResult, check_consistency, repair};
+use nimiq::consensus::base::primitive::hash::Hash;
+use nimiq::consensus::networks::NetworkId;
+use nimiq::network::NetworkTime;
+use nimiq::utils::db::WriteTransaction;
+use nimiq::utils::db::volatile::VolatileEnvironment;
+
+use super::blockchain::BLOCK_2;
+
+#[test]
+fn it_finds_no_inconsistencies_in_a_valid_chain() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let block = Block::deserialize_from_vec(&hex::decode(BLOCK_2).unwrap()).unwrap();
+    {
+        let blockchain = Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap();
+        assert_eq!(blockchain.push(block), PushResult::Extended);
+    }
+
+    let report = check_consistency(&env, NetworkId::Main);
+    assert!(report.is_consistent());
+    assert_eq!(report.head_height, 2);
+}
+
+#[test]
+fn it_can_rebuild_the_accounts_tree() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let block = Block::deserialize_from_vec(&hex::decode(BLOCK_2).unwrap()).unwrap();
+    {
+        let blockchain = Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap();
+        assert_eq!(blockchain.push(block), PushResult::Extended);
+    }
+
+    // Wipe the accounts tree.
+    {
+        let accounts = Accounts::new(&env);
+        let mut txn = WriteTransaction::new(&env);
+        accounts.clear(&mut txn);
+        txn.commit().unwrap();
+    }
+    assert_eq!(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).err(), Some(BlockchainError::InconsistentState));
+
+    let report = check_consistency(&env, NetworkId::Main);
+    assert_eq!(report.inconsistencies, vec![Inconsistency::AccountsHashMismatch]);
+
+    let report = repair(&env, NetworkId::Main).unwrap();
+    assert!(report.is_consistent());
+
+    let blockchain = Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap();
+    assert_eq!(blockchain.height(), 2);
+}
+
+#[test]
+fn it_can_repair_main_chain_flags() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let block = Block::deserialize_from_vec(&hex::decode(BLOCK_2).unwrap()).unwrap();
+    let hash = block.header.hash();
+    {
+        let blockchain = Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap();
+        assert_eq!(blockchain.push(block), PushResult::Extended);
+    }
+
+    // Drop the main chain flag of the head.
+    {
+        let chain_store = ChainStore::new(&env);
+        let mut chain_info = chain_store.get_chain_info(&hash, false, None).unwrap();
+        chain_info.on_main_chain = false;
+        let mut txn = WriteTransaction::new(&env);
+        chain_store.put_chain_info(&mut txn, &hash, &chain_info, false);
+        txn.commit().unwrap();
+    }
+
+    let report = check_consistency(&env, NetworkId::Main);
+    assert_eq!(report.inconsistencies, vec![Inconsistency::NotOnMainChain(2)]);
+    assert!(report.is_repairable());
+
+    let report = repair(&env, NetworkId::Main).unwrap();
+    assert!(report.is_consistent());
+    assert!(ChainStore::new(&env).get_chain_info(&hash, false, None).unwrap().on_main_chain);
+}
@@ -1,4 +1,10 @@
 mod blockchain;
+#[cfg(feature = "simulation")]
+mod chain_builder;
 mod chain_info;
 mod chain_store;
+mod consistency;
+mod schema;
+mod snapshot;
+mod view;
 mod transaction_cache;
@@ -0,0 +1,58 @@
+use beserial::Deserialize;
+use std::sync::Arc;
+use nimiq::consensus::base::block::Block;
+use nimiq::consensus::base::blockchain::{Blockchain, BlockchainError, MetadataStore, PushResult, SCHEMA_VERSION};
+use nimiq::consensus::networks::NetworkId;
+use nimiq::network::NetworkTime;
+use nimiq::utils::db::WriteTransaction;
+use nimiq::utils::db::volatile::VolatileEnvironment;
+
+use super::blockchain::BLOCK_2;
+
+#[test]
+fn it_stamps_a_fresh_database_with_the_schema_version() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap();
+
+    let meta_store = MetadataStore::new(&env);
+    assert_eq!(meta_store.get_schema_version(None), Some(SCHEMA_VERSION));
+}
+
+#[test]
+fn it_migrates_an_unversioned_database() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let block = Block::deserialize_from_vec(&hex::decode(BLOCK_2).unwrap()).unwrap();
+    {
+        let blockchain = Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap();
+        assert_eq!(blockchain.push(block), PushResult::Extended);
+    }
+
+    // Simulate a database written before schema versioning existed.
+    // A database can only be opened once at a time, so close it again before the blockchain opens it.
+    {
+        let meta_db = env.open_database("Metadata".to_string());
+        let mut txn = WriteTransaction::new(&env);
+        txn.remove(&meta_db, "schemaVersion");
+        txn.commit().unwrap();
+    }
+
+    let blockchain = Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap();
+    assert_eq!(blockchain.height(), 2);
+    assert_eq!(MetadataStore::new(&env).get_schema_version(None), Some(SCHEMA_VERSION));
+}
+
+#[test]
+fn it_rejects_a_newer_schema_version() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap();
+
+    {
+        let meta_store = MetadataStore::new(&env);
+        let mut txn = WriteTransaction::new(&env);
+        meta_store.set_schema_version(&mut txn, SCHEMA_VERSION + 1);
+        txn.commit().unwrap();
+    }
+
+    let result = Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new()));
+    assert_eq!(result.err(), Some(BlockchainError::UnsupportedSchemaVersion(SCHEMA_VERSION + 1)));
+}
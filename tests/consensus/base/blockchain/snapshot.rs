@@ -0,0 +1,41 @@
+use beserial::Deserialize;
+use lmdb_zero::open::Flags;
+use std::sync::Arc;
+use tempdir::TempDir;
+use nimiq::consensus::base::block::Block;
+use nimiq::consensus::base::blockchain::{Blockchain, PushResult, SnapshotError, SnapshotManifest, create_snapshot, restore_snapshot};
+use nimiq::consensus::networks::NetworkId;
+use nimiq::network::NetworkTime;
+use nimiq::utils::db::lmdb::LmdbEnvironment;
+use nimiq::utils::db::volatile::VolatileEnvironment;
+
+use super::blockchain::BLOCK_2;
+
+#[test]
+fn it_can_create_and_restore_snapshots() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap();
+    let block = Block::deserialize_from_vec(&hex::decode(BLOCK_2).unwrap()).unwrap();
+    assert_eq!(blockchain.push(block), PushResult::Extended);
+
+    let temp_dir = TempDir::new("snapshot").unwrap();
+    let snapshot_dir = temp_dir.path().join("snapshot").to_str().unwrap().to_string();
+    let db_dir = temp_dir.path().join("db").to_str().unwrap().to_string();
+
+    // The blockchain keeps its environment open while the snapshot is taken.
+    let manifest = create_snapshot(&env, NetworkId::Main, &snapshot_dir).unwrap();
+    assert_eq!(manifest.height, 2);
+    assert_eq!(manifest.head_hash, blockchain.head_hash());
+    assert_eq!(SnapshotManifest::read(&snapshot_dir).unwrap(), manifest);
+
+    match restore_snapshot(&snapshot_dir, &db_dir, NetworkId::Test) {
+        Err(SnapshotError::NetworkMismatch(NetworkId::Main)) => {},
+        result => panic!("Unexpected result: {:?}", result),
+    }
+    assert_eq!(restore_snapshot(&snapshot_dir, &db_dir, NetworkId::Main).unwrap(), manifest);
+
+    let restored_env = LmdbEnvironment::new(&db_dir, 1024 * 1024, 10, Flags::empty()).unwrap();
+    let restored = Blockchain::new(&restored_env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap();
+    assert_eq!(restored.height(), 2);
+    assert_eq!(restored.head_hash(), manifest.head_hash);
+}
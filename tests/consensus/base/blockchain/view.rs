@@ -0,0 +1,52 @@
+use beserial::Deserialize;
+use lmdb_zero::open::Flags;
+use std::sync::Arc;
+use tempdir::TempDir;
+use nimiq::consensus::base::block::Block;
+use nimiq::consensus::base::blockchain::{Blockchain, BlockchainError, ChainStore, PushResult};
+use nimiq::consensus::networks::NetworkId;
+use nimiq::network::NetworkTime;
+use nimiq::utils::db::lmdb::LmdbEnvironment;
+
+use super::blockchain::BLOCK_2;
+
+#[test]
+fn it_can_read_a_stored_chain() {
+    let temp_dir = TempDir::new("view").unwrap();
+    let path = temp_dir.path().to_str().unwrap();
+
+    let block = Block::deserialize_from_vec(&hex::decode(BLOCK_2).unwrap()).unwrap();
+    let (head_hash, miner_balance) = {
+        let env = LmdbEnvironment::new(path, 1024 * 1024, 10, Flags::empty()).unwrap();
+        let blockchain = Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap();
+        assert_eq!(blockchain.push(block.clone()), PushResult::Extended);
+        let miner = &block.body.as_ref().unwrap().miner;
+        let balance = blockchain.accounts().get(miner, None).balance();
+        (blockchain.head_hash(), balance)
+    };
+
+    let env = LmdbEnvironment::open_read_only(path, 10).unwrap();
+    let view = Blockchain::new_read_only(&env, NetworkId::Main).unwrap();
+    assert_eq!(view.height(), 2);
+    assert_eq!(view.head_hash(), head_hash);
+    assert_eq!(view.get_block_at(2, true), Some(block.clone()));
+    assert_eq!(view.get_account(&block.body.as_ref().unwrap().miner).balance(), miner_balance);
+
+    let txn = view.read_transaction();
+    assert_eq!(view.accounts().hash(Some(&txn)), view.head().header.accounts_hash);
+}
+
+#[test]
+fn it_rejects_an_unversioned_database() {
+    let temp_dir = TempDir::new("view").unwrap();
+    let path = temp_dir.path().to_str().unwrap();
+
+    // A database written before schema versioning existed has no metadata.
+    {
+        let env = LmdbEnvironment::new(path, 1024 * 1024, 10, Flags::empty()).unwrap();
+        ChainStore::new(&env);
+    }
+
+    let env = LmdbEnvironment::open_read_only(path, 10).unwrap();
+    assert_eq!(Blockchain::new_read_only(&env, NetworkId::Main).err(), Some(BlockchainError::UnsupportedSchemaVersion(0)));
+}
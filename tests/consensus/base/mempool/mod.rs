@@ -1,14 +1,17 @@
 use beserial::{Serialize, Deserialize};
 use hex;
+use parking_lot::Mutex;
 use std::sync::Arc;
 
+use nimiq::consensus::base::account::{Account, AccountError};
 use nimiq::consensus::base::primitive::{Address, Coin};
-use nimiq::consensus::base::primitive::hash::Hash;
+use nimiq::consensus::base::primitive::hash::{Blake2bHash, Hash};
 use nimiq::consensus::base::primitive::crypto::KeyPair;
 use nimiq::consensus::base::block::BlockBody;
-use nimiq::consensus::base::mempool::{Mempool, ReturnCode};
+use nimiq::consensus::base::mempool::{Mempool, MempoolEvent, PendingBalance, MempoolStore, ReturnCode, FeeTarget, estimate_fee, estimate_fee_per_byte, FULL_MEMPOOL_FEE_INCREMENT};
 use nimiq::consensus::base::transaction::{Transaction, SignatureProof};
 use nimiq::consensus::networks::NetworkId;
+use nimiq::consensus::policy;
 use nimiq::utils::db::WriteTransaction;
 use nimiq::utils::db::volatile::VolatileEnvironment;
 use nimiq::consensus::base::blockchain::Blockchain;
@@ -19,7 +22,7 @@ const BASIC_TRANSACTION: &str = "000222666efadc937148a6d61589ce6d4aeecca97fda4c3
 #[test]
 fn push_same_tx_twice() {
     let env = VolatileEnvironment::new(10).unwrap();
-    let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())));
+    let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap());
     let mempool = Mempool::new(blockchain.clone());
 
     let keypair_a = KeyPair::generate();
@@ -30,7 +33,7 @@ fn push_same_tx_twice() {
     let body = BlockBody { miner: address_a.clone(), extra_data: Vec::new(), transactions: Vec::new(), pruned_accounts: Vec::new() };
     let mut txn = WriteTransaction::new(&env);
     blockchain.accounts().commit_block_body(&mut txn, &body, 1).unwrap();
-    txn.commit();
+    txn.commit().unwrap();
 
     // Generate and sign transaction from address_a
     let mut tx = Transaction::new_basic( address_a.clone(), address_b.clone(), Coin::from(10), Coin::from(0), 1, NetworkId::Main );
@@ -43,7 +46,7 @@ fn push_same_tx_twice() {
 #[test]
 fn push_tx_with_wrong_signature() {
     let env = VolatileEnvironment::new(10).unwrap();
-    let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())));
+    let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap());
     let mempool = Mempool::new(blockchain);
 
     let v: Vec<u8> = hex::decode(BASIC_TRANSACTION).unwrap();
@@ -55,7 +58,7 @@ fn push_tx_with_wrong_signature() {
 #[test]
 fn push_tx_with_insufficient_balance() {
     let env = VolatileEnvironment::new(10).unwrap();
-    let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())));
+    let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap());
     let mempool = Mempool::new(blockchain);
 
     let v: Vec<u8> = hex::decode(BASIC_TRANSACTION).unwrap();
@@ -67,7 +70,7 @@ fn push_tx_with_insufficient_balance() {
 #[test]
 fn push_and_get_valid_tx() {
     let env = VolatileEnvironment::new(10).unwrap();
-    let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())));
+    let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap());
     let mempool = Mempool::new(blockchain.clone());
 
     let keypair_a = KeyPair::generate();
@@ -78,7 +81,7 @@ fn push_and_get_valid_tx() {
     let body = BlockBody { miner: address_a.clone(), extra_data: Vec::new(), transactions: Vec::new(), pruned_accounts: Vec::new() };
     let mut txn = WriteTransaction::new(&env);
     blockchain.accounts().commit_block_body(&mut txn, &body, 1).unwrap();
-    txn.commit();
+    txn.commit().unwrap();
 
     // Generate and sign transaction from address_a
     let mut tx = Transaction::new_basic( address_a.clone(), address_b.clone(), Coin::from(10), Coin::from(0), 1, NetworkId::Main );
@@ -97,7 +100,7 @@ fn push_and_get_valid_tx() {
 #[test]
 fn push_and_get_two_tx_same_user() {
     let env = VolatileEnvironment::new(10).unwrap();
-    let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())));
+    let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap());
     let mempool = Mempool::new(blockchain.clone());
 
     let keypair_a = KeyPair::generate();
@@ -108,7 +111,7 @@ fn push_and_get_two_tx_same_user() {
     let body = BlockBody { miner: address_a.clone(), extra_data: Vec::new(), transactions: Vec::new(), pruned_accounts: Vec::new() };
     let mut txn = WriteTransaction::new(&env);
     blockchain.accounts().commit_block_body(&mut txn, &body, 1).unwrap();
-    txn.commit();
+    txn.commit().unwrap();
 
     // Generate, sign and push 1st transaction from address_a
     let mut tx1 = Transaction::new_basic( address_a.clone(), address_b.clone(), Coin::from(10), Coin::from(0), 1, NetworkId::Main );
@@ -133,7 +136,7 @@ fn push_and_get_two_tx_same_user() {
 #[test]
 fn reject_free_tx_beyond_limit() {
     let env = VolatileEnvironment::new(10).unwrap();
-    let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())));
+    let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap());
     let mempool = Mempool::new(blockchain.clone());
 
     let keypair_a = KeyPair::generate();
@@ -144,7 +147,7 @@ fn reject_free_tx_beyond_limit() {
     let body = BlockBody { miner: address_a.clone(), extra_data: Vec::new(), transactions: Vec::new(), pruned_accounts: Vec::new() };
     let mut txn = WriteTransaction::new(&env);
     blockchain.accounts().commit_block_body(&mut txn, &body, 1).unwrap();
-    txn.commit();
+    txn.commit().unwrap();
 
     for i in 0..10 + 1 {
         let mut tx1 = Transaction::new_basic( address_a.clone(), address_b.clone(), Coin::from(1 + i), Coin::from(0), 1, NetworkId::Main );
@@ -157,3 +160,386 @@ fn reject_free_tx_beyond_limit() {
         }
     }
 }
+
+#[test]
+fn get_transactions_for_block_by_fee() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap());
+    let mempool = Mempool::new(blockchain.clone());
+
+    let keypair_a = KeyPair::generate();
+    let address_a = Address::from(&keypair_a.public);
+    let address_b = Address::from([2u8; Address::SIZE]);
+
+    // Give address_a balance
+    let body = BlockBody { miner: address_a.clone(), extra_data: Vec::new(), transactions: Vec::new(), pruned_accounts: Vec::new() };
+    let mut txn = WriteTransaction::new(&env);
+    blockchain.accounts().commit_block_body(&mut txn, &body, 1).unwrap();
+    txn.commit().unwrap();
+
+    let mut txs = Vec::new();
+    for i in 0..3 {
+        let mut tx = Transaction::new_basic( address_a.clone(), address_b.clone(), Coin::from(10), Coin::from(1000 * (i + 1)), 1, NetworkId::Main );
+        let signature_proof = SignatureProof::from(keypair_a.public.clone(), keypair_a.sign(&tx.serialize_content()));
+        tx.proof = signature_proof.serialize_to_vec();
+        assert_eq!(mempool.push_transaction(tx.clone()), ReturnCode::Accepted);
+        txs.push(tx);
+    }
+
+    // All transactions fit and are returned in block order.
+    let (block_txs, pruned_accounts) = mempool.get_transactions_for_block(10000);
+    assert_eq!(block_txs.len(), 3);
+    assert!(pruned_accounts.is_empty());
+    for i in 1..block_txs.len() {
+        assert_eq!(block_txs[i - 1].cmp_block_order(&block_txs[i]), std::cmp::Ordering::Less);
+    }
+
+    // Only the two highest fee transactions fit.
+    let tx_size = txs[0].serialized_size();
+    let (block_txs, _) = mempool.get_transactions_for_block(2 * tx_size);
+    assert_eq!(block_txs.len(), 2);
+    assert!(block_txs.contains(&txs[1]));
+    assert!(block_txs.contains(&txs[2]));
+
+    // Plain queries return the transactions in ascending order of their fee/byte.
+    let pool_txs: Vec<Transaction> = mempool.get_transactions(10000, 0f64).iter().map(|tx| Transaction::clone(tx)).collect();
+    assert_eq!(pool_txs, txs);
+    let pool_txs: Vec<Transaction> = mempool.get_transactions(10000, txs[1].fee_per_byte()).iter().map(|tx| Transaction::clone(tx)).collect();
+    assert_eq!(pool_txs, txs[1..].to_vec());
+}
+
+#[test]
+fn notify_evicted_tx() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap());
+    let mempool = Mempool::new(blockchain.clone());
+
+    let keypair_a = KeyPair::generate();
+    let address_a = Address::from(&keypair_a.public);
+    let address_b = Address::from([2u8; Address::SIZE]);
+
+    // Give address_a balance
+    let body = BlockBody { miner: address_a.clone(), extra_data: Vec::new(), transactions: Vec::new(), pruned_accounts: Vec::new() };
+    let mut txn = WriteTransaction::new(&env);
+    blockchain.accounts().commit_block_body(&mut txn, &body, 1).unwrap();
+    txn.commit().unwrap();
+    let balance = u64::from(blockchain.accounts().get(&address_a, None).balance());
+
+    let evicted = Arc::new(Mutex::new(Vec::new()));
+    let evicted_listener = evicted.clone();
+    mempool.notifier.write().register(move |event: &MempoolEvent| {
+        if let MempoolEvent::TransactionEvicted(hash, _) = event {
+            evicted_listener.lock().push(hash.clone());
+        }
+    });
+
+    // A free transaction spending almost the whole balance.
+    let mut tx1 = Transaction::new_basic( address_a.clone(), address_b.clone(), Coin::from(balance - 1000), Coin::from(0), 1, NetworkId::Main );
+    let signature_proof1 = SignatureProof::from(keypair_a.public.clone(), keypair_a.sign(&tx1.serialize_content()));
+    tx1.proof = signature_proof1.serialize_to_vec();
+    let hash1: Blake2bHash = tx1.hash();
+    assert_eq!(mempool.push_transaction(tx1), ReturnCode::Accepted);
+
+    // A higher fee transaction from the same sender invalidates the first one.
+    let mut tx2 = Transaction::new_basic( address_a.clone(), address_b.clone(), Coin::from(balance - 1000), Coin::from(1000), 1, NetworkId::Main );
+    let signature_proof2 = SignatureProof::from(keypair_a.public.clone(), keypair_a.sign(&tx2.serialize_content()));
+    tx2.proof = signature_proof2.serialize_to_vec();
+    assert_eq!(mempool.push_transaction(tx2), ReturnCode::Accepted);
+
+    assert_eq!(*evicted.lock(), vec![hash1.clone()]);
+    assert!(mempool.get_transaction(&hash1).is_none());
+}
+
+#[test]
+fn persist_and_restore_txs() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap());
+    let mempool = Mempool::new(blockchain.clone());
+    let store = MempoolStore::new(&env);
+
+    let keypair_a = KeyPair::generate();
+    let address_a = Address::from(&keypair_a.public);
+    let address_b = Address::from([2u8; Address::SIZE]);
+
+    // Give address_a balance
+    let body = BlockBody { miner: address_a.clone(), extra_data: Vec::new(), transactions: Vec::new(), pruned_accounts: Vec::new() };
+    let mut txn = WriteTransaction::new(&env);
+    blockchain.accounts().commit_block_body(&mut txn, &body, 1).unwrap();
+    txn.commit().unwrap();
+
+    // A valid transaction and one that is outside the validity window at the next block height.
+    let mut tx1 = Transaction::new_basic( address_a.clone(), address_b.clone(), Coin::from(10), Coin::from(1000), 1, NetworkId::Main );
+    let signature_proof1 = SignatureProof::from(keypair_a.public.clone(), keypair_a.sign(&tx1.serialize_content()));
+    tx1.proof = signature_proof1.serialize_to_vec();
+    let hash1: Blake2bHash = tx1.hash();
+    assert_eq!(mempool.push_transaction(tx1), ReturnCode::Accepted);
+    mempool.persist(&store);
+    assert_eq!(store.get_transactions().len(), 1);
+
+    let mut tx2 = Transaction::new_basic( address_a.clone(), address_b.clone(), Coin::from(20), Coin::from(1000), 1_000_000, NetworkId::Main );
+    let signature_proof2 = SignatureProof::from(keypair_a.public.clone(), keypair_a.sign(&tx2.serialize_content()));
+    tx2.proof = signature_proof2.serialize_to_vec();
+    let hash2: Blake2bHash = tx2.hash();
+    store.put_transactions(&[mempool.get_transaction(&hash1).unwrap(), Arc::new(tx2)]);
+
+    // Restore into a fresh mempool.
+    let mempool2 = Mempool::new(blockchain.clone());
+    assert_eq!(mempool2.restore_from_store(&store), 1);
+    assert!(mempool2.get_transaction(&hash1).is_some());
+    assert!(mempool2.get_transaction(&hash2).is_none());
+}
+
+#[test]
+fn estimate_fee_for_free_tx() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap());
+    let mempool = Mempool::new(blockchain.clone());
+
+    let keypair_a = KeyPair::generate();
+    let address_a = Address::from(&keypair_a.public);
+    let address_b = Address::from([2u8; Address::SIZE]);
+
+    // Give address_a balance
+    let body = BlockBody { miner: address_a.clone(), extra_data: Vec::new(), transactions: Vec::new(), pruned_accounts: Vec::new() };
+    let mut txn = WriteTransaction::new(&env);
+    blockchain.accounts().commit_block_body(&mut txn, &body, 1).unwrap();
+    txn.commit().unwrap();
+
+    // An almost empty mempool lets transactions through for free.
+    assert_eq!(estimate_fee_per_byte(&mempool, &address_a, FeeTarget::NextBlock), 0f64);
+    assert_eq!(estimate_fee_per_byte(&mempool, &address_a, FeeTarget::WithinBlocks(5)), 0f64);
+    assert_eq!(estimate_fee(&mempool, &address_a, 138, FeeTarget::Free), Coin::from(0));
+
+    for i in 0..10 {
+        let mut tx = Transaction::new_basic( address_a.clone(), address_b.clone(), Coin::from(1 + i), Coin::from(0), 1, NetworkId::Main );
+        let signature_proof = SignatureProof::from(keypair_a.public.clone(), keypair_a.sign(&tx.serialize_content()));
+        tx.proof = signature_proof.serialize_to_vec();
+        assert_eq!(mempool.push_transaction(tx), ReturnCode::Accepted);
+    }
+
+    // The sender has used up its free transactions.
+    assert_eq!(estimate_fee_per_byte(&mempool, &address_a, FeeTarget::Free), 1f64);
+    assert_eq!(estimate_fee(&mempool, &address_a, 138, FeeTarget::NextBlock), Coin::from(138));
+    assert_eq!(estimate_fee_per_byte(&mempool, &address_b, FeeTarget::Free), 0f64);
+}
+
+#[test]
+fn estimate_fee_for_full_mempool() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap());
+    let mempool = Mempool::with_size_max(blockchain.clone(), 2);
+
+    let keypair_a = KeyPair::generate();
+    let address_a = Address::from(&keypair_a.public);
+    let address_b = Address::from([2u8; Address::SIZE]);
+
+    // Give address_a balance
+    let body = BlockBody { miner: address_a.clone(), extra_data: Vec::new(), transactions: Vec::new(), pruned_accounts: Vec::new() };
+    let mut txn = WriteTransaction::new(&env);
+    blockchain.accounts().commit_block_body(&mut txn, &body, 1).unwrap();
+    txn.commit().unwrap();
+
+    let mut txs = Vec::new();
+    for i in 0..2 {
+        let mut tx = Transaction::new_basic( address_a.clone(), address_b.clone(), Coin::from(1 + i), Coin::from(1000 * (i + 1)), 1, NetworkId::Main );
+        let signature_proof = SignatureProof::from(keypair_a.public.clone(), keypair_a.sign(&tx.serialize_content()));
+        tx.proof = signature_proof.serialize_to_vec();
+        assert_eq!(mempool.push_transaction(tx.clone()), ReturnCode::Accepted);
+        txs.push(tx);
+    }
+
+    // The mempool is full, so the estimate has to outbid its lowest fee transaction.
+    let min_fee_per_byte = txs[0].fee_per_byte() + FULL_MEMPOOL_FEE_INCREMENT;
+    assert_eq!(estimate_fee_per_byte(&mempool, &address_a, FeeTarget::Free), min_fee_per_byte);
+    assert_eq!(estimate_fee_per_byte(&mempool, &address_a, FeeTarget::NextBlock), min_fee_per_byte);
+
+    // A transaction paying less than the estimate is rejected.
+    let tx_size = txs[0].serialized_size();
+    let fee = u64::from(estimate_fee(&mempool, &address_a, tx_size, FeeTarget::NextBlock));
+    let mut tx3 = Transaction::new_basic( address_a.clone(), address_b.clone(), Coin::from(3), Coin::from(fee - 1), 1, NetworkId::Main );
+    let signature_proof3 = SignatureProof::from(keypair_a.public.clone(), keypair_a.sign(&tx3.serialize_content()));
+    tx3.proof = signature_proof3.serialize_to_vec();
+    assert_eq!(mempool.push_transaction(tx3), ReturnCode::FeeTooLow);
+
+    // A transaction paying the estimate gets in and evicts the lowest fee transaction.
+    let mut tx4 = Transaction::new_basic( address_a.clone(), address_b.clone(), Coin::from(4), Coin::from(fee), 1, NetworkId::Main );
+    let signature_proof4 = SignatureProof::from(keypair_a.public.clone(), keypair_a.sign(&tx4.serialize_content()));
+    tx4.proof = signature_proof4.serialize_to_vec();
+    assert_eq!(mempool.push_transaction(tx4), ReturnCode::Accepted);
+    assert!(mempool.get_transaction(&txs[0].hash::<Blake2bHash>()).is_none());
+    assert!(mempool.get_transaction(&txs[1].hash::<Blake2bHash>()).is_some());
+}
+
+#[test]
+fn simulate_tx_on_top_of_pending_txs() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap());
+    let mempool = Mempool::new(blockchain.clone());
+
+    let keypair_a = KeyPair::generate();
+    let address_a = Address::from(&keypair_a.public);
+    let address_b = Address::from([2u8; Address::SIZE]);
+
+    // Give address_a balance
+    let body = BlockBody { miner: address_a.clone(), extra_data: Vec::new(), transactions: Vec::new(), pruned_accounts: Vec::new() };
+    let mut txn = WriteTransaction::new(&env);
+    blockchain.accounts().commit_block_body(&mut txn, &body, 1).unwrap();
+    txn.commit().unwrap();
+    let balance = u64::from(policy::block_reward_at(1));
+
+    // Spend all but 1000 in a pending transaction.
+    let mut tx1 = Transaction::new_basic( address_a.clone(), address_b.clone(), Coin::from(balance - 1000), Coin::from(0), 1, NetworkId::Main );
+    let signature_proof1 = SignatureProof::from(keypair_a.public.clone(), keypair_a.sign(&tx1.serialize_content()));
+    tx1.proof = signature_proof1.serialize_to_vec();
+    assert_eq!(mempool.push_transaction(tx1), ReturnCode::Accepted);
+
+    // More than the remaining balance.
+    let mut tx2 = Transaction::new_basic( address_a.clone(), address_b.clone(), Coin::from(2000), Coin::from(0), 1, NetworkId::Main );
+    let signature_proof2 = SignatureProof::from(keypair_a.public.clone(), keypair_a.sign(&tx2.serialize_content()));
+    tx2.proof = signature_proof2.serialize_to_vec();
+    assert_eq!(mempool.simulate_transaction(&tx2), Err(AccountError::InsufficientFunds));
+
+    // Within the remaining balance.
+    let mut tx3 = Transaction::new_basic( address_a.clone(), address_b.clone(), Coin::from(600), Coin::from(0), 1, NetworkId::Main );
+    let signature_proof3 = SignatureProof::from(keypair_a.public.clone(), keypair_a.sign(&tx3.serialize_content()));
+    tx3.proof = signature_proof3.serialize_to_vec();
+    let simulation = mempool.simulate_transaction(&tx3).unwrap();
+    assert_eq!(simulation.block_height, 2);
+    assert_eq!(simulation.sender, Account::new_basic(Coin::from(400)));
+    assert_eq!(simulation.recipient, Account::new_basic(Coin::from(balance - 400)));
+    assert!(simulation.skipped_transactions.is_empty());
+
+    // Invalid signature.
+    tx3.value = Coin::from(500);
+    assert!(mempool.simulate_transaction(&tx3).is_err());
+
+    // Nothing was committed.
+    assert_eq!(blockchain.accounts().get(&address_a, None), Account::new_basic(Coin::from(balance)));
+    assert_eq!(blockchain.accounts().get(&address_b, None).balance(), Coin::ZERO);
+}
+
+#[test]
+fn simulate_tx_skips_pending_txs_that_no_longer_apply() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap());
+    let mempool = Mempool::new(blockchain.clone());
+
+    let keypair_a = KeyPair::generate();
+    let address_a = Address::from(&keypair_a.public);
+    let address_b = Address::from([2u8; Address::SIZE]);
+
+    // Give address_a balance
+    let body = BlockBody { miner: address_a.clone(), extra_data: Vec::new(), transactions: Vec::new(), pruned_accounts: Vec::new() };
+    let mut txn = WriteTransaction::new(&env);
+    blockchain.accounts().commit_block_body(&mut txn, &body, 1).unwrap();
+    txn.commit().unwrap();
+    let balance = u64::from(policy::block_reward_at(1));
+
+    // Spend all but 1000 in a pending transaction.
+    let mut tx1 = Transaction::new_basic( address_a.clone(), address_b.clone(), Coin::from(balance - 1000), Coin::from(0), 1, NetworkId::Main );
+    let signature_proof1 = SignatureProof::from(keypair_a.public.clone(), keypair_a.sign(&tx1.serialize_content()));
+    tx1.proof = signature_proof1.serialize_to_vec();
+    assert_eq!(mempool.push_transaction(tx1.clone()), ReturnCode::Accepted);
+
+    // Another transaction spends all but 500 behind the mempool's back, so the pending one no longer applies.
+    let mut tx2 = Transaction::new_basic( address_a.clone(), address_b.clone(), Coin::from(balance - 500), Coin::from(0), 1, NetworkId::Main );
+    let signature_proof2 = SignatureProof::from(keypair_a.public.clone(), keypair_a.sign(&tx2.serialize_content()));
+    tx2.proof = signature_proof2.serialize_to_vec();
+    let body = BlockBody { miner: address_b.clone(), extra_data: Vec::new(), transactions: vec![tx2], pruned_accounts: Vec::new() };
+    let mut txn = WriteTransaction::new(&env);
+    blockchain.accounts().commit_block_body(&mut txn, &body, 2).unwrap();
+    txn.commit().unwrap();
+
+    let mut tx3 = Transaction::new_basic( address_a.clone(), address_b.clone(), Coin::from(100), Coin::from(0), 1, NetworkId::Main );
+    let signature_proof3 = SignatureProof::from(keypair_a.public.clone(), keypair_a.sign(&tx3.serialize_content()));
+    tx3.proof = signature_proof3.serialize_to_vec();
+    let simulation = mempool.simulate_transaction(&tx3).unwrap();
+    assert_eq!(simulation.sender, Account::new_basic(Coin::from(400)));
+    assert_eq!(simulation.skipped_transactions, vec![tx1.hash::<Blake2bHash>()]);
+}
+
+#[test]
+fn simulate_tx_on_head_accounts() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap());
+
+    let keypair_a = KeyPair::generate();
+    let address_a = Address::from(&keypair_a.public);
+
+    // Give address_a balance
+    let body = BlockBody { miner: address_a.clone(), extra_data: Vec::new(), transactions: Vec::new(), pruned_accounts: Vec::new() };
+    let mut txn = WriteTransaction::new(&env);
+    blockchain.accounts().commit_block_body(&mut txn, &body, 1).unwrap();
+    txn.commit().unwrap();
+    let balance = u64::from(policy::block_reward_at(1));
+
+    // A transaction to the sender itself only costs the fee.
+    let mut tx = Transaction::new_basic( address_a.clone(), address_a.clone(), Coin::from(1000), Coin::from(10), 1, NetworkId::Main );
+    let signature_proof = SignatureProof::from(keypair_a.public.clone(), keypair_a.sign(&tx.serialize_content()));
+    tx.proof = signature_proof.serialize_to_vec();
+    let simulation = blockchain.simulate_transaction(&tx).unwrap();
+    assert_eq!(simulation.block_height, 2);
+    assert_eq!(simulation.sender, Account::new_basic(Coin::from(balance - 10)));
+    assert_eq!(simulation.recipient, simulation.sender);
+    assert_eq!(blockchain.accounts().get(&address_a, None), Account::new_basic(Coin::from(balance)));
+}
+
+#[test]
+fn get_pending_balances() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap());
+    let mempool = Mempool::new(blockchain.clone());
+
+    let keypair_a = KeyPair::generate();
+    let address_a = Address::from(&keypair_a.public);
+    let address_b = Address::from([2u8; Address::SIZE]);
+
+    // Give address_a balance
+    let body = BlockBody { miner: address_a.clone(), extra_data: Vec::new(), transactions: Vec::new(), pruned_accounts: Vec::new() };
+    let mut txn = WriteTransaction::new(&env);
+    blockchain.accounts().commit_block_body(&mut txn, &body, 1).unwrap();
+    txn.commit().unwrap();
+    let balance = policy::block_reward_at(1);
+
+    let mut tx1 = Transaction::new_basic( address_a.clone(), address_b.clone(), Coin::from(1000), Coin::from(200), 1, NetworkId::Main );
+    let signature_proof1 = SignatureProof::from(keypair_a.public.clone(), keypair_a.sign(&tx1.serialize_content()));
+    tx1.proof = signature_proof1.serialize_to_vec();
+    let hash1: Blake2bHash = tx1.hash();
+    assert_eq!(mempool.push_transaction(tx1), ReturnCode::Accepted);
+
+    let mut tx2 = Transaction::new_basic( address_a.clone(), address_b.clone(), Coin::from(10), Coin::from(0), 1, NetworkId::Main );
+    let signature_proof2 = SignatureProof::from(keypair_a.public.clone(), keypair_a.sign(&tx2.serialize_content()));
+    tx2.proof = signature_proof2.serialize_to_vec();
+    let hash2: Blake2bHash = tx2.hash();
+    assert_eq!(mempool.push_transaction(tx2), ReturnCode::Accepted);
+
+    let balances = mempool.get_pending_balances(vec![address_a.clone(), address_b.clone()]);
+    assert_eq!(balances[0], PendingBalance {
+        address: address_a,
+        confirmed: balance,
+        pending_outgoing: Coin::from(1210),
+        pending_incoming: Coin::ZERO,
+        pending_transactions: vec![hash1.clone(), hash2.clone()],
+    });
+    assert_eq!(balances[0].effective(), Some(balance - Coin::from(1210)));
+    assert_eq!(balances[1], PendingBalance {
+        address: address_b,
+        confirmed: Coin::ZERO,
+        pending_outgoing: Coin::ZERO,
+        pending_incoming: Coin::from(1010),
+        pending_transactions: vec![hash1, hash2],
+    });
+    assert_eq!(balances[1].effective(), Some(Coin::from(1010)));
+}
+
+#[test]
+fn pending_balance_does_not_underflow() {
+    let balance = PendingBalance {
+        address: Address::from([1u8; Address::SIZE]),
+        confirmed: Coin::from(100),
+        pending_outgoing: Coin::from(200),
+        pending_incoming: Coin::from(50),
+        pending_transactions: Vec::new(),
+    };
+    assert_eq!(balance.effective(), None);
+}
@@ -0,0 +1,76 @@
+use beserial::Serialize;
+use std::sync::Arc;
+
+use nimiq::consensus::base::account::Account;
+use nimiq::consensus::base::block::TargetCompact;
+use nimiq::consensus::base::blockchain::Blockchain;
+use nimiq::consensus::base::mempool::{Mempool, ReturnCode};
+use nimiq::consensus::base::primitive::{Address, Coin};
+use nimiq::consensus::base::primitive::crypto::KeyPair;
+use nimiq::consensus::base::primitive::hash::{Blake2bHash, Hash};
+use nimiq::consensus::base::transaction::{SignatureProof, Transaction};
+use nimiq::consensus::generator::{GenerateError, generate_blocks};
+use nimiq::consensus::genesis::generate_dev_genesis;
+use nimiq::consensus::networks::{NetworkId, register_network};
+use nimiq::consensus::policy;
+use nimiq::network::NetworkTime;
+use nimiq::utils::db::volatile::VolatileEnvironment;
+
+#[test]
+fn it_generates_blocks_on_development_networks() {
+    let network_id = NetworkId(220);
+    let keypair_a = KeyPair::generate();
+    let address_a = Address::from(&keypair_a.public);
+    let address_b = Address::from([2u8; Address::SIZE]);
+    let miner = Address::from([3u8; Address::SIZE]);
+
+    let config = generate_dev_genesis(network_id, "dev".to_string(), vec![(address_a.clone(), Account::new_basic(Coin::from(1000)))], 1546300800).unwrap();
+    let genesis_n_bits = config.block.header.n_bits;
+    register_network(config.into_network_info()).unwrap();
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = Arc::new(Blockchain::new(&env, network_id, Arc::new(NetworkTime::new())).unwrap());
+    let mempool = Mempool::new(blockchain.clone());
+
+    let mut tx = Transaction::new_basic(address_a.clone(), address_b.clone(), Coin::from(100), Coin::from(10), 1, network_id);
+    let signature_proof = SignatureProof::from(keypair_a.public.clone(), keypair_a.sign(&tx.serialize_content()));
+    tx.proof = signature_proof.serialize_to_vec();
+    let tx_hash: Blake2bHash = tx.hash();
+    assert_eq!(mempool.push_transaction(tx), ReturnCode::Accepted);
+
+    let hashes = generate_blocks(&blockchain, &mempool, 3, &miner).unwrap();
+    assert_eq!(hashes.len(), 3);
+    assert_eq!(blockchain.height(), 4);
+    assert_eq!(blockchain.head_hash(), hashes[2]);
+
+    // The pending transaction was included in the first block and left the mempool.
+    let block = blockchain.get_block(&hashes[0], false, true).unwrap();
+    assert_eq!(block.body.unwrap().transactions[0].hash::<Blake2bHash>(), tx_hash);
+    assert!(!mempool.contains(&tx_hash));
+    assert_eq!(blockchain.accounts().get(&address_b, None), Account::new_basic(Coin::from(100)));
+    let reward = u64::from(policy::block_reward_at(2)) + u64::from(policy::block_reward_at(3)) + u64::from(policy::block_reward_at(4)) + 10;
+    assert_eq!(blockchain.accounts().get(&miner, None).balance(), Coin::from(reward));
+
+    // The difficulty never changes.
+    assert_eq!(blockchain.head().header.n_bits, genesis_n_bits);
+    assert_eq!(TargetCompact::from(blockchain.get_next_target(None)), genesis_n_bits);
+
+    // Generated blocks are reproducible.
+    let generate_empty_blocks = || {
+        let env = VolatileEnvironment::new(10).unwrap();
+        let blockchain = Arc::new(Blockchain::new(&env, network_id, Arc::new(NetworkTime::new())).unwrap());
+        let mempool = Mempool::new(blockchain.clone());
+        generate_blocks(&blockchain, &mempool, 3, &miner).unwrap()
+    };
+    assert_eq!(generate_empty_blocks(), generate_empty_blocks());
+}
+
+#[test]
+fn it_refuses_to_generate_blocks_on_other_networks() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap());
+    let mempool = Mempool::new(blockchain.clone());
+
+    assert_eq!(generate_blocks(&blockchain, &mempool, 1, &Address::from([3u8; Address::SIZE])), Err(GenerateError::NotDevelopmentNetwork(NetworkId::Main)));
+    assert_eq!(blockchain.height(), 1);
+}
@@ -0,0 +1,55 @@
+use std::sync::Arc;
+use nimiq::consensus::base::account::Account;
+use nimiq::consensus::base::block::TargetCompact;
+use nimiq::consensus::base::blockchain::Blockchain;
+use nimiq::consensus::base::primitive::{Address, Coin};
+use nimiq::consensus::base::primitive::hash::{Blake2bHash, Hash};
+use nimiq::consensus::genesis::{GenesisConfig, GenesisError, generate_genesis, parse_genesis_accounts};
+use nimiq::consensus::networks::{NetworkId, get_network_info, register_network};
+use nimiq::network::NetworkTime;
+use nimiq::utils::db::volatile::VolatileEnvironment;
+
+const GENESIS_ACCOUNTS: &str = "
+# Two basic accounts
+basic: 0101010101010101010101010101010101010101 100000
+basic: 0202020202020202020202020202020202020202 5
+";
+
+#[test]
+fn it_parses_genesis_accounts() {
+    let accounts = parse_genesis_accounts(GENESIS_ACCOUNTS).unwrap();
+    assert_eq!(accounts, vec![
+        (Address::from([1u8; Address::SIZE]), Account::new_basic(Coin::from(100000))),
+        (Address::from([2u8; Address::SIZE]), Account::new_basic(Coin::from(5))),
+    ]);
+
+    match parse_genesis_accounts("basic: 0101 5") {
+        Err(GenesisError::InvalidEntry("basic")) => {},
+        _ => panic!("Parsed an invalid address"),
+    }
+}
+
+#[test]
+fn it_generates_a_valid_genesis_block() {
+    let network_id = NetworkId(210);
+    let accounts = parse_genesis_accounts(GENESIS_ACCOUNTS).unwrap();
+    // A trivial target, so that mining takes only a few hashes.
+    let config = generate_genesis(network_id, "generated".to_string(), accounts.clone(), 1546300800, TargetCompact::from(0x207fffff), b"genesis".to_vec()).unwrap();
+
+    assert_eq!(config.hash, config.block.header.hash::<Blake2bHash>());
+    assert!(config.block.header.verify_proof_of_work());
+    assert_eq!(config.block.header.height, 1);
+    assert_eq!(config.block.header.interlink_hash, Blake2bHash::from([0u8; Blake2bHash::SIZE]));
+
+    // The written genesis file can be loaded by the network registry.
+    let config = GenesisConfig::parse(&config.to_string()).unwrap();
+    let network_info = register_network(config.into_network_info()).unwrap();
+    assert!(network_info.genesis_block.verify(network_info.genesis_block.header.timestamp_in_millis(), network_id).is_ok());
+    assert_eq!(get_network_info(network_id).unwrap().genesis_accounts, accounts);
+
+    // Starting a chain checks the accounts hash of the genesis block.
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = Blockchain::new(&env, network_id, Arc::new(NetworkTime::new())).unwrap();
+    assert_eq!(blockchain.head_hash(), network_info.genesis_hash);
+    assert_eq!(blockchain.accounts().get(&Address::from([2u8; Address::SIZE]), None), Account::new_basic(Coin::from(5)));
+}
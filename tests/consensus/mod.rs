@@ -1,2 +1,7 @@
 mod base;
+mod generator;
+mod genesis;
 mod networks;
+mod orphan_pool;
+#[cfg(feature = "simulation")]
+mod simulation;
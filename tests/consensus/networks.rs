@@ -1,5 +1,11 @@
+use beserial::{Deserialize, Serialize};
+use std::sync::Arc;
+use nimiq::consensus::base::blockchain::Blockchain;
 use nimiq::consensus::base::primitive::hash::{Blake2bHash, Hash};
+use nimiq::consensus::genesis::{GenesisConfig, GenesisError, generate_dev_genesis};
 use nimiq::consensus::networks::*;
+use nimiq::network::NetworkTime;
+use nimiq::utils::db::volatile::VolatileEnvironment;
 use hex;
 
 #[test]
@@ -9,3 +15,94 @@ fn it_has_expected_main_hash() {
         &hex::decode("264AAF8A4F9828A76C550635DA078EB466306A189FCC03710BEE9F649C869D12").unwrap()[..]
     )
 }
+
+#[test]
+fn it_has_expected_dummy_genesis() {
+    let dummy = get_network_info(NetworkId::Dummy).unwrap();
+    assert_eq!(
+        dummy.genesis_block.header.hash::<Blake2bHash>().as_bytes(),
+        &hex::decode("7B9CC90AB58021BEFA1C47D9F98755B1D042621079C3084E1CDA400C81DC3D3B").unwrap()[..]
+    );
+    assert_eq!(dummy.genesis_block.header.hash::<Blake2bHash>(), dummy.genesis_hash);
+    assert!(dummy.genesis_block.header.verify_proof_of_work());
+
+    // The hardcoded block is the one the generator creates.
+    let generated = generate_dev_genesis(NetworkId::Dummy, "dummy".to_string(), vec![], 1523727000).unwrap();
+    assert_eq!(generated.block, dummy.genesis_block);
+    assert_eq!(generated.hash, dummy.genesis_hash);
+    assert_eq!(generated.accounts, dummy.genesis_accounts);
+}
+
+#[test]
+fn it_serializes_network_ids_as_a_single_byte() {
+    assert_eq!(NetworkId::Main.serialize_to_vec(), vec![42u8]);
+    assert_eq!(NetworkId::deserialize_from_vec(&vec![1u8]).unwrap(), NetworkId::Test);
+    assert_eq!(NetworkId::deserialize_from_vec(&vec![123u8]).unwrap(), NetworkId(123));
+    assert_eq!(format!("{:?}", NetworkId::Main), "Main");
+}
+
+fn main_genesis_config(network_id: NetworkId) -> GenesisConfig {
+    let main = get_network_info(NetworkId::Main).unwrap();
+    GenesisConfig {
+        network_id,
+        name: "private".to_string(),
+        seed_peers: main.seed_peers[..2].to_vec(),
+        block: main.genesis_block.clone(),
+        hash: main.genesis_hash.clone(),
+        accounts: main.genesis_accounts.clone(),
+        development: false,
+    }
+}
+
+#[test]
+fn it_can_write_and_parse_a_genesis_file() {
+    let config = main_genesis_config(NetworkId(200));
+    let parsed = GenesisConfig::parse(&config.to_string()).unwrap();
+
+    assert_eq!(parsed.network_id, NetworkId(200));
+    assert_eq!(parsed.name, "private");
+    assert_eq!(parsed.seed_peers.len(), 2);
+    assert_eq!(parsed.seed_peers[0].ty, config.seed_peers[0].ty);
+    assert_eq!(parsed.seed_peers[0].public_key, config.seed_peers[0].public_key);
+    assert_eq!(parsed.block, config.block);
+    assert_eq!(parsed.hash, config.hash);
+    assert_eq!(parsed.accounts, config.accounts);
+
+    match GenesisConfig::parse("network: 200\nname: private\n") {
+        Err(GenesisError::MissingEntry("block")) => {},
+        _ => panic!("Parsed a genesis file without block"),
+    }
+}
+
+#[test]
+fn it_can_register_networks() {
+    match register_network(main_genesis_config(NetworkId::Main).into_network_info()) {
+        Err(GenesisError::NetworkExists(NetworkId::Main)) => {},
+        _ => panic!("Registered the main network twice"),
+    }
+
+    let mut config = main_genesis_config(NetworkId(201));
+    config.hash = Blake2bHash::default();
+    match register_network(config.into_network_info()) {
+        Err(GenesisError::HashMismatch(_)) => {},
+        _ => panic!("Registered a network with the wrong genesis hash"),
+    }
+
+    let mut config = main_genesis_config(NetworkId(201));
+    config.accounts.pop();
+    match register_network(config.into_network_info()) {
+        Err(GenesisError::AccountsHashMismatch(_)) => {},
+        _ => panic!("Registered a network with the wrong genesis accounts"),
+    }
+
+    let network_info = register_network(main_genesis_config(NetworkId(201)).into_network_info()).unwrap();
+    assert_eq!(network_info.name, "private");
+    assert_eq!(get_network_info(NetworkId(201)).unwrap().genesis_hash, network_info.genesis_hash);
+
+    // A chain can be started on the new network.
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = Blockchain::new(&env, NetworkId(201), Arc::new(NetworkTime::new())).unwrap();
+    assert_eq!(blockchain.head_hash(), network_info.genesis_hash);
+    let address = &network_info.genesis_accounts[0].0;
+    assert_eq!(blockchain.accounts().get(address, None), network_info.genesis_accounts[0].1);
+}
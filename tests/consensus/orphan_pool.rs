@@ -0,0 +1,49 @@
+use nimiq::consensus::base::block::Block;
+use nimiq::consensus::base::primitive::hash::{Blake2bHash, Hash};
+use nimiq::consensus::orphan_pool::OrphanPool;
+
+fn block_with(prev_hash: &Blake2bHash, nonce: u32) -> Block {
+    let mut block = Block::default();
+    block.header.prev_hash = prev_hash.clone();
+    block.header.nonce = nonce;
+    block
+}
+
+#[test]
+fn it_can_take_successors() {
+    let pool = OrphanPool::new();
+    let parent: Blake2bHash = [1u8; Blake2bHash::SIZE].into();
+    let other: Blake2bHash = [2u8; Blake2bHash::SIZE].into();
+
+    let block1 = block_with(&parent, 1);
+    let block2 = block_with(&parent, 2);
+    let block3 = block_with(&other, 3);
+    assert!(pool.add(block1.clone()));
+    assert!(pool.add(block2.clone()));
+    assert!(pool.add(block3.clone()));
+    assert!(!pool.add(block1.clone()));
+    assert_eq!(pool.len(), 3);
+
+    let successors = pool.take_successors(&parent);
+    assert_eq!(successors, vec![block1.clone(), block2]);
+    assert!(!pool.contains(&block1.header.hash()));
+    assert!(pool.contains(&block3.header.hash()));
+    assert!(pool.take_successors(&parent).is_empty());
+    assert_eq!(pool.len(), 1);
+}
+
+#[test]
+fn it_evicts_oldest_orphans() {
+    let pool = OrphanPool::new();
+    let parent: Blake2bHash = [1u8; Blake2bHash::SIZE].into();
+
+    let first = block_with(&parent, 0);
+    pool.add(first.clone());
+    for nonce in 1..OrphanPool::SIZE_MAX as u32 + 1 {
+        assert!(pool.add(block_with(&parent, nonce)));
+    }
+
+    assert_eq!(pool.len(), OrphanPool::SIZE_MAX);
+    assert!(!pool.contains(&first.header.hash()));
+    assert_eq!(pool.take_successors(&parent).len(), OrphanPool::SIZE_MAX);
+}
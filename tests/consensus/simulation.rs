@@ -0,0 +1,80 @@
+use std::time::Duration;
+
+use nimiq::consensus::networks::NetworkId;
+use nimiq::consensus::simulation::Simulation;
+
+const TIMEOUT: Duration = Duration::from_secs(20);
+
+#[test]
+fn it_syncs_all_nodes_of_a_star() {
+    let mut simulation = Simulation::new(NetworkId::Dummy, 4);
+    let hashes = simulation.generate(0, 3).unwrap();
+
+    simulation.connect_star(0);
+    assert!(simulation.wait_for_head(&hashes[2], TIMEOUT));
+    assert!(simulation.wait_for_consensus(TIMEOUT));
+    assert!(simulation.wait_until(TIMEOUT, |simulation| simulation.node(0).network.peer_count() == 3));
+}
+
+#[test]
+fn it_syncs_a_chain_across_several_hops() {
+    let mut simulation = Simulation::new(NetworkId::Dummy, 3);
+    let hashes = simulation.generate(0, 5).unwrap();
+
+    // A node syncs with what its peers know when it connects, so connect hop by hop.
+    assert!(simulation.connect(1, 0));
+    assert!(simulation.wait_until(TIMEOUT, |simulation| simulation.node(1).blockchain.head_hash() == hashes[4]));
+    assert!(simulation.connect(2, 1));
+    assert!(simulation.wait_for_head(&hashes[4], TIMEOUT));
+    assert!(simulation.wait_for_consensus(TIMEOUT));
+    for i in 0..simulation.num_nodes() {
+        assert_eq!(simulation.node(i).blockchain.height(), 6);
+    }
+    assert_eq!(simulation.node(2).network.peer_count(), 1);
+}
+
+#[test]
+fn it_syncs_a_node_that_joins_later() {
+    let mut simulation = Simulation::new(NetworkId::Dummy, 2);
+    simulation.connect_all();
+    assert!(simulation.wait_for_consensus(TIMEOUT));
+
+    let hashes = simulation.generate(1, 3).unwrap();
+    let node = simulation.add_node();
+    assert!(simulation.connect(node, 1));
+    assert!(simulation.wait_until(TIMEOUT, |simulation| simulation.node(node).blockchain.head_hash() == hashes[2]));
+    assert!(simulation.wait_until(TIMEOUT, |simulation| simulation.node(node).established()));
+    assert!(simulation.wait_until(TIMEOUT, |simulation| simulation.node(1).network.peer_count() == 2));
+}
+
+#[test]
+fn it_rebranches_to_the_harder_chain() {
+    let mut simulation = Simulation::new(NetworkId::Dummy, 2);
+
+    // Both nodes mine on their own, the difficulty is fixed so the longer chain is harder.
+    let short_chain = simulation.generate(0, 2).unwrap();
+    let long_chain = simulation.generate(1, 3).unwrap();
+    assert_ne!(short_chain[0], long_chain[0]);
+
+    assert!(simulation.connect(0, 1));
+    assert!(simulation.wait_for_head(&long_chain[2], TIMEOUT));
+    assert_eq!(simulation.node(0).blockchain.height(), 4);
+
+    // The abandoned blocks are still known as a fork.
+    assert!(simulation.node(0).blockchain.contains(&short_chain[1], true));
+    assert!(!simulation.node(0).blockchain.contains(&short_chain[1], false));
+}
+
+#[test]
+fn it_relays_new_blocks_to_connected_peers() {
+    let mut simulation = Simulation::new(NetworkId::Dummy, 3);
+    simulation.connect_line();
+    assert!(simulation.wait_for_consensus(TIMEOUT));
+
+    // Node 2 is not connected to node 0, it only learns about the blocks through node 1.
+    let hashes = simulation.generate(0, 2).unwrap();
+    assert!(simulation.wait_for_head(&hashes[1], TIMEOUT));
+    for i in 0..simulation.num_nodes() {
+        assert_eq!(simulation.node(i).blockchain.height(), 3);
+    }
+}
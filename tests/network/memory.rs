@@ -0,0 +1,50 @@
+use futures::prelude::*;
+use std::net::Ipv4Addr;
+
+use nimiq::consensus::base::primitive::{Address, Coin};
+use nimiq::consensus::base::transaction::Transaction;
+use nimiq::consensus::networks::{NetworkId, get_network_info};
+use nimiq::network::address::NetAddress;
+use nimiq::network::memory::MemoryStream;
+use nimiq::network::message::Message;
+use nimiq::network::websocket::NimiqMessageStream;
+
+fn stream_pair() -> (NimiqMessageStream, NimiqMessageStream) {
+    let (a, b) = MemoryStream::pair();
+    (NimiqMessageStream::new_memory(a, NetAddress::IPv4(Ipv4Addr::new(10, 0, 2, 1)), true),
+     NimiqMessageStream::new_memory(b, NetAddress::IPv4(Ipv4Addr::new(10, 0, 1, 1)), false))
+}
+
+#[test]
+fn it_transfers_messages_in_both_directions() {
+    let (a, b) = stream_pair();
+    assert!(a.outbound());
+    assert_eq!(b.net_address(), &NetAddress::IPv4(Ipv4Addr::new(10, 0, 1, 1)));
+
+    let a = a.send(Message::Ping(42)).wait().unwrap();
+    let (msg, b) = b.into_future().wait().map_err(|(e, _)| e).unwrap();
+    match msg { Some(Message::Ping(nonce)) => assert_eq!(nonce, 42), _ => assert!(false) };
+
+    let b = b.send(Message::Pong(42)).wait().unwrap();
+    let (msg, a) = a.into_future().wait().map_err(|(e, _)| e).unwrap();
+    match msg { Some(Message::Pong(nonce)) => assert_eq!(nonce, 42), _ => assert!(false) };
+
+    // Closing one end ends the other.
+    drop(b);
+    let (msg, _) = a.into_future().wait().map_err(|(e, _)| e).unwrap();
+    assert!(msg.is_none());
+}
+
+#[test]
+fn it_transfers_messages_larger_than_a_chunk() {
+    // The transport doesn't care whether the block is valid.
+    let mut block = get_network_info(NetworkId::Dummy).unwrap().genesis_block.clone();
+    block.body.as_mut().unwrap().transactions = (0..200).map(|i| {
+        Transaction::new_basic(Address::from([1u8; Address::SIZE]), Address::from([2u8; Address::SIZE]), Coin::from(i + 1), Coin::ZERO, 1, NetworkId::Dummy)
+    }).collect();
+
+    let (a, b) = stream_pair();
+    let _a = a.send(Message::Block(block.clone())).wait().unwrap();
+    let (msg, _) = b.into_future().wait().map_err(|(e, _)| e).unwrap();
+    match msg { Some(Message::Block(received)) => assert_eq!(received, block), _ => assert!(false) };
+}
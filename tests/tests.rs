@@ -8,6 +8,8 @@ extern crate bigdecimal;
 extern crate num_traits;
 extern crate num_bigint;
 extern crate pretty_env_logger;
+extern crate parking_lot;
+extern crate tempdir;
 
 use nimiq::consensus::base::account::PrunedAccount;
 use nimiq::consensus::base::block::{Block, BlockHeader, BlockBody, Difficulty, TargetCompact};